@@ -0,0 +1,86 @@
+use crate::config::{self, AppConfig, Severity};
+use crate::util::error::AppError;
+use clap::ValueEnum;
+use std::path::{Path, PathBuf};
+
+/// Output format for `watt config dump`
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum DumpFormat {
+    Toml,
+    Json,
+}
+
+/// Validates configuration files and prints a diagnostic for every problem found.
+///
+/// Without an explicit path, the base file and every drop-in fragment Watt would
+/// load are checked, starting from `config_path` if one was selected. With
+/// `no_config`, Watt loads no files, so an explicit path is required.
+pub fn run_check(
+    path: Option<&Path>,
+    config_path: Option<&Path>,
+    no_config: bool,
+) -> Result<(), AppError> {
+    let paths: Vec<PathBuf> = match path {
+        Some(path) => vec![path.to_path_buf()],
+        None if no_config => {
+            return Err(AppError::Generic(
+                "--no-config ignores every configuration file, pass the path of a file to check"
+                    .to_string(),
+            ));
+        }
+        None => config::find_config_layers(config_path),
+    };
+
+    if paths.is_empty() {
+        return Err(AppError::Generic(
+            "No configuration file found to check".to_string(),
+        ));
+    }
+
+    let (mut errors, mut warnings) = (0, 0);
+    for path in &paths {
+        let diagnostics = config::check_config_file(path)?;
+        for diagnostic in &diagnostics {
+            println!("{diagnostic}");
+        }
+
+        let file_errors = diagnostics
+            .iter()
+            .filter(|d| d.severity == Severity::Error)
+            .count();
+        errors += file_errors;
+        warnings += diagnostics.len() - file_errors;
+
+        if file_errors == 0 {
+            println!("{}: OK", path.display());
+        }
+    }
+
+    if errors > 0 {
+        return Err(AppError::Generic(format!(
+            "Configuration has {errors} error(s) and {warnings} warning(s)"
+        )));
+    }
+
+    println!("Configuration is valid ({warnings} warning(s))");
+    Ok(())
+}
+
+/// Prints the effective configuration with the source of every value, or a
+/// fully commented reference configuration if `defaults` is set
+pub fn run_dump(config: &AppConfig, format: DumpFormat, defaults: bool) -> Result<(), AppError> {
+    let output = if defaults {
+        config::reference_config()?
+    } else {
+        match format {
+            DumpFormat::Toml => config::render_toml(config)?,
+            DumpFormat::Json => config::render_json(config)?,
+        }
+    };
+
+    print!("{output}");
+    if !output.ends_with('\n') {
+        println!();
+    }
+    Ok(())
+}
@@ -1,6 +1,9 @@
 use crate::config::AppConfig;
 use crate::cpu;
+use crate::governor;
 use crate::monitor;
+use crate::profile;
+use crate::pstate;
 use crate::util::error::AppError;
 use std::fs;
 use std::process::{Command, Stdio};
@@ -44,8 +47,52 @@ pub fn run_debug(config: &AppConfig) -> Result<(), AppError> {
             );
 
             println!("\n--- CONFIGURATION ---");
+            if config.sources.layers().is_empty() {
+                println!("Configuration Layers: None (using defaults)");
+            } else {
+                println!("Configuration Layers (lowest precedence first):");
+                for (index, layer) in config.sources.layers().iter().enumerate() {
+                    println!("  {}. {layer}", index + 1);
+                }
+            }
             println!("Current Configuration: {config:#?}");
 
+            println!("\n--- PROFILES ---");
+            println!(
+                "Available Profiles: {}",
+                config.profile_names().collect::<Vec<_>>().join(", ")
+            );
+            let selection = profile::select_profile(&report, config);
+            println!(
+                "Selected Profile: {} ({})",
+                selection.name, selection.reason
+            );
+            println!(
+                "Active Schedule: {}",
+                selection.schedule.as_deref().unwrap_or("None")
+            );
+            println!(
+                "Active Process Trigger: {}",
+                selection.process_trigger.as_deref().unwrap_or("None")
+            );
+            println!(
+                "Active Battery Tiers: {}",
+                if selection.battery_tiers.is_empty() {
+                    "None".to_string()
+                } else {
+                    selection
+                        .battery_tiers
+                        .iter()
+                        .map(|below| format!("below {below}%"))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                }
+            );
+            println!(
+                "Profile Override: {}",
+                profile::get_profile_override().as_deref().unwrap_or("None")
+            );
+
             // Print important sysfs paths and whether they exist
             println!("\n--- SYSFS PATHS ---");
             check_and_print_sysfs_path(
@@ -64,6 +111,7 @@ pub fn run_debug(config: &AppConfig) -> Result<(), AppError> {
                 "/sys/firmware/acpi/platform_profile",
                 "ACPI Platform Profile Control",
             );
+            check_and_print_sysfs_path("/sys/devices/system/cpu/smt/control", "SMT Control");
             check_and_print_sysfs_path("/sys/class/power_supply", "Power Supply Information");
 
             println!("\n--- CPU INFORMATION ---");
@@ -78,6 +126,8 @@ pub fn run_debug(config: &AppConfig) -> Result<(), AppError> {
                 report.cpu_global.epp
             );
             println!("Energy Performance Bias (EPB): {:?}", report.cpu_global.epb);
+            println!("SMT Control: {:?}", report.cpu_global.smt_control);
+            println!("SMT Active: {:?}", report.cpu_global.smt_active);
 
             // Add governor override information
             if let Some(override_governor) = cpu::get_governor_override() {
@@ -96,8 +146,67 @@ pub fn run_debug(config: &AppConfig) -> Result<(), AppError> {
                 Err(_) => println!("Available Platform Profiles: Not supported on this system"),
             }
 
+            println!("\n--- GOVERNOR TUNABLES ---");
+            let tunables = governor::get_governor_tunables();
+            if tunables.is_empty() {
+                println!("No governor with tunables Watt manages is in use");
+            }
+            for tunables in tunables {
+                let values: Vec<String> = tunables
+                    .values
+                    .into_iter()
+                    .map(|(tunable, value)| {
+                        format!("{tunable}={}", value.as_deref().unwrap_or("N/A"))
+                    })
+                    .collect();
+                match tunables.policy {
+                    Some(policy) => println!(
+                        "{} (policy {policy}): {}",
+                        tunables.governor,
+                        values.join(", ")
+                    ),
+                    None => println!("{}: {}", tunables.governor, values.join(", ")),
+                }
+            }
+
+            println!("\n--- INTEL P-STATE ---");
+            if pstate::intel_pstate_available() {
+                for (knob, value) in pstate::get_intel_pstate_knobs() {
+                    match value {
+                        Some(value) => println!("{knob}: {value}"),
+                        None => println!("{knob}: Not available"),
+                    }
+                }
+            } else {
+                println!("intel_pstate driver: Not in use on this system");
+            }
+
+            println!("\n--- AMD P-STATE ---");
+            match pstate::get_amd_pstate_status() {
+                Some(status) => {
+                    println!("Status: {status}");
+                    println!(
+                        "Preferred Core Ranking: {}",
+                        pstate::get_amd_pstate_prefcore()
+                            .as_deref()
+                            .unwrap_or("Unknown")
+                    );
+                    for (policy, knobs) in pstate::get_amd_pstate_policy_knobs() {
+                        let values: Vec<String> = knobs
+                            .into_iter()
+                            .map(|(knob, value)| {
+                                format!("{knob}={}", value.as_deref().unwrap_or("N/A"))
+                            })
+                            .collect();
+                        println!("Policy {policy}: {}", values.join(", "));
+                    }
+                }
+                None => println!("amd_pstate driver: Not in use on this system"),
+            }
+
             println!("\n--- CPU CORES DETAIL ---");
-            println!("Total CPU Cores: {}", report.cpu_cores.len());
+            // Offline cores, e.g. SMT siblings or parked cores, are not listed
+            println!("Online CPU Cores: {}", report.cpu_cores.len());
             for core in &report.cpu_cores {
                 println!("Core {}:", core.core_id);
                 println!(
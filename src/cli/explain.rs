@@ -0,0 +1,191 @@
+use crate::config::AppConfig;
+use crate::monitor;
+use crate::profile;
+use crate::rules::RuleInputs;
+use crate::util::error::AppError;
+
+/// Prints which profile would be applied right now and why, including how
+/// every rule compares against the current system state
+pub fn run_explain(config: &AppConfig) -> Result<(), AppError> {
+    let report = monitor::collect_system_report(config)?;
+    let inputs = RuleInputs::from_report(&report);
+    let selection = profile::select_profile(&report, config);
+
+    println!("Selected Profile: {}", selection.name);
+    println!("Reason:           {}", selection.reason);
+    if let Some(name) = profile::get_profile_override() {
+        println!("Profile Override: {name}");
+    }
+    if !config.schedules.is_empty() {
+        println!(
+            "Schedule:         {}",
+            selection.schedule.as_deref().unwrap_or("None active")
+        );
+    }
+    if !config.process_triggers.is_empty() {
+        println!(
+            "Process Trigger:  {}",
+            selection
+                .process_trigger
+                .as_deref()
+                .unwrap_or("None active")
+        );
+    }
+    if !config.battery_tiers.is_empty() {
+        println!(
+            "Battery Tiers:    {}",
+            if selection.battery_tiers.is_empty() {
+                "None active".to_string()
+            } else {
+                selection
+                    .battery_tiers
+                    .iter()
+                    .map(|below| format!("below {below}%"))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            }
+        );
+    }
+
+    println!("\nSystem State:");
+    println!(
+        "  Power Source:       {}",
+        if inputs.on_ac { "AC" } else { "Battery" }
+    );
+    println!(
+        "  Battery:            {}",
+        inputs
+            .battery_percent
+            .map_or_else(|| "N/A".to_string(), |c| format!("{c}%"))
+    );
+    println!(
+        "  CPU Temperature:    {}",
+        inputs
+            .temperature
+            .map_or_else(|| "N/A".to_string(), |t| format!("{t:.1}°C"))
+    );
+    println!(
+        "  CPU Usage:          {}",
+        inputs
+            .cpu_usage
+            .map_or_else(|| "N/A".to_string(), |u| format!("{u:.1}%"))
+    );
+    println!("  Load Average (1m):  {:.2}", inputs.load_avg_1min);
+
+    if !config.schedules.is_empty() {
+        let now = jiff::Zoned::now();
+        println!(
+            "\nSchedules (first active one applies), local time {}:",
+            now.strftime("%a %H:%M")
+        );
+        for schedule in &config.schedules {
+            let days = if schedule.days.is_empty() {
+                "every day".to_string()
+            } else {
+                schedule
+                    .days
+                    .iter()
+                    .map(|day| format!("{day:?}").to_lowercase())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            };
+            println!(
+                "  {} ({days}, {}-{}){}: {}",
+                schedule.name.as_deref().unwrap_or_default(),
+                schedule.from,
+                schedule.to,
+                schedule
+                    .profile
+                    .as_ref()
+                    .map_or_else(String::new, |profile| format!(" -> {profile}")),
+                if schedule.is_active(now.datetime()) {
+                    "active"
+                } else {
+                    "inactive"
+                }
+            );
+        }
+    }
+
+    if !config.process_triggers.is_empty() {
+        println!("\nProcess Triggers (first active one applies):");
+        for (trigger, status) in config.process_triggers.iter().zip(&selection.processes) {
+            let mut details = trigger.processes.join(", ");
+            if let Some(cpu_above) = trigger.cpu_above {
+                details.push_str(&format!(", cpu_above {cpu_above}%"));
+            }
+            println!(
+                "  {} ({details}){}: {}",
+                trigger.name.as_deref().unwrap_or_default(),
+                trigger
+                    .profile
+                    .as_ref()
+                    .map_or_else(String::new, |profile| format!(" -> {profile}")),
+                if status.active { "active" } else { "inactive" }
+            );
+
+            if status.processes.is_empty() {
+                println!("      (no matching processes)");
+                continue;
+            }
+            for process in &status.processes {
+                println!(
+                    "      {:<24} pid {:<8} {}",
+                    process.name,
+                    process.pid,
+                    process
+                        .cpu_percent
+                        .map_or_else(|| "N/A".to_string(), |usage| format!("{usage:.1}% CPU"))
+                );
+            }
+            if let Some(usage) = status.cpu_percent {
+                println!("      Combined CPU usage: {usage:.1}%");
+            }
+        }
+    }
+
+    if config.rules.is_empty() {
+        println!("\nNo rules configured.");
+        return Ok(());
+    }
+
+    println!("\nRules (in evaluation order):");
+    let mut matched = false;
+    for rule in &config.rules {
+        let checks = rule.when.check(&inputs);
+        let holds = checks.iter().all(|check| check.holds);
+        let name = rule.name.as_deref().unwrap_or_default();
+
+        let status = match (holds, matched) {
+            (true, false) => {
+                matched = true;
+                "MATCHED"
+            }
+            (true, true) => "matches, shadowed by an earlier rule",
+            (false, _) => "no match",
+        };
+
+        let mut details = format!("priority {}", rule.priority);
+        if rule.debounce_sec > 0 {
+            details.push_str(&format!(", debounce {}s", rule.debounce_sec));
+        }
+        println!("  {name} -> {} ({details}): {status}", rule.profile);
+
+        if checks.is_empty() {
+            println!("      (no conditions, always matches)");
+        }
+        for check in checks {
+            println!(
+                "      {:<24} {} ({})",
+                check.condition,
+                if check.holds { "holds" } else { "fails" },
+                check.actual.as_deref().unwrap_or("unknown")
+            );
+        }
+    }
+
+    println!(
+        "\nNote: debounce and hold durations, idle_for_sec and discharge_above are only tracked by the daemon."
+    );
+    Ok(())
+}
@@ -1 +1,4 @@
+pub mod config;
 pub mod debug;
+pub mod explain;
+pub mod profile;
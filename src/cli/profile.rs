@@ -0,0 +1,99 @@
+use crate::config::AppConfig;
+use crate::control::{self, Request};
+use crate::engine;
+use crate::monitor;
+use crate::profile;
+use crate::util::error::{AppError, EngineError};
+
+/// Prints every configured profile, marking the one that would be applied now
+pub fn run_list(config: &AppConfig) -> Result<(), AppError> {
+    let report = monitor::collect_system_report(config)?;
+    let selection = profile::select_profile(&report, config);
+
+    for name in config.profile_names() {
+        if name == selection.name {
+            println!("* {name} ({})", selection.reason);
+        } else {
+            println!("  {name}");
+        }
+    }
+    Ok(())
+}
+
+/// Persistently selects a profile and applies it immediately. A running daemon
+/// is asked to do both through its control socket, so that only one process
+/// writes the settings.
+pub fn run_set(config: &AppConfig, name: &str) -> Result<(), AppError> {
+    let request = Request::SetProfile(name.to_string());
+    if !send_to_daemon(&request)? {
+        profile::set_profile_override(config, name)?;
+        apply_selected_profile(config)?;
+    }
+
+    println!("Profile override set to '{name}'. This setting will persist across reboots.");
+    println!("To return to automatic selection, use: watt profile reset");
+    Ok(())
+}
+
+/// Removes the profile override and re-applies the automatically selected profile
+pub fn run_reset(config: &AppConfig) -> Result<(), AppError> {
+    if profile::get_profile_override().is_none() {
+        println!("No profile override was set.");
+        return Ok(());
+    }
+
+    if !send_to_daemon(&Request::ResetProfile)? {
+        profile::clear_profile_override()?;
+        apply_selected_profile(config)?;
+    }
+
+    println!("Profile override has been reset. Normal profile selection will be used.");
+    Ok(())
+}
+
+/// Hands a request to the running daemon and prints its reply. Returns
+/// whether a daemon handled the request.
+fn send_to_daemon(request: &Request) -> Result<bool, AppError> {
+    let Some(reply) = control::send(request)? else {
+        return Ok(false);
+    };
+
+    if !reply.ok {
+        for line in &reply.lines {
+            eprintln!("{line}");
+        }
+        return Err(AppError::Generic(format!(
+            "The daemon could not handle '{request}'"
+        )));
+    }
+    for line in &reply.lines {
+        println!("{line}");
+    }
+    Ok(true)
+}
+
+/// Applies the profile selected now, printing the outcome of every
+/// setting. Only used without a daemon, as nothing coordinates these writes
+/// with a daemon's.
+fn apply_selected_profile(config: &AppConfig) -> Result<(), AppError> {
+    let report = monitor::collect_system_report(config)?;
+    let selection = profile::select_profile(&report, config);
+
+    let results = match engine::determine_and_apply_settings(&report, &selection) {
+        Ok(results) => results,
+        Err(e) => {
+            if let EngineError::RolledBack { results, .. } = &e {
+                for result in engine::reported(results) {
+                    eprintln!("  {result}");
+                }
+            }
+            return Err(e.into());
+        }
+    };
+
+    println!("Applied profile '{}':", selection.name);
+    for result in engine::reported(&results) {
+        println!("  {result}");
+    }
+    Ok(())
+}
@@ -0,0 +1,1367 @@
+// Configuration validation used by `watt config check`
+use std::fmt;
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use toml_edit::{ImDocument, Item, TableLike, Value};
+
+use crate::config::schema::{
+    AMD_PSTATE_KEYS, BATTERY_TIER_KEYS, CONDITION_KEYS, CONSERVATIVE_KEYS, CORE_TYPE_KEYS,
+    DAEMON_KEYS, INTEL_PSTATE_KEYS, KeyDoc, NAMED_PROFILE_KEYS, ONDEMAND_KEYS,
+    PROCESS_TRIGGER_KEYS, PROFILE_KEYS, PROFILE_OVERRIDE_KEYS, RENAMED_KEYS, RULE_KEYS,
+    SCHEDULE_KEYS, SCHEDUTIL_KEYS, THRESHOLD_KEYS, TOP_LEVEL_KEYS, TURBO_AUTO_KEYS,
+};
+use crate::config::types::{
+    AppConfigToml, BUILTIN_PROFILES, BatteryChargeThresholds, ConfigError, FrequencyLimit,
+};
+use crate::cpu;
+use crate::cpuidle;
+use crate::gpu;
+use crate::pstate;
+use crate::rapl;
+use crate::topology;
+use crate::util::error::ControlError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Error => write!(f, "error"),
+            Self::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// A single problem found in a configuration file
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub path: PathBuf,
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}: {}: {}",
+            self.path.display(),
+            self.line,
+            self.column,
+            self.severity,
+            self.message
+        )
+    }
+}
+
+/// Capabilities of the running machine that configuration values are checked against.
+/// A `None` capability could not be determined, and the related checks are skipped.
+#[derive(Default)]
+struct HardwareCaps {
+    governors: Option<Vec<String>>,
+    epp_values: Option<Vec<String>>,
+    platform_profiles: Option<Vec<String>>,
+    frequency_limits: Option<(u32, u32)>,
+    intel_pstate: bool,
+    amd_pstate: bool,
+    rapl: bool,
+    uncore_frequency: bool,
+    intel_gpu: bool,
+    /// Power profile modes of the amdgpu GPUs, `None` without amdgpu
+    amd_gpu_power_profiles: Option<Vec<String>>,
+    cpuidle_governors: Option<Vec<String>>,
+    /// Names of the idle states of the online cores
+    idle_states: Vec<String>,
+}
+
+impl HardwareCaps {
+    fn detect() -> Self {
+        Self {
+            governors: cpu::get_available_governors().ok(),
+            epp_values: cpu::get_available_epp_values().ok(),
+            platform_profiles: cpu::get_platform_profiles().ok(),
+            frequency_limits: cpu::get_hardware_frequency_limits().ok(),
+            intel_pstate: pstate::intel_pstate_available(),
+            amd_pstate: pstate::amd_pstate_available(),
+            rapl: rapl::rapl_available(),
+            uncore_frequency: !cpu::get_uncore_frequencies().is_empty(),
+            intel_gpu: gpu::get_intel_gpus().is_ok(),
+            amd_gpu_power_profiles: gpu::get_amd_gpu_power_profiles().ok(),
+            cpuidle_governors: cpuidle::get_available_cpuidle_governors().ok(),
+            idle_states: cpuidle::get_idle_states()
+                .into_iter()
+                .map(|state| state.name)
+                .collect(),
+        }
+    }
+}
+
+/// Check a configuration file for syntax errors, unknown keys, invalid values and
+/// settings that are not supported by this machine.
+///
+/// # Errors
+///
+/// Returns [`ConfigError::Io`] if the file cannot be read. Problems with the
+/// file's contents are reported as diagnostics instead.
+pub fn check_config_file(path: &Path) -> Result<Vec<Diagnostic>, ConfigError> {
+    let source = fs::read_to_string(path).map_err(ConfigError::Io)?;
+    let hardware = HardwareCaps::detect();
+
+    let mut checker = Checker {
+        path,
+        source: &source,
+        hardware: &hardware,
+        diagnostics: Vec::new(),
+    };
+    checker.check();
+
+    let mut diagnostics = checker.diagnostics;
+    diagnostics.sort_by_key(|d| (d.line, d.column));
+    Ok(diagnostics)
+}
+
+struct Checker<'a> {
+    path: &'a Path,
+    source: &'a str,
+    hardware: &'a HardwareCaps,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Checker<'_> {
+    fn check(&mut self) {
+        let document = match ImDocument::parse(self.source) {
+            Ok(document) => document,
+            Err(e) => {
+                self.push(Severity::Error, e.span(), e.message().to_string());
+                return;
+            }
+        };
+        let root = document.as_table();
+
+        self.check_keys(root, "", TOP_LEVEL_KEYS);
+
+        for profile in BUILTIN_PROFILES {
+            if let Some(table) = root.get(profile).and_then(Item::as_table_like) {
+                self.check_keys(table, profile, PROFILE_KEYS);
+                self.check_profile(profile, table);
+            }
+        }
+
+        if let Some(profiles) = root.get("profiles") {
+            self.check_named_profiles(root, profiles);
+        }
+
+        if let Some(rules) = root.get("rules") {
+            self.check_rules(root, rules);
+        }
+
+        if let Some(tiers) = root.get("battery_tiers") {
+            self.check_battery_tiers(root, tiers);
+        }
+
+        if let Some(schedules) = root.get("schedules") {
+            self.check_schedules(root, schedules);
+        }
+
+        if let Some(triggers) = root.get("process_triggers") {
+            self.check_process_triggers(root, triggers);
+        }
+
+        self.check_thresholds(root, "");
+
+        if let Some(table) = root.get("daemon").and_then(Item::as_table_like) {
+            self.check_daemon(table);
+        }
+
+        // Let serde catch type mismatches (e.g. a string where a number is expected)
+        if let Err(e) = toml::from_str::<AppConfigToml>(self.source) {
+            self.push(Severity::Error, e.span(), e.message().to_string());
+        }
+    }
+
+    fn check_keys(&mut self, table: &dyn TableLike, section: &str, known: &[KeyDoc]) {
+        self.check_keys_in(table, section, &known.iter().collect::<Vec<_>>());
+    }
+
+    /// Check for unknown keys in a section that accepts keys from several lists
+    fn check_keys_in(&mut self, table: &dyn TableLike, section: &str, known: &[&KeyDoc]) {
+        for (key, _) in table.iter() {
+            if known.iter().any(|doc| doc.name == key) {
+                continue;
+            }
+
+            let renamed = RENAMED_KEYS.iter().find(|(former, current)| {
+                *former == key && known.iter().any(|doc| doc.name == *current)
+            });
+            if let Some((former, current)) = renamed {
+                if table.contains_key(current) {
+                    self.push(
+                        Severity::Error,
+                        key_span(table, key),
+                        format!(
+                            "`{former}` and `{current}` are both set in {}, remove `{former}`",
+                            section_name(section)
+                        ),
+                    );
+                } else {
+                    self.push(
+                        Severity::Warning,
+                        key_span(table, key),
+                        format!("`{former}` is deprecated, use `{current}` instead"),
+                    );
+                }
+                continue;
+            }
+
+            let mut message = format!("unknown key `{key}` in {}", section_name(section));
+            if let Some(suggestion) = closest_match(key, known) {
+                message.push_str(&format!(", did you mean `{suggestion}`?"));
+            }
+            self.push(Severity::Error, key_span(table, key), message);
+        }
+    }
+
+    fn check_named_profiles(&mut self, root: &dyn TableLike, profiles: &Item) {
+        let Some(profiles) = profiles.as_table_like() else {
+            self.push(
+                Severity::Error,
+                value_span(profiles).or_else(|| key_span(root, "profiles")),
+                "`profiles` must be a table of named profiles".to_string(),
+            );
+            return;
+        };
+
+        for (name, profile) in profiles.iter() {
+            let section = format!("profiles.{name}");
+
+            if BUILTIN_PROFILES.contains(&name) {
+                self.push(
+                    Severity::Error,
+                    key_span(profiles, name),
+                    format!("[{section}] uses a reserved name, configure [{name}] instead"),
+                );
+                continue;
+            }
+
+            let Some(table) = profile.as_table_like() else {
+                self.push(
+                    Severity::Error,
+                    key_span(profiles, name),
+                    format!("[{section}] must be a table"),
+                );
+                continue;
+            };
+
+            let known: Vec<&KeyDoc> = PROFILE_KEYS.iter().chain(NAMED_PROFILE_KEYS).collect();
+            self.check_keys_in(table, &section, &known);
+            self.check_extends(name, table, profiles);
+            self.check_profile(&section, table);
+        }
+    }
+
+    /// Check that `extends` names a profile and does not lead to a cycle
+    fn check_extends(&mut self, name: &str, table: &dyn TableLike, profiles: &dyn TableLike) {
+        let Some((parent, span)) = string_value(table, "extends") else {
+            return;
+        };
+
+        // Follow the chain of `extends` until it ends at a profile without one
+        let mut chain = vec![name, parent];
+        let mut current = parent;
+        while !BUILTIN_PROFILES.contains(&current) {
+            let Some(profile) = profiles.get(current).and_then(Item::as_table_like) else {
+                // Fragments may extend profiles defined in another layer
+                self.push(
+                    Severity::Warning,
+                    span,
+                    format!(
+                        "profile `{current}` extended by [profiles.{name}] is not defined in this file"
+                    ),
+                );
+                return;
+            };
+            let Some((next, _)) = string_value(profile, "extends") else {
+                return;
+            };
+            if chain.contains(&next) {
+                chain.push(next);
+                self.push(
+                    Severity::Error,
+                    span,
+                    format!("profile inheritance cycle: {}", chain.join(" -> ")),
+                );
+                return;
+            }
+            chain.push(next);
+            current = next;
+        }
+    }
+
+    /// Get the entries of an array of tables, e.g. `[[rules]]`
+    fn array_of_tables<'t>(
+        &mut self,
+        root: &dyn TableLike,
+        key: &str,
+        item: &'t Item,
+    ) -> Option<Vec<&'t dyn TableLike>> {
+        if let Some(array) = item.as_array_of_tables() {
+            Some(array.iter().map(|table| table as &dyn TableLike).collect())
+        } else if let Some(array) = item.as_array() {
+            Some(
+                array
+                    .iter()
+                    .filter_map(Value::as_inline_table)
+                    .map(|table| table as &dyn TableLike)
+                    .collect(),
+            )
+        } else {
+            self.push(
+                Severity::Error,
+                value_span(item).or_else(|| key_span(root, key)),
+                format!("`{key}` must be an array of tables, written as [[{key}]]"),
+            );
+            None
+        }
+    }
+
+    fn check_battery_tiers(&mut self, root: &dyn TableLike, tiers: &Item) {
+        let Some(tiers) = self.array_of_tables(root, "battery_tiers", tiers) else {
+            return;
+        };
+
+        let known: Vec<&KeyDoc> = BATTERY_TIER_KEYS
+            .iter()
+            .chain(
+                PROFILE_KEYS
+                    .iter()
+                    .filter(|key| PROFILE_OVERRIDE_KEYS.contains(&key.name)),
+            )
+            .collect();
+
+        for tier in tiers {
+            let Some((below, span)) = integer_value(tier, "below") else {
+                // A missing `below` is reported by the serde pass
+                continue;
+            };
+            let section = format!("battery_tiers (below {below}%)");
+            self.check_keys_in(tier, &section, &known);
+
+            if !(1..=100).contains(&below) {
+                self.push(
+                    Severity::Error,
+                    span,
+                    format!("battery tier threshold ({below}) must be between 1 and 100"),
+                );
+            }
+
+            self.check_profile(&section, tier);
+        }
+    }
+
+    fn check_schedules(&mut self, root: &dyn TableLike, schedules: &Item) {
+        let Some(schedules) = self.array_of_tables(root, "schedules", schedules) else {
+            return;
+        };
+
+        let known: Vec<&KeyDoc> = SCHEDULE_KEYS
+            .iter()
+            .chain(
+                PROFILE_KEYS
+                    .iter()
+                    .filter(|key| PROFILE_OVERRIDE_KEYS.contains(&key.name)),
+            )
+            .collect();
+        let profiles = root.get("profiles").and_then(Item::as_table_like);
+
+        for (index, schedule) in schedules.into_iter().enumerate() {
+            let name = string_value(schedule, "name").map_or_else(
+                || format!("schedule #{}", index + 1),
+                |(name, _)| name.to_string(),
+            );
+            let section = format!("schedules ({name})");
+            self.check_keys_in(schedule, &section, &known);
+            self.check_profile_reference(schedule, profiles, &format!("schedule '{name}'"));
+            self.check_profile(&section, schedule);
+
+            // Invalid days and times are reported by the serde pass
+        }
+    }
+
+    fn check_process_triggers(&mut self, root: &dyn TableLike, triggers: &Item) {
+        let Some(triggers) = self.array_of_tables(root, "process_triggers", triggers) else {
+            return;
+        };
+
+        let known: Vec<&KeyDoc> = PROCESS_TRIGGER_KEYS
+            .iter()
+            .chain(
+                PROFILE_KEYS
+                    .iter()
+                    .filter(|key| PROFILE_OVERRIDE_KEYS.contains(&key.name)),
+            )
+            .collect();
+        let profiles = root.get("profiles").and_then(Item::as_table_like);
+
+        for (index, trigger) in triggers.into_iter().enumerate() {
+            let name = string_value(trigger, "name").map_or_else(
+                || format!("process trigger #{}", index + 1),
+                |(name, _)| name.to_string(),
+            );
+            let section = format!("process_triggers ({name})");
+            self.check_keys_in(trigger, &section, &known);
+
+            if let Some(processes) = trigger.get("processes") {
+                let empty = processes
+                    .as_array()
+                    .is_some_and(|array| array.iter().all(|p| p.as_str() == Some("")));
+                if empty {
+                    self.push(
+                        Severity::Error,
+                        value_span(processes),
+                        format!("process trigger '{name}' must list at least one process"),
+                    );
+                }
+            }
+
+            if let Some((percent, span)) = number_value(trigger, "cpu_above") {
+                if percent.is_nan() || percent < 0.0 {
+                    self.push(
+                        Severity::Error,
+                        span,
+                        format!(
+                            "cpu_above ({percent}) in process trigger '{name}' must not be negative"
+                        ),
+                    );
+                }
+            }
+
+            self.check_profile_reference(trigger, profiles, &format!("process trigger '{name}'"));
+            self.check_profile(&section, trigger);
+        }
+    }
+
+    /// Warn about a `profile` key naming a profile this file does not define
+    fn check_profile_reference(
+        &mut self,
+        table: &dyn TableLike,
+        profiles: Option<&dyn TableLike>,
+        owner: &str,
+    ) {
+        let Some((profile, span)) = string_value(table, "profile") else {
+            return;
+        };
+
+        let defined = BUILTIN_PROFILES.contains(&profile)
+            || profiles.is_some_and(|profiles| profiles.contains_key(profile));
+        if !defined {
+            // Fragments may select profiles defined in another layer
+            self.push(
+                Severity::Warning,
+                span,
+                format!("profile `{profile}` selected by {owner} is not defined in this file"),
+            );
+        }
+    }
+
+    fn check_rules(&mut self, root: &dyn TableLike, rules: &Item) {
+        let Some(tables) = self.array_of_tables(root, "rules", rules) else {
+            return;
+        };
+
+        let profiles = root.get("profiles").and_then(Item::as_table_like);
+
+        for (index, rule) in tables.into_iter().enumerate() {
+            let name = string_value(rule, "name").map_or_else(
+                || format!("rule #{}", index + 1),
+                |(name, _)| name.to_string(),
+            );
+            let section = format!("rules ({name})");
+            self.check_keys(rule, &section, RULE_KEYS);
+            self.check_profile_reference(rule, profiles, &format!("rule '{name}'"));
+
+            let Some(when) = rule.get("when").and_then(Item::as_table_like) else {
+                continue;
+            };
+            self.check_keys(when, &format!("{section}.when"), CONDITION_KEYS);
+
+            for key in ["battery_below", "battery_above"] {
+                if let Some((percent, span)) = integer_value(when, key) {
+                    if !(0..=100).contains(&percent) {
+                        self.push(
+                            Severity::Error,
+                            span,
+                            format!("{key} ({percent}) in rule '{name}' must be between 0 and 100"),
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    fn check_profile(&mut self, name: &str, table: &dyn TableLike) {
+        // An invalid turbo setting is reported by the serde pass
+        if let (Some((governor, span)), Some(available)) =
+            (string_value(table, "governor"), &self.hardware.governors)
+        {
+            if !available.iter().any(|g| g.eq_ignore_ascii_case(governor)) {
+                // The engine skips unavailable governors, so this is not fatal
+                self.push(
+                    Severity::Warning,
+                    span,
+                    format!(
+                        "governor `{governor}` in [{name}] is not available on this machine (available: {})",
+                        available.join(", ")
+                    ),
+                );
+            }
+        }
+
+        if let (Some((epp, span)), Some(available)) =
+            (string_value(table, "epp"), &self.hardware.epp_values)
+        {
+            if !available.iter().any(|v| v.eq_ignore_ascii_case(epp)) {
+                self.push(
+                    Severity::Error,
+                    span,
+                    format!(
+                        "EPP `{epp}` in [{name}] is not available on this machine (available: {})",
+                        available.join(", ")
+                    ),
+                );
+            }
+        }
+
+        if let Some((epb, span)) = string_value(table, "epb") {
+            if let Err(ControlError::InvalidValueError(message)) = cpu::validate_epb_value(epb) {
+                self.push(Severity::Error, span, format!("{message} in [{name}]"));
+            }
+        }
+
+        if let (Some((profile, span)), Some(available)) = (
+            string_value(table, "platform_profile"),
+            &self.hardware.platform_profiles,
+        ) {
+            if !available.iter().any(|p| p == profile) {
+                self.push(
+                    Severity::Error,
+                    span,
+                    format!(
+                        "platform profile `{profile}` in [{name}] is not available on this machine (available: {})",
+                        available.join(", ")
+                    ),
+                );
+            }
+        }
+
+        self.check_frequencies(name, table);
+        self.check_online_cores(name, table);
+        self.check_uncore_frequencies(name, table);
+        self.check_gpu_frequencies(name, table);
+        self.check_amd_gpu(name, table);
+        self.check_cpuidle(name, table);
+        self.check_power_limits(name, table);
+
+        if let Some(settings) = table
+            .get("turbo_auto_settings")
+            .and_then(Item::as_table_like)
+        {
+            self.check_turbo_auto_settings(name, settings);
+        }
+
+        for key in ["p_cores", "e_cores"] {
+            if let Some(settings) = table.get(key).and_then(Item::as_table_like) {
+                let section = format!("{name}.{key}");
+                self.check_keys(settings, &section, CORE_TYPE_KEYS);
+                self.check_profile(&section, settings);
+            }
+        }
+
+        if let Some(settings) = table.get("intel_pstate").and_then(Item::as_table_like) {
+            self.check_intel_pstate(name, settings);
+        }
+        if let Some(settings) = table.get("amd_pstate").and_then(Item::as_table_like) {
+            let section = format!("{name}.amd_pstate");
+            self.check_keys(settings, &section, AMD_PSTATE_KEYS);
+            if !self.hardware.amd_pstate {
+                self.warn_unused_driver(&section, settings, "amd_pstate");
+            }
+        }
+        self.check_governor_tunables(name, table);
+
+        self.check_thresholds(table, name);
+    }
+
+    /// Warn about settings for a scaling driver that is not in use
+    fn warn_unused_driver(&mut self, section: &str, table: &dyn TableLike, driver: &str) {
+        if let Some((_, item)) = table.iter().next() {
+            self.push(
+                Severity::Warning,
+                value_span(item),
+                format!("[{section}] has no effect, {driver} is not in use on this machine"),
+            );
+        }
+    }
+
+    fn check_governor_tunables(&mut self, name: &str, table: &dyn TableLike) {
+        // Tunables with the range the kernel accepts
+        type TunableRange = (&'static str, i64, i64);
+        let governors: [(&str, &[KeyDoc], &[TunableRange]); 3] = [
+            ("schedutil", SCHEDUTIL_KEYS, &[]),
+            (
+                "ondemand",
+                ONDEMAND_KEYS,
+                &[("up_threshold", 1, 100), ("powersave_bias", 0, 1000)],
+            ),
+            (
+                "conservative",
+                CONSERVATIVE_KEYS,
+                &[("freq_step", 0, 100), ("down_threshold", 1, 99)],
+            ),
+        ];
+
+        for (governor, keys, ranges) in governors {
+            let Some(settings) = table.get(governor).and_then(Item::as_table_like) else {
+                continue;
+            };
+            let section = format!("{name}.{governor}");
+            self.check_keys(settings, &section, keys);
+
+            for &(key, min, max) in ranges {
+                // Values that do not fit the type are reported by the serde pass
+                if let Some((value, span)) = integer_value(settings, key) {
+                    if value < min || value > max {
+                        self.push(
+                            Severity::Error,
+                            span,
+                            format!(
+                                "{key} ({value}) in [{section}] must be between {min} and {max}"
+                            ),
+                        );
+                    }
+                }
+            }
+
+            let available = self
+                .hardware
+                .governors
+                .as_ref()
+                .is_none_or(|governors| governors.iter().any(|g| g == governor));
+            if !available {
+                if let Some((_, item)) = settings.iter().next() {
+                    self.push(
+                        Severity::Warning,
+                        value_span(item),
+                        format!(
+                            "[{section}] has no effect, the {governor} governor is not available on this machine"
+                        ),
+                    );
+                }
+            }
+        }
+    }
+
+    fn check_intel_pstate(&mut self, name: &str, table: &dyn TableLike) {
+        let section = format!("{name}.intel_pstate");
+        self.check_keys(table, &section, INTEL_PSTATE_KEYS);
+
+        if !self.hardware.intel_pstate {
+            self.warn_unused_driver(&section, table, "intel_pstate");
+        }
+
+        let min = integer_value(table, "min_perf_pct");
+        let max = integer_value(table, "max_perf_pct");
+        // Values that do not fit a u8 are reported by the serde pass
+        for (key, value) in [("min_perf_pct", &min), ("max_perf_pct", &max)] {
+            if let Some((percent, span)) = value {
+                if (101..=i64::from(u8::MAX)).contains(percent) {
+                    self.push(
+                        Severity::Error,
+                        span.clone(),
+                        format!("{key} ({percent}) in [{section}] must be between 0 and 100"),
+                    );
+                }
+            }
+        }
+        if let (Some((min, span)), Some((max, _))) = (&min, &max) {
+            if min > max {
+                self.push(
+                    Severity::Error,
+                    span.clone(),
+                    format!(
+                        "min_perf_pct ({min}) is higher than max_perf_pct ({max}) in [{section}]"
+                    ),
+                );
+            }
+        }
+
+        if let (Some((status, _)), Some(item)) = (
+            string_value(table, "status"),
+            table.get("hwp_dynamic_boost"),
+        ) {
+            if status != "active" {
+                self.push(
+                    Severity::Warning,
+                    value_span(item),
+                    format!(
+                        "hwp_dynamic_boost in [{section}] is only available in active mode, not {status}"
+                    ),
+                );
+            }
+        }
+    }
+
+    fn check_frequencies(&mut self, name: &str, table: &dyn TableLike) {
+        let min_freq = frequency_value(table, "min_freq");
+        let max_freq = frequency_value(table, "max_freq");
+        let hardware = self.hardware.frequency_limits;
+
+        // Relative limits can only be compared once the hardware limits are known
+        let resolve = |limit: FrequencyLimit| match (limit, hardware) {
+            (FrequencyLimit::Mhz(mhz), _) => Some(mhz),
+            (FrequencyLimit::Percent(percent), Some((_, hw_max))) => {
+                Some((hw_max as f32 * percent / 100.0).round() as u32)
+            }
+            (FrequencyLimit::Min, Some((hw_min, _))) => Some(hw_min),
+            (FrequencyLimit::Max, Some((_, hw_max))) => Some(hw_max),
+            _ => None,
+        };
+
+        if let (Some((min, span)), Some((max, _))) = (&min_freq, &max_freq) {
+            if let (Some(min_mhz), Some(max_mhz)) = (resolve(*min), resolve(*max)) {
+                if min_mhz > max_mhz {
+                    self.push(
+                        Severity::Error,
+                        span.clone(),
+                        format!("min_freq ({min}) is higher than max_freq ({max}) in [{name}]"),
+                    );
+                }
+            }
+        }
+
+        let Some((hw_min, hw_max)) = hardware else {
+            return;
+        };
+
+        for (key, value) in [("min_freq", min_freq), ("max_freq", max_freq)] {
+            let Some((FrequencyLimit::Mhz(freq), span)) = value else {
+                continue;
+            };
+            if freq < hw_min || freq > hw_max {
+                // The engine clamps the limit to the hardware range
+                self.push(
+                    Severity::Warning,
+                    span,
+                    format!(
+                        "{key} ({freq} MHz) in [{name}] is outside the hardware range of {hw_min}-{hw_max} MHz and will be clamped"
+                    ),
+                );
+            }
+        }
+    }
+
+    fn check_uncore_frequencies(&mut self, name: &str, table: &dyn TableLike) {
+        let min_freq = integer_value(table, "uncore_min_freq_mhz");
+        let max_freq = integer_value(table, "uncore_max_freq_mhz");
+
+        if let (Some((min, span)), Some((max, _))) = (&min_freq, &max_freq) {
+            if min > max {
+                self.push(
+                    Severity::Error,
+                    span.clone(),
+                    format!(
+                        "uncore_min_freq_mhz ({min} MHz) is higher than uncore_max_freq_mhz ({max} MHz) in [{name}]"
+                    ),
+                );
+            }
+        }
+
+        if !self.hardware.uncore_frequency {
+            if let Some(item) = table
+                .get("uncore_min_freq_mhz")
+                .or_else(|| table.get("uncore_max_freq_mhz"))
+            {
+                self.push(
+                    Severity::Warning,
+                    value_span(item),
+                    format!(
+                        "uncore frequency limits in [{name}] have no effect, intel_uncore_frequency is not available on this machine"
+                    ),
+                );
+            }
+        }
+    }
+
+    fn check_gpu_frequencies(&mut self, name: &str, table: &dyn TableLike) {
+        let min_freq = integer_value(table, "gpu_min_freq_mhz");
+        let max_freq = integer_value(table, "gpu_max_freq_mhz");
+
+        if let (Some((min, span)), Some((max, _))) = (&min_freq, &max_freq) {
+            if min > max {
+                self.push(
+                    Severity::Error,
+                    span.clone(),
+                    format!(
+                        "gpu_min_freq_mhz ({min} MHz) is higher than gpu_max_freq_mhz ({max} MHz) in [{name}]"
+                    ),
+                );
+            }
+        }
+
+        if !self.hardware.intel_gpu {
+            let item = ["gpu_min_freq_mhz", "gpu_max_freq_mhz", "gpu_boost_freq_mhz"]
+                .into_iter()
+                .find_map(|key| table.get(key));
+            if let Some(item) = item {
+                self.push(
+                    Severity::Warning,
+                    value_span(item),
+                    format!(
+                        "GPU frequency limits in [{name}] have no effect, no Intel GPU with frequency controls was found"
+                    ),
+                );
+            }
+        }
+    }
+
+    fn check_amd_gpu(&mut self, name: &str, table: &dyn TableLike) {
+        if let Some((level, span)) = string_value(table, "gpu_dpm_level") {
+            if !gpu::AMDGPU_DPM_LEVELS.contains(&level) {
+                self.push(
+                    Severity::Error,
+                    span,
+                    format!(
+                        "GPU DPM level `{level}` in [{name}] is invalid (valid: {})",
+                        gpu::AMDGPU_DPM_LEVELS.join(", ")
+                    ),
+                );
+            }
+        }
+
+        let Some(available) = &self.hardware.amd_gpu_power_profiles else {
+            let item = ["gpu_dpm_level", "gpu_power_profile"]
+                .into_iter()
+                .find_map(|key| table.get(key));
+            if let Some(item) = item {
+                self.push(
+                    Severity::Warning,
+                    value_span(item),
+                    format!(
+                        "amdgpu settings in [{name}] have no effect, no amdgpu GPU with power management controls was found"
+                    ),
+                );
+            }
+            return;
+        };
+
+        if let Some((profile, span)) = string_value(table, "gpu_power_profile") {
+            let known = available.iter().any(|p| p.eq_ignore_ascii_case(profile))
+                || profile.parse::<u32>().is_ok();
+            if !available.is_empty() && !known {
+                self.push(
+                    Severity::Error,
+                    span,
+                    format!(
+                        "GPU power profile `{profile}` in [{name}] is not available on this machine (available: {})",
+                        available.join(", ")
+                    ),
+                );
+            }
+        }
+    }
+
+    fn check_cpuidle(&mut self, name: &str, table: &dyn TableLike) {
+        if let Some((governor, span)) = string_value(table, "cpuidle_governor") {
+            match &self.hardware.cpuidle_governors {
+                Some(available) if !available.iter().any(|g| g == governor) => self.push(
+                    Severity::Error,
+                    span,
+                    format!(
+                        "cpuidle governor `{governor}` in [{name}] is not available on this machine (available: {})",
+                        available.join(", ")
+                    ),
+                ),
+                Some(_) => {}
+                None => self.push(
+                    Severity::Warning,
+                    span,
+                    format!(
+                        "cpuidle_governor in [{name}] has no effect, the cpuidle governor cannot be switched on this machine"
+                    ),
+                ),
+            }
+        }
+
+        // Type mismatches are reported by the serde pass
+        let Some(states) = table.get("disabled_idle_states").and_then(Item::as_array) else {
+            return;
+        };
+        if self.hardware.idle_states.is_empty() {
+            self.push(
+                Severity::Warning,
+                states.span(),
+                format!(
+                    "disabled_idle_states in [{name}] has no effect, no idle states are exposed on this machine"
+                ),
+            );
+            return;
+        }
+        for state in states {
+            let Some(state_name) = state.as_str() else {
+                continue;
+            };
+            if !self
+                .hardware
+                .idle_states
+                .iter()
+                .any(|known| known.eq_ignore_ascii_case(state_name))
+            {
+                self.push(
+                    Severity::Error,
+                    state.span(),
+                    format!(
+                        "idle state `{state_name}` in [{name}] does not exist on this machine (available: {})",
+                        self.hardware.idle_states.join(", ")
+                    ),
+                );
+            }
+        }
+    }
+
+    fn check_power_limits(&mut self, name: &str, table: &dyn TableLike) {
+        let limits = [
+            ("package_power_limit_w", "package_power_window_sec"),
+            (
+                "package_power_limit_short_w",
+                "package_power_window_short_sec",
+            ),
+        ];
+
+        for (limit_key, window_key) in limits {
+            for key in [limit_key, window_key] {
+                if let Some((value, span)) = number_value(table, key) {
+                    if value <= 0.0 {
+                        self.push(
+                            Severity::Error,
+                            span,
+                            format!("{key} ({value}) in [{name}] must be greater than 0"),
+                        );
+                    }
+                }
+            }
+
+            if let (Some(item), None) = (table.get(window_key), table.get(limit_key)) {
+                self.push(
+                    Severity::Warning,
+                    value_span(item),
+                    format!("{window_key} in [{name}] has no effect without {limit_key}"),
+                );
+            }
+        }
+
+        if let (Some((long, _)), Some((short, span))) = (
+            number_value(table, "package_power_limit_w"),
+            number_value(table, "package_power_limit_short_w"),
+        ) {
+            if short < long {
+                self.push(
+                    Severity::Warning,
+                    span,
+                    format!(
+                        "package_power_limit_short_w ({short} W) is lower than package_power_limit_w ({long} W) in [{name}]"
+                    ),
+                );
+            }
+        }
+
+        if !self.hardware.rapl {
+            let key = limits
+                .iter()
+                .flat_map(|(limit, window)| [limit, window])
+                .find_map(|key| table.get(key));
+            if let Some(item) = key {
+                self.push(
+                    Severity::Warning,
+                    value_span(item),
+                    format!("package power limits in [{name}] have no effect, RAPL is not available on this machine"),
+                );
+            }
+        }
+    }
+
+    fn check_online_cores(&mut self, name: &str, table: &dyn TableLike) {
+        let Some(item) = table.get("online_cores") else {
+            return;
+        };
+        let span = value_span(item);
+
+        // Invalid counts and CPU lists are reported by the serde pass
+        let Some(cpus) = item
+            .as_str()
+            .and_then(topology::parse_cpu_list)
+            .filter(|cpus| !cpus.is_empty())
+        else {
+            return;
+        };
+
+        let present = topology::present_cpus();
+        let missing: Vec<u32> = cpus
+            .into_iter()
+            .filter(|cpu| !present.is_empty() && !present.contains(cpu))
+            .collect();
+        if !missing.is_empty() {
+            self.push(
+                Severity::Warning,
+                span,
+                format!(
+                    "online_cores in [{name}] lists cores that do not exist on this machine: {}",
+                    topology::format_cpu_list(&missing)
+                ),
+            );
+        }
+    }
+
+    fn check_turbo_auto_settings(&mut self, name: &str, table: &dyn TableLike) {
+        let section = format!("{name}.turbo_auto_settings");
+        self.check_keys(table, &section, TURBO_AUTO_KEYS);
+
+        let high = number_value(table, "load_threshold_high");
+        let low = number_value(table, "load_threshold_low");
+
+        for (key, value) in [("load_threshold_high", &high), ("load_threshold_low", &low)] {
+            if let Some((load, span)) = value {
+                if !(0.0..=100.0).contains(load) {
+                    self.push(
+                        Severity::Error,
+                        span.clone(),
+                        format!("{key} ({load}) in [{section}] must be between 0 and 100"),
+                    );
+                }
+            }
+        }
+
+        if let (Some((high, span)), Some((low, _))) = (&high, &low) {
+            if high <= low {
+                self.push(
+                    Severity::Error,
+                    span.clone(),
+                    format!(
+                        "load_threshold_high ({high}) must be greater than load_threshold_low ({low}) in [{section}]"
+                    ),
+                );
+            }
+        }
+
+        if let Some((temp, span)) = number_value(table, "temp_threshold_high") {
+            if temp <= 0.0 || temp > 110.0 {
+                self.push(
+                    Severity::Error,
+                    span,
+                    format!(
+                        "temp_threshold_high ({temp}) in [{section}] must be between 0°C and 110°C"
+                    ),
+                );
+            }
+        }
+    }
+
+    /// Check `battery_charge_thresholds`, which may be written as `[start, stop]`
+    /// or as a `{ start, stop }` table
+    fn check_thresholds(&mut self, table: &dyn TableLike, section: &str) {
+        let Some(value) = table.get("battery_charge_thresholds") else {
+            return;
+        };
+
+        let (start, stop) = if let Some(array) = value.as_array() {
+            (
+                array.get(0).and_then(Value::as_integer),
+                array.get(1).and_then(Value::as_integer),
+            )
+        } else if let Some(thresholds) = value.as_table_like() {
+            self.check_keys(
+                thresholds,
+                &format!("{section}.battery_charge_thresholds"),
+                THRESHOLD_KEYS,
+            );
+            (
+                thresholds.get("start").and_then(Item::as_integer),
+                thresholds.get("stop").and_then(Item::as_integer),
+            )
+        } else {
+            // Type mismatches are reported by the serde pass
+            return;
+        };
+
+        let (Some(start), Some(stop)) = (start, stop) else {
+            return;
+        };
+        let span = value_span(value).or_else(|| key_span(table, "battery_charge_thresholds"));
+
+        match (u8::try_from(start), u8::try_from(stop)) {
+            (Ok(start), Ok(stop)) => {
+                if let Err(ConfigError::Validation(message)) =
+                    BatteryChargeThresholds::new(start, stop)
+                {
+                    self.push(
+                        Severity::Error,
+                        span,
+                        format!("{message} in {}", section_name(section)),
+                    );
+                }
+            }
+            _ => self.push(
+                Severity::Error,
+                span,
+                format!(
+                    "battery charge thresholds in {} must be between 0 and 100",
+                    section_name(section)
+                ),
+            ),
+        }
+    }
+
+    fn check_daemon(&mut self, table: &dyn TableLike) {
+        self.check_keys(table, "daemon", DAEMON_KEYS);
+
+        if let Some((interval, span)) = integer_value(table, "poll_interval_sec") {
+            if interval == 0 {
+                self.push(
+                    Severity::Warning,
+                    span,
+                    "poll_interval_sec is 0, the daemon will use a 1s minimum".to_string(),
+                );
+            }
+        }
+
+        if let Some((interval, span)) = integer_value(table, "min_poll_interval_sec") {
+            if interval < 1 {
+                self.push(
+                    Severity::Error,
+                    span,
+                    "min_poll_interval_sec must be at least 1".to_string(),
+                );
+            }
+        }
+
+        if let (Some((min, span)), Some((max, _))) = (
+            integer_value(table, "min_poll_interval_sec"),
+            integer_value(table, "max_poll_interval_sec"),
+        ) {
+            if min > max {
+                self.push(
+                    Severity::Error,
+                    span,
+                    format!(
+                        "min_poll_interval_sec ({min}) is greater than max_poll_interval_sec ({max})"
+                    ),
+                );
+            }
+        }
+    }
+
+    fn push(&mut self, severity: Severity, span: Option<Range<usize>>, message: String) {
+        let (line, column) = span.map_or((1, 1), |span| line_column(self.source, span.start));
+
+        self.diagnostics.push(Diagnostic {
+            severity,
+            path: self.path.to_path_buf(),
+            line,
+            column,
+            message,
+        });
+    }
+}
+
+/// Human readable name for a section path, `""` being the top level
+fn section_name(section: &str) -> String {
+    if section.is_empty() {
+        "the top level".to_string()
+    } else {
+        format!("[{section}]")
+    }
+}
+
+fn key_span(table: &dyn TableLike, key: &str) -> Option<Range<usize>> {
+    table.get_key_value(key).and_then(|(key, _)| key.span())
+}
+
+fn value_span(item: &Item) -> Option<Range<usize>> {
+    match item {
+        Item::Value(value) => value.span(),
+        Item::Table(table) => table.span(),
+        Item::ArrayOfTables(array) => array.span(),
+        Item::None => None,
+    }
+}
+
+fn string_value<'t>(
+    table: &'t dyn TableLike,
+    key: &str,
+) -> Option<(&'t str, Option<Range<usize>>)> {
+    let item = table.get(key)?;
+    Some((item.as_str()?, value_span(item)))
+}
+
+fn integer_value(table: &dyn TableLike, key: &str) -> Option<(i64, Option<Range<usize>>)> {
+    let item = table.get(key)?;
+    Some((item.as_integer()?, value_span(item)))
+}
+
+/// A frequency limit in MHz or as a string such as `"70%"`, also under the
+/// key's former name
+fn frequency_value(
+    table: &dyn TableLike,
+    key: &str,
+) -> Option<(FrequencyLimit, Option<Range<usize>>)> {
+    let item = table.get(key).or_else(|| {
+        RENAMED_KEYS
+            .iter()
+            .find(|(_, current)| *current == key)
+            .and_then(|(former, _)| table.get(former))
+    })?;
+    let limit = match item.as_integer() {
+        Some(mhz) => mhz.to_string().parse().ok()?,
+        None => item.as_str()?.parse().ok()?,
+    };
+    Some((limit, value_span(item)))
+}
+
+/// Numbers may be written as integers or floats where the config expects a float
+fn number_value(table: &dyn TableLike, key: &str) -> Option<(f64, Option<Range<usize>>)> {
+    let item = table.get(key)?;
+    let number = item
+        .as_float()
+        .or_else(|| item.as_integer().map(|i| i as f64))?;
+    Some((number, value_span(item)))
+}
+
+/// Convert a byte offset into a 1-based line and column
+fn line_column(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset.min(source.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before
+        .rfind('\n')
+        .map_or(before, |newline| &before[newline + 1..])
+        .chars()
+        .count()
+        + 1;
+    (line, column)
+}
+
+/// Suggest the known key closest to a misspelled one, if any is close enough
+fn closest_match(key: &str, known: &[&KeyDoc]) -> Option<&'static str> {
+    known
+        .iter()
+        .map(|doc| (edit_distance(key, doc.name), doc.name))
+        .filter(|(distance, _)| *distance <= 3)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// Levenshtein distance between two strings
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Check a config without comparing it against the hardware
+    fn check_source(source: &str) -> Vec<Diagnostic> {
+        let hardware = HardwareCaps::default();
+        let mut checker = Checker {
+            path: Path::new("test.toml"),
+            source,
+            hardware: &hardware,
+            diagnostics: Vec::new(),
+        };
+        checker.check();
+        checker.diagnostics
+    }
+
+    #[test]
+    fn edit_distance_counts_single_edits() {
+        assert_eq!(edit_distance("turbo", "turbo"), 0);
+        assert_eq!(edit_distance("tubro", "turbo"), 2);
+        assert_eq!(edit_distance("turb", "turbo"), 1);
+        assert_eq!(edit_distance("governer", "governor"), 1);
+        assert_eq!(edit_distance("", "epp"), 3);
+        assert_eq!(edit_distance("epp", ""), 3);
+    }
+
+    #[test]
+    fn closest_match_suggests_nearby_keys_only() {
+        let known: Vec<&KeyDoc> = PROFILE_KEYS.iter().collect();
+        assert_eq!(closest_match("governer", &known), Some("governor"));
+        assert_eq!(closest_match("tubro", &known), Some("turbo"));
+        assert_eq!(closest_match("completely_unrelated", &known), None);
+    }
+
+    #[test]
+    fn line_column_is_one_based() {
+        let source = "[charger]\nturbo = \"auto\"\n";
+        assert_eq!(line_column(source, 0), (1, 1));
+        assert_eq!(line_column(source, 10), (2, 1));
+        assert_eq!(line_column(source, 16), (2, 7));
+    }
+
+    #[test]
+    fn unknown_key_reports_line_and_suggestion() {
+        let diagnostics = check_source("[charger]\ngoverner = \"powersave\"\n");
+        assert_eq!(diagnostics.len(), 1);
+        let diagnostic = &diagnostics[0];
+        assert_eq!(diagnostic.severity, Severity::Error);
+        assert_eq!((diagnostic.line, diagnostic.column), (2, 1));
+        assert!(diagnostic.message.contains("did you mean `governor`?"));
+    }
+
+    #[test]
+    fn valid_config_has_no_diagnostics() {
+        let diagnostics = check_source("[charger]\ngovernor = \"performance\"\nturbo = \"auto\"\n");
+        assert!(diagnostics.is_empty(), "{diagnostics:?}");
+    }
+
+    #[test]
+    fn invalid_turbo_is_reported_once() {
+        let diagnostics = check_source("[charger]\nturbo = \"sometimes\"\n");
+        assert_eq!(diagnostics.len(), 1, "{diagnostics:?}");
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+        assert_eq!((diagnostics[0].line, diagnostics[0].column), (2, 9));
+    }
+
+    #[test]
+    fn former_key_names_are_deprecated() {
+        let diagnostics = check_source("[battery]\nmax_freq_mhz = 2000\n");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Severity::Warning);
+        assert!(diagnostics[0].message.contains("use `max_freq` instead"));
+
+        let diagnostics = check_source("[battery]\nmax_freq_mhz = 2000\nmax_freq = 1800\n");
+        assert!(
+            diagnostics
+                .iter()
+                .any(|d| d.severity == Severity::Error && d.message.contains("are both set")),
+            "{diagnostics:?}"
+        );
+    }
+}
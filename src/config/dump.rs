@@ -0,0 +1,192 @@
+// Rendering of the effective configuration used by `watt config dump`
+use serde_json::{Map, Value};
+
+use crate::config::schema::{KeyKind, section_keys};
+use crate::config::types::{AppConfig, ConfigError, ConfigSources};
+
+/// Render the effective configuration as TOML, annotating every value with
+/// where it came from
+pub fn render_toml(config: &AppConfig) -> Result<String, ConfigError> {
+    let Value::Object(root) = serde_json::to_value(config)? else {
+        unreachable!("AppConfig always serializes to a map");
+    };
+
+    let mut out = String::new();
+    if config.sources.layers().is_empty() {
+        out.push_str("# No configuration files loaded, using built-in defaults\n\n");
+    } else {
+        out.push_str("# Configuration layers, lowest precedence first:\n");
+        for layer in config.sources.layers() {
+            out.push_str(&format!("#   {layer}\n"));
+        }
+        out.push('\n');
+    }
+    render_toml_table(&mut out, "", &root, &config.sources);
+    Ok(out)
+}
+
+/// Render the effective configuration as JSON, wrapping every value as
+/// `{ "value": ..., "source": ... }`
+pub fn render_json(config: &AppConfig) -> Result<String, ConfigError> {
+    let annotated = annotate_json("", serde_json::to_value(config)?, &config.sources);
+    Ok(serde_json::to_string_pretty(&annotated)?)
+}
+
+/// Render a reference configuration that documents every key, with each
+/// setting commented out and showing its default value (or an example)
+pub fn reference_config() -> Result<String, ConfigError> {
+    let defaults = serde_json::to_value(AppConfig::default())?;
+
+    let mut out = String::from(
+        "# Watt reference configuration\n\
+         #\n\
+         # Every setting is commented out and shows its default value, or an\n\
+         # example if it has no default. Uncomment the ones you want to change.\n",
+    );
+    render_reference_section(&mut out, "", &defaults);
+    Ok(out)
+}
+
+fn render_toml_table(
+    out: &mut String,
+    path: &str,
+    table: &Map<String, Value>,
+    sources: &ConfigSources,
+) {
+    // Values must come before any sub-tables in TOML
+    for (key, value) in table.iter().filter(|(_, value)| !value.is_object()) {
+        let key_path = join_path(path, key);
+        if value.is_null() {
+            out.push_str(&format!("# {} is not set\n", toml_key(key)));
+        } else {
+            out.push_str(&format!(
+                "{} = {}  # {}\n",
+                toml_key(key),
+                toml_literal(value),
+                sources.source_of(&key_path)
+            ));
+        }
+    }
+
+    for (key, value) in table {
+        if let Value::Object(subtable) = value {
+            let key_path = join_path(path, key);
+            out.push_str(&format!("\n[{}]\n", toml_path(&key_path)));
+            render_toml_table(out, &key_path, subtable, sources);
+        }
+    }
+}
+
+fn annotate_json(path: &str, value: Value, sources: &ConfigSources) -> Value {
+    match value {
+        Value::Object(table) => Value::Object(
+            table
+                .into_iter()
+                .map(|(key, value)| {
+                    let annotated = annotate_json(&join_path(path, &key), value, sources);
+                    (key, annotated)
+                })
+                .collect(),
+        ),
+        value => serde_json::json!({
+            "value": value,
+            "source": sources.source_of(path).to_string(),
+        }),
+    }
+}
+
+fn render_reference_section(out: &mut String, path: &str, defaults: &Value) {
+    let Some(keys) = section_keys(path) else {
+        return;
+    };
+
+    for key in keys {
+        let KeyKind::Value { example } = key.kind else {
+            continue;
+        };
+
+        let default = lookup(defaults, &join_path(path, key.name)).filter(|v| !v.is_null());
+        out.push_str(&format!("\n# {}\n", key.description));
+        out.push_str(&format!(
+            "# {} = {}\n",
+            key.name,
+            default.map_or_else(|| example.to_string(), toml_literal)
+        ));
+    }
+
+    for key in keys.iter().filter(|key| matches!(key.kind, KeyKind::Table)) {
+        let key_path = join_path(path, key.name);
+        out.push_str(&format!(
+            "\n# {}\n[{}]\n",
+            key.description,
+            toml_path(&key_path)
+        ));
+        render_reference_section(out, &key_path, defaults);
+    }
+
+    for key in keys {
+        if let KeyKind::Sections { example } = key.kind {
+            out.push_str(&format!("\n# {}\n", key.description));
+            for line in example.lines() {
+                out.push_str(&format!("# {line}\n"));
+            }
+        }
+    }
+}
+
+fn lookup<'v>(value: &'v Value, path: &str) -> Option<&'v Value> {
+    path.split('.').try_fold(value, |value, key| value.get(key))
+}
+
+fn join_path(prefix: &str, key: &str) -> String {
+    if prefix.is_empty() {
+        key.to_string()
+    } else {
+        format!("{prefix}.{key}")
+    }
+}
+
+/// Quote a key if it cannot be written as a bare TOML key
+fn toml_key(key: &str) -> String {
+    let is_bare = !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+
+    if is_bare {
+        key.to_string()
+    } else {
+        toml::Value::String(key.to_string()).to_string()
+    }
+}
+
+fn toml_path(path: &str) -> String {
+    path.split('.').map(toml_key).collect::<Vec<_>>().join(".")
+}
+
+/// Write a JSON value as a TOML literal, using inline tables for nested maps
+fn toml_literal(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::Bool(b) => b.to_string(),
+        Value::Number(n) => n.to_string(),
+        Value::String(s) => toml::Value::String(s.clone()).to_string(),
+        Value::Array(items) => format!(
+            "[{}]",
+            items
+                .iter()
+                .map(toml_literal)
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        Value::Object(table) => format!(
+            "{{ {} }}",
+            table
+                .iter()
+                .filter(|(_, value)| !value.is_null())
+                .map(|(key, value)| format!("{} = {}", toml_key(key), toml_literal(value)))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    }
+}
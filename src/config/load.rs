@@ -1,8 +1,20 @@
 // Configuration loading functionality
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::{Path, PathBuf};
 
-use crate::config::types::{AppConfig, AppConfigToml, ConfigError, DaemonConfig, ProfileConfig};
+use crate::config::schema::RENAMED_KEYS;
+use crate::config::types::{
+    AppConfig, AppConfigToml, BUILTIN_PROFILES, BatteryTierConfig, ConfigError, ConfigSource,
+    ConfigSources, DaemonConfig, ProcessTriggerConfig, ProfileConfig, ProfileConfigToml,
+    RuleConfig, ScheduleConfig,
+};
+
+/// System-wide configuration paths, in order of preference
+const SYSTEM_CONFIG_PATHS: &[&str] = &["/etc/xdg/watt/config.toml", "/etc/watt.toml"];
+
+/// Drop-in directory used when no base configuration file exists
+const DEFAULT_DROP_IN_DIR: &str = "/etc/watt.d";
 
 /// The primary function to load application configuration from a specific path or from default locations.
 ///
@@ -18,29 +30,79 @@ pub fn load_config() -> Result<AppConfig, ConfigError> {
     load_config_from_path(None)
 }
 
-/// Load configuration from a specific path or try default paths
-pub fn load_config_from_path(specific_path: Option<&str>) -> Result<AppConfig, ConfigError> {
-    // If a specific path is provided, only try that one
-    if let Some(path_str) = specific_path {
-        let path = Path::new(path_str);
-        if path.exists() {
-            return load_and_parse_config(path);
+/// Load configuration from a specific path or try default paths.
+///
+/// The base file is `specific_path` if given, and otherwise the first of
+/// `WATT_CONFIG` and the system-wide paths that exists. An explicit path that
+/// does not exist or fails to parse is an error rather than a fallback to the
+/// default locations.
+///
+/// The base file is layered with the `*.toml` fragments of its drop-in
+/// directory (see [`drop_in_dir`]), merged in lexical order. Later layers take
+/// precedence: tables are merged key by key, arrays of tables are appended to,
+/// and other values and arrays are replaced as a whole. Progress is printed
+/// to stderr, so that `watt config dump` can be piped into other tools.
+pub fn load_config_from_path(specific_path: Option<&Path>) -> Result<AppConfig, ConfigError> {
+    let mut merged = toml::Table::new();
+    let mut sources = ConfigSources::default();
+
+    let base = if let Some(path) = specific_path {
+        // If a specific path is provided, only try that one
+        if !path.exists() {
+            return Err(ConfigError::Io(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("Specified config file not found: {}", path.display()),
+            )));
         }
-        return Err(ConfigError::Io(std::io::Error::new(
-            std::io::ErrorKind::NotFound,
-            format!("Specified config file not found: {}", path.display()),
-        )));
+        let source = ConfigSource::File(path.to_path_buf());
+        merge_layer(&mut merged, &mut sources, read_config_table(path)?, source);
+        Some(path.to_path_buf())
+    } else {
+        load_default_base(&mut merged, &mut sources)?
+    };
+
+    for fragment in find_drop_ins(&drop_in_dir(base.as_deref())) {
+        eprintln!("Loading config fragment from: {}", fragment.display());
+        match read_config_table(&fragment) {
+            Ok(table) => merge_layer(
+                &mut merged,
+                &mut sources,
+                table,
+                ConfigSource::File(fragment),
+            ),
+            Err(e) => {
+                eprintln!("Error with config fragment {}: {}", fragment.display(), e);
+                // Continue with the remaining fragments
+            }
+        }
+    }
+
+    if sources.layers().is_empty() {
+        eprintln!(
+            "No configuration file found or all failed to parse. Using default configuration."
+        );
     }
 
+    build_config(merged, sources)
+}
+
+/// Load the base configuration file from `WATT_CONFIG` or the system-wide paths,
+/// returning its path if one was loaded
+fn load_default_base(
+    merged: &mut toml::Table,
+    sources: &mut ConfigSources,
+) -> Result<Option<PathBuf>, ConfigError> {
     // Check for WATT_CONFIG environment variable
     if let Ok(env_path) = std::env::var("WATT_CONFIG") {
-        let env_path = Path::new(&env_path);
+        let env_path = PathBuf::from(env_path);
         if env_path.exists() {
-            println!(
-                "Loading config from WATT_CONFIG: {}",
-                env_path.display()
-            );
-            return load_and_parse_config(env_path);
+            eprintln!("Loading config from WATT_CONFIG: {}", env_path.display());
+            let source = ConfigSource::Env {
+                variable: "WATT_CONFIG",
+                path: env_path.clone(),
+            };
+            merge_layer(merged, sources, read_config_table(&env_path)?, source);
+            return Ok(Some(env_path));
         }
         eprintln!(
             "Warning: Config file specified by WATT_CONFIG not found: {}",
@@ -49,16 +111,14 @@ pub fn load_config_from_path(specific_path: Option<&str>) -> Result<AppConfig, C
     }
 
     // System-wide paths
-    let config_paths = vec![
-        PathBuf::from("/etc/xdg/watt/config.toml"),
-        PathBuf::from("/etc/watt.toml"),
-    ];
-
-    for path in config_paths {
+    for path in SYSTEM_CONFIG_PATHS.iter().map(PathBuf::from) {
         if path.exists() {
-            println!("Loading config from: {}", path.display());
-            match load_and_parse_config(&path) {
-                Ok(config) => return Ok(config),
+            eprintln!("Loading config from: {}", path.display());
+            match read_config_table(&path) {
+                Ok(table) => {
+                    merge_layer(merged, sources, table, ConfigSource::File(path.clone()));
+                    return Ok(Some(path));
+                }
                 Err(e) => {
                     eprintln!("Error with config file {}: {}", path.display(), e);
                     // Continue trying other files
@@ -67,44 +127,478 @@ pub fn load_config_from_path(specific_path: Option<&str>) -> Result<AppConfig, C
         }
     }
 
-    println!("No configuration file found or all failed to parse. Using default configuration.");
-    // Construct default AppConfig by converting default AppConfigToml
-    let default_toml_config = AppConfigToml::default();
-    Ok(AppConfig {
-        charger: ProfileConfig::from(default_toml_config.charger),
-        battery: ProfileConfig::from(default_toml_config.battery),
-        ignored_power_supplies: default_toml_config.ignored_power_supplies,
-        daemon: DaemonConfig::default(),
-    })
+    Ok(None)
 }
 
-/// Load and parse a configuration file
-fn load_and_parse_config(path: &Path) -> Result<AppConfig, ConfigError> {
+/// Find the base configuration file that would be loaded, without parsing it.
+///
+/// Honours `WATT_CONFIG` first and then falls back to the system-wide paths.
+pub fn find_config_path() -> Option<PathBuf> {
+    if let Ok(env_path) = std::env::var("WATT_CONFIG") {
+        let env_path = PathBuf::from(env_path);
+        if env_path.exists() {
+            return Some(env_path);
+        }
+    }
+
+    SYSTEM_CONFIG_PATHS
+        .iter()
+        .map(PathBuf::from)
+        .find(|path| path.exists())
+}
+
+/// Find every configuration file that would be loaded, lowest precedence first.
+///
+/// With `specific_path`, that file and its drop-in fragments are returned.
+pub fn find_config_layers(specific_path: Option<&Path>) -> Vec<PathBuf> {
+    let base = specific_path.map_or_else(find_config_path, |path| Some(path.to_path_buf()));
+    let drop_ins = find_drop_ins(&drop_in_dir(base.as_deref()));
+    base.into_iter().chain(drop_ins).collect()
+}
+
+/// The drop-in directory belonging to a base configuration file, e.g.
+/// `/etc/watt.d` for `/etc/watt.toml`
+pub fn drop_in_dir(base: Option<&Path>) -> PathBuf {
+    base.map_or_else(
+        || PathBuf::from(DEFAULT_DROP_IN_DIR),
+        |base| base.with_extension("d"),
+    )
+}
+
+/// List the `*.toml` fragments in a drop-in directory in lexical order
+fn find_drop_ins(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+
+    let mut fragments: Vec<PathBuf> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == "toml"))
+        .collect();
+    fragments.sort();
+    fragments
+}
+
+fn read_config_table(path: &Path) -> Result<toml::Table, ConfigError> {
     let contents = fs::read_to_string(path).map_err(ConfigError::Io)?;
+    let mut table = toml::from_str::<toml::Table>(&contents).map_err(ConfigError::Toml)?;
+    rename_former_keys(&mut table);
+    Ok(table)
+}
+
+/// Rename keys written under their former name throughout a layer, so that
+/// layers using either name merge with each other. The current name wins if
+/// a table has both.
+fn rename_former_keys(table: &mut toml::Table) {
+    for (former, current) in RENAMED_KEYS {
+        if let Some(value) = table.remove(*former) {
+            table.entry(*current).or_insert(value);
+        }
+    }
+
+    for (_, value) in table.iter_mut() {
+        match value {
+            toml::Value::Table(table) => rename_former_keys(table),
+            toml::Value::Array(array) => {
+                for table in array.iter_mut().filter_map(toml::Value::as_table_mut) {
+                    rename_former_keys(table);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Merge a configuration layer on top of the layers loaded so far
+fn merge_layer(
+    merged: &mut toml::Table,
+    sources: &mut ConfigSources,
+    layer: toml::Table,
+    source: ConfigSource,
+) {
+    sources.record_table("", &layer, &source);
+    sources.add_layer(source);
+    merge_tables(merged, layer);
+}
+
+/// Deep-merge `overlay` into `base`: tables are merged recursively, and arrays
+/// of tables such as `[[rules]]` are appended to, see [`merge_table_arrays`].
+/// Anything else in `overlay` replaces the value in `base`.
+pub fn merge_tables(base: &mut toml::Table, overlay: toml::Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base_table)), toml::Value::Table(overlay_table)) => {
+                merge_tables(base_table, overlay_table);
+            }
+            (Some(toml::Value::Array(base_array)), toml::Value::Array(overlay_array))
+                if is_table_array(base_array) && is_table_array(&overlay_array) =>
+            {
+                merge_table_arrays(base_array, overlay_array);
+            }
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
 
-    let toml_app_config = toml::from_str::<AppConfigToml>(&contents).map_err(ConfigError::Toml)?;
+/// Whether an array holds tables only. Empty arrays do not count, so that an
+/// empty array in a later layer clears the entries of the earlier ones.
+fn is_table_array(array: &[toml::Value]) -> bool {
+    !array.is_empty() && array.iter().all(toml::Value::is_table)
+}
+
+/// Append the entries of `overlay` to `base`. An entry with the same `name` as
+/// an entry of `base` replaces that entry in place instead.
+fn merge_table_arrays(base: &mut Vec<toml::Value>, overlay: Vec<toml::Value>) {
+    for entry in overlay {
+        let name = entry.get("name").and_then(toml::Value::as_str);
+        let existing = name.and_then(|name| {
+            base.iter()
+                .position(|other| other.get("name").and_then(toml::Value::as_str) == Some(name))
+        });
+        match existing {
+            Some(index) => base[index] = entry,
+            None => base.push(entry),
+        }
+    }
+}
+
+/// Resolve `extends` in the `[profiles.<name>]` tables by merging every named
+/// profile on top of the profile it extends. Inherited values keep the source
+/// of the profile they came from.
+fn resolve_profiles(
+    table: &mut toml::Table,
+    sources: &mut ConfigSources,
+) -> Result<(), ConfigError> {
+    let Some(profiles) = table.get("profiles") else {
+        return Ok(());
+    };
+    let Some(profiles) = profiles.as_table() else {
+        return Err(ConfigError::Validation(
+            "`profiles` must be a table of named profiles".to_string(),
+        ));
+    };
+
+    let mut resolver = ProfileResolver {
+        root: table,
+        profiles,
+        sources,
+        resolved: BTreeMap::new(),
+        chain: Vec::new(),
+    };
+    for name in profiles.keys() {
+        if BUILTIN_PROFILES.contains(&name.as_str()) {
+            return Err(ConfigError::Validation(format!(
+                "[profiles.{name}] uses a reserved name, configure [{name}] instead"
+            )));
+        }
+        resolver.resolve(name)?;
+    }
+
+    let resolved = resolver
+        .resolved
+        .into_iter()
+        .map(|(name, profile)| (name, toml::Value::Table(profile)))
+        .collect();
+    table.insert("profiles".to_string(), toml::Value::Table(resolved));
+    Ok(())
+}
+
+struct ProfileResolver<'a> {
+    root: &'a toml::Table,
+    profiles: &'a toml::Table,
+    sources: &'a mut ConfigSources,
+    resolved: BTreeMap<String, toml::Table>,
+    /// Profiles currently being resolved, to detect inheritance cycles
+    chain: Vec<String>,
+}
+
+impl ProfileResolver<'_> {
+    fn resolve(&mut self, name: &str) -> Result<toml::Table, ConfigError> {
+        if BUILTIN_PROFILES.contains(&name) {
+            return Ok(self
+                .root
+                .get(name)
+                .and_then(toml::Value::as_table)
+                .cloned()
+                .unwrap_or_default());
+        }
+        if let Some(profile) = self.resolved.get(name) {
+            return Ok(profile.clone());
+        }
+        if self.chain.iter().any(|n| n == name) {
+            return Err(ConfigError::Validation(format!(
+                "profile inheritance cycle: {} -> {name}",
+                self.chain.join(" -> ")
+            )));
+        }
+
+        let mut profile = match self.profiles.get(name) {
+            Some(toml::Value::Table(profile)) => profile.clone(),
+            Some(_) => {
+                return Err(ConfigError::Validation(format!(
+                    "[profiles.{name}] must be a table"
+                )));
+            }
+            None => {
+                return Err(ConfigError::Validation(format!(
+                    "profile `{}` extends unknown profile `{name}`",
+                    self.chain.last().map_or("", String::as_str)
+                )));
+            }
+        };
+
+        let resolved = match profile.remove("extends") {
+            None => profile,
+            Some(toml::Value::String(parent)) => {
+                self.chain.push(name.to_string());
+                let mut merged = self.resolve(&parent)?;
+                self.chain.pop();
+
+                record_inherited(
+                    self.sources,
+                    &profile_path(&parent),
+                    &profile_path(name),
+                    &merged,
+                    &profile,
+                );
+                merge_tables(&mut merged, profile);
+                merged
+            }
+            Some(_) => {
+                return Err(ConfigError::Validation(format!(
+                    "`extends` in [profiles.{name}] must be a profile name"
+                )));
+            }
+        };
+
+        self.resolved.insert(name.to_string(), resolved.clone());
+        Ok(resolved)
+    }
+}
+
+/// Dotted configuration path of a profile
+fn profile_path(name: &str) -> String {
+    if BUILTIN_PROFILES.contains(&name) {
+        name.to_string()
+    } else {
+        format!("profiles.{name}")
+    }
+}
+
+/// Record the values a profile inherits from `parent` with the parent's sources
+fn record_inherited(
+    sources: &mut ConfigSources,
+    parent_path: &str,
+    path: &str,
+    parent: &toml::Table,
+    own: &toml::Table,
+) {
+    for (key, value) in parent {
+        let key_parent_path = format!("{parent_path}.{key}");
+        let key_path = format!("{path}.{key}");
+
+        match (value, own.get(key)) {
+            (toml::Value::Table(parent), Some(toml::Value::Table(own))) => {
+                record_inherited(sources, &key_parent_path, &key_path, parent, own);
+            }
+            (toml::Value::Table(parent), None) => {
+                record_inherited(
+                    sources,
+                    &key_parent_path,
+                    &key_path,
+                    parent,
+                    &toml::Table::new(),
+                );
+            }
+            (_, None) => {
+                let source = sources.source_of(&key_parent_path).clone();
+                sources.record(key_path, source);
+            }
+            // Overridden by the profile itself
+            (_, Some(_)) => {}
+        }
+    }
+}
+
+/// Name unnamed rules after their position, check that every rule selects an
+/// existing profile, and order the rules by descending priority. Rules with the
+/// same priority keep their order from the configuration.
+fn resolve_rules(
+    mut rules: Vec<RuleConfig>,
+    profiles: &BTreeMap<String, ProfileConfigToml>,
+) -> Result<Vec<RuleConfig>, ConfigError> {
+    for (index, rule) in rules.iter_mut().enumerate() {
+        let name = rule
+            .name
+            .get_or_insert_with(|| format!("rule #{}", index + 1));
+
+        if !BUILTIN_PROFILES.contains(&rule.profile.as_str())
+            && !profiles.contains_key(&rule.profile)
+        {
+            return Err(ConfigError::Validation(format!(
+                "rule '{name}' selects unknown profile `{}`",
+                rule.profile
+            )));
+        }
+
+        for (key, value) in [
+            ("battery_below", rule.when.battery_below),
+            ("battery_above", rule.when.battery_above),
+        ] {
+            if value.is_some_and(|percent| percent > 100) {
+                return Err(ConfigError::Validation(format!(
+                    "{key} in rule '{name}' cannot exceed 100%"
+                )));
+            }
+        }
+    }
+
+    rules.sort_by_key(|rule| std::cmp::Reverse(rule.priority));
+    Ok(rules)
+}
+
+/// Name unnamed schedules after their position and check that every schedule
+/// selects an existing profile
+fn resolve_schedules(
+    mut schedules: Vec<ScheduleConfig>,
+    profiles: &BTreeMap<String, ProfileConfigToml>,
+) -> Result<Vec<ScheduleConfig>, ConfigError> {
+    for (index, schedule) in schedules.iter_mut().enumerate() {
+        let name = schedule
+            .name
+            .get_or_insert_with(|| format!("schedule #{}", index + 1));
+
+        if let Some(profile) = &schedule.profile {
+            if !BUILTIN_PROFILES.contains(&profile.as_str()) && !profiles.contains_key(profile) {
+                return Err(ConfigError::Validation(format!(
+                    "schedule '{name}' selects unknown profile `{profile}`"
+                )));
+            }
+        }
+    }
+
+    Ok(schedules)
+}
+
+/// Name unnamed process triggers after their position and check their
+/// patterns, CPU thresholds and profiles
+fn resolve_process_triggers(
+    mut triggers: Vec<ProcessTriggerConfig>,
+    profiles: &BTreeMap<String, ProfileConfigToml>,
+) -> Result<Vec<ProcessTriggerConfig>, ConfigError> {
+    for (index, trigger) in triggers.iter_mut().enumerate() {
+        let name = trigger
+            .name
+            .get_or_insert_with(|| format!("process trigger #{}", index + 1));
+
+        if trigger.processes.iter().all(|pattern| pattern.is_empty()) {
+            return Err(ConfigError::Validation(format!(
+                "process trigger '{name}' must list at least one process"
+            )));
+        }
+
+        if let Some(cpu_above) = trigger.cpu_above {
+            if cpu_above.is_nan() || cpu_above < 0.0 {
+                return Err(ConfigError::Validation(format!(
+                    "cpu_above in process trigger '{name}' must not be negative"
+                )));
+            }
+        }
+
+        if let Some(profile) = &trigger.profile {
+            if !BUILTIN_PROFILES.contains(&profile.as_str()) && !profiles.contains_key(profile) {
+                return Err(ConfigError::Validation(format!(
+                    "process trigger '{name}' selects unknown profile `{profile}`"
+                )));
+            }
+        }
+    }
+
+    Ok(triggers)
+}
+
+/// Check the battery tier thresholds and order the tiers by descending
+/// threshold, so that the settings of lower tiers are applied last
+fn resolve_battery_tiers(
+    mut tiers: Vec<BatteryTierConfig>,
+) -> Result<Vec<BatteryTierConfig>, ConfigError> {
+    for tier in &tiers {
+        if tier.below == 0 || tier.below > 100 {
+            return Err(ConfigError::Validation(format!(
+                "battery tier threshold ({}) must be between 1 and 100",
+                tier.below
+            )));
+        }
+    }
+
+    tiers.sort_by_key(|tier| std::cmp::Reverse(tier.below));
+    Ok(tiers)
+}
+
+/// Build the application configuration from the merged layers
+fn build_config(
+    mut table: toml::Table,
+    mut sources: ConfigSources,
+) -> Result<AppConfig, ConfigError> {
+    resolve_profiles(&mut table, &mut sources)?;
+
+    let toml_app_config: AppConfigToml = toml::Value::Table(table)
+        .try_into()
+        .map_err(ConfigError::Toml)?;
 
     // Handle inheritance of values from global to profile configs
     let mut charger_profile = toml_app_config.charger.clone();
     let mut battery_profile = toml_app_config.battery.clone();
+    let mut named_profiles = toml_app_config.profiles.clone();
 
     // Clone global battery_charge_thresholds once if it exists
     if let Some(global_thresholds) = toml_app_config.battery_charge_thresholds {
-        // Apply to charger profile if not already set
-        if charger_profile.battery_charge_thresholds.is_none() {
-            charger_profile.battery_charge_thresholds = Some(global_thresholds.clone());
-        }
+        let global_source = sources.source_of("battery_charge_thresholds").clone();
 
-        // Apply to battery profile if not already set
-        if battery_profile.battery_charge_thresholds.is_none() {
-            battery_profile.battery_charge_thresholds = Some(global_thresholds);
+        let profiles = [
+            ("charger".to_string(), &mut charger_profile),
+            ("battery".to_string(), &mut battery_profile),
+        ]
+        .into_iter()
+        .chain(
+            named_profiles
+                .iter_mut()
+                .map(|(name, profile)| (profile_path(name), profile)),
+        );
+
+        // Apply to every profile that does not set its own thresholds
+        for (path, profile) in profiles {
+            if profile.battery_charge_thresholds.is_none() {
+                profile.battery_charge_thresholds = Some(global_thresholds.clone());
+                sources.record(
+                    format!("{path}.battery_charge_thresholds"),
+                    global_source.clone(),
+                );
+            }
         }
     }
 
+    let rules = resolve_rules(toml_app_config.rules, &named_profiles)?;
+    let battery_tiers = resolve_battery_tiers(toml_app_config.battery_tiers)?;
+    let schedules = resolve_schedules(toml_app_config.schedules, &named_profiles)?;
+    let process_triggers =
+        resolve_process_triggers(toml_app_config.process_triggers, &named_profiles)?;
+
     // Convert AppConfigToml to AppConfig
     Ok(AppConfig {
         charger: ProfileConfig::from(charger_profile),
         battery: ProfileConfig::from(battery_profile),
+        profiles: named_profiles
+            .into_iter()
+            .map(|(name, profile)| (name, ProfileConfig::from(profile)))
+            .collect(),
+        rules,
+        battery_tiers,
+        schedules,
+        process_triggers,
         ignored_power_supplies: toml_app_config.ignored_power_supplies,
         daemon: DaemonConfig {
             poll_interval_sec: toml_app_config.daemon.poll_interval_sec,
@@ -115,5 +609,138 @@ fn load_and_parse_config(path: &Path) -> Result<AppConfig, ConfigError> {
             log_level: toml_app_config.daemon.log_level,
             stats_file_path: toml_app_config.daemon.stats_file_path,
         },
+        sources,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(source: &str) -> toml::Table {
+        toml::from_str(source).expect("test TOML is valid")
+    }
+
+    fn resolve(source: &str) -> Result<toml::Table, ConfigError> {
+        let mut table = table(source);
+        resolve_profiles(&mut table, &mut ConfigSources::default())?;
+        Ok(table)
+    }
+
+    #[test]
+    fn extends_inherits_from_the_parent_profile() {
+        let resolved = resolve(
+            "[battery]\nturbo = \"never\"\n[profiles.quiet]\nextends = \"battery\"\nepp = \"power\"\n",
+        )
+        .expect("profiles resolve");
+        assert_eq!(
+            resolved["profiles"]["quiet"],
+            toml::Value::Table(table("turbo = \"never\"\nepp = \"power\"\n"))
+        );
+    }
+
+    #[test]
+    fn extends_detects_cycles() {
+        let error = resolve(
+            "[profiles.a]\nextends = \"b\"\n[profiles.b]\nextends = \"c\"\n[profiles.c]\nextends = \"a\"\n",
+        )
+        .expect_err("cycle is rejected");
+        assert!(
+            error
+                .to_string()
+                .contains("inheritance cycle: a -> b -> c -> a"),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn extends_detects_self_reference() {
+        let error = resolve("[profiles.a]\nextends = \"a\"\n").expect_err("cycle is rejected");
+        assert!(
+            error.to_string().contains("inheritance cycle: a -> a"),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn extends_rejects_unknown_parent() {
+        let error =
+            resolve("[profiles.a]\nextends = \"missing\"\n").expect_err("parent must exist");
+        assert!(
+            error.to_string().contains("unknown profile `missing`"),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn merge_tables_merges_nested_tables_key_by_key() {
+        let mut base = table("[charger]\ngovernor = \"performance\"\nturbo = \"auto\"\n");
+        merge_tables(&mut base, table("[charger]\nturbo = \"never\"\n"));
+        assert_eq!(
+            base,
+            table("[charger]\ngovernor = \"performance\"\nturbo = \"never\"\n")
+        );
+    }
+
+    #[test]
+    fn merge_tables_replaces_values_and_plain_arrays() {
+        let mut base =
+            table("ignored_power_supplies = [\"a\", \"b\"]\n[daemon]\npoll_interval_sec = 5\n");
+        merge_tables(
+            &mut base,
+            table("ignored_power_supplies = [\"c\"]\n[daemon]\npoll_interval_sec = 10\n"),
+        );
+        assert_eq!(
+            base,
+            table("ignored_power_supplies = [\"c\"]\n[daemon]\npoll_interval_sec = 10\n")
+        );
+    }
+
+    #[test]
+    fn merge_tables_appends_arrays_of_tables() {
+        let mut base = table("[[rules]]\nprofile = \"quiet\"\n");
+        merge_tables(&mut base, table("[[rules]]\nprofile = \"compile\"\n"));
+        assert_eq!(
+            base,
+            table("[[rules]]\nprofile = \"quiet\"\n[[rules]]\nprofile = \"compile\"\n")
+        );
+    }
+
+    #[test]
+    fn merge_tables_replaces_named_entries_in_place() {
+        let mut base = table(
+            "[[rules]]\nname = \"hot\"\nprofile = \"quiet\"\n[[rules]]\nname = \"low\"\nprofile = \"battery\"\n",
+        );
+        merge_tables(
+            &mut base,
+            table("[[rules]]\nname = \"hot\"\nprofile = \"powersave\"\n"),
+        );
+        assert_eq!(
+            base,
+            table(
+                "[[rules]]\nname = \"hot\"\nprofile = \"powersave\"\n[[rules]]\nname = \"low\"\nprofile = \"battery\"\n",
+            )
+        );
+    }
+
+    #[test]
+    fn merge_tables_clears_arrays_of_tables_with_empty_array() {
+        let mut base = table("[[rules]]\nprofile = \"quiet\"\n");
+        merge_tables(&mut base, table("rules = []\n"));
+        assert_eq!(base, table("rules = []\n"));
+    }
+
+    #[test]
+    fn former_key_names_are_renamed_everywhere() {
+        let mut layer = table(
+            "[battery]\nmin_freq_mhz = 800\n\n[battery.e_cores]\nmax_freq_mhz = 1800\n\n[[battery_tiers]]\nbelow = 20\nmax_freq_mhz = 1200\nmax_freq = 1000\n",
+        );
+        rename_former_keys(&mut layer);
+        assert_eq!(
+            layer,
+            table(
+                "[battery]\nmin_freq = 800\n\n[battery.e_cores]\nmax_freq = 1800\n\n[[battery_tiers]]\nbelow = 20\nmax_freq = 1000\n"
+            )
+        );
+    }
+}
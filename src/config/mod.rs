@@ -1,5 +1,10 @@
+pub mod check;
+pub mod dump;
 pub mod load;
+pub mod schema;
 pub mod types;
 
+pub use check::*;
+pub use dump::*;
 pub use load::*;
 pub use types::*;
@@ -0,0 +1,639 @@
+// Description of every configuration key Watt understands.
+//
+// Used to detect unknown keys in `watt config check` and to render the
+// reference configuration printed by `watt config dump --defaults`.
+
+/// What kind of entry a key is
+pub enum KeyKind {
+    /// A plain value, with an example used when the key has no default
+    Value { example: &'static str },
+    /// A nested table, documented by its own key list
+    Table,
+    /// User-defined sections (named tables or an array of tables), documented
+    /// by an example
+    Sections { example: &'static str },
+}
+
+/// A documented configuration key
+pub struct KeyDoc {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub kind: KeyKind,
+}
+
+macro_rules! key {
+    ($name:literal, $description:literal, table) => {
+        KeyDoc {
+            name: $name,
+            description: $description,
+            kind: KeyKind::Table,
+        }
+    };
+    ($name:literal, $description:literal, sections $example:literal) => {
+        KeyDoc {
+            name: $name,
+            description: $description,
+            kind: KeyKind::Sections { example: $example },
+        }
+    };
+    ($name:literal, $description:literal, $example:literal) => {
+        KeyDoc {
+            name: $name,
+            description: $description,
+            kind: KeyKind::Value { example: $example },
+        }
+    };
+}
+
+/// Keys accepted at the top level of the configuration file
+pub const TOP_LEVEL_KEYS: &[KeyDoc] = &[
+    key!(
+        "battery_charge_thresholds",
+        "Battery charge thresholds as [start, stop] percentages, inherited by profiles that do not set their own",
+        "[40, 80]"
+    ),
+    key!(
+        "ignored_power_supplies",
+        "Power supplies to ignore when detecting batteries, e.g. peripheral batteries",
+        r#"["hid-12:34:56:78:90:ab-battery"]"#
+    ),
+    key!(
+        "charger",
+        "Settings used while connected to AC power",
+        table
+    ),
+    key!(
+        "battery",
+        "Settings used while running on battery power",
+        table
+    ),
+    key!(
+        "profiles",
+        "Named profiles, selected with `watt profile set <name>`",
+        sections "[profiles.quiet]\nextends = \"battery\"\nmax_freq = 2000\nturbo = \"never\""
+    ),
+    key!(
+        "rules",
+        "Rules selecting a profile from the system state, the first active rule by priority wins",
+        sections "[[rules]]\nname = \"critical\"\nprofile = \"quiet\"\npriority = 10\ndebounce_sec = 30\nwhen = { ac = false, battery_below = 20 }"
+    ),
+    key!(
+        "battery_tiers",
+        "Settings layered on top of the selected profile on battery power while the battery is low",
+        sections "[[battery_tiers]]\nbelow = 30\nmax_freq = 2000\nturbo = \"never\"\n\n[[battery_tiers]]\nbelow = 10\nepp = \"power\"\nplatform_profile = \"low-power\""
+    ),
+    key!(
+        "schedules",
+        "Time-of-day windows selecting a profile or overriding settings, the first active one applies",
+        sections "[[schedules]]\nname = \"night\"\ndays = [\"weekdays\"]\nfrom = \"19:00\"\nto = \"07:00\"\nprofile = \"quiet\"\nmax_freq = 2000"
+    ),
+    key!(
+        "process_triggers",
+        "Running processes selecting a profile or overriding settings, the first active one applies",
+        sections "[[process_triggers]]\nname = \"compile\"\nprocesses = [\"cargo\", \"rustc\", \"ld.lld\"]\ncpu_above = 50\nhold_sec = 10\nprofile = \"compile\"\n\n[[process_triggers]]\nname = \"games\"\nprocesses = [\"steam\", \"*.exe\"]\nturbo = \"always\""
+    ),
+    key!("daemon", "Daemon behaviour", table),
+];
+
+/// Keys accepted in the `[charger]` and `[battery]` profile sections
+pub const PROFILE_KEYS: &[KeyDoc] = &[
+    key!("governor", "CPU frequency governor", r#""powersave""#),
+    key!(
+        "turbo",
+        r#"Turbo boost behaviour: "always", "auto" or "never""#,
+        r#""auto""#
+    ),
+    key!(
+        "enable_auto_turbo",
+        r#"Let Watt manage turbo dynamically when turbo = "auto""#,
+        "true"
+    ),
+    key!(
+        "epp",
+        "Energy Performance Preference (see energy_performance_available_preferences)",
+        r#""balance_power""#
+    ),
+    key!(
+        "epb",
+        "Energy Performance Bias, 0-15 or a named value",
+        r#""balance_power""#
+    ),
+    key!(
+        "min_freq",
+        r#"Minimum CPU frequency in MHz, or e.g. "2.4GHz", "70%", "min" or "base""#,
+        "800"
+    ),
+    key!(
+        "max_freq",
+        r#"Maximum CPU frequency in MHz, or e.g. "2.4GHz", "70%", "base" or "max""#,
+        r#""70%""#
+    ),
+    key!(
+        "uncore_min_freq_mhz",
+        "Minimum uncore (ring bus and cache) frequency in MHz on Intel CPUs",
+        "800"
+    ),
+    key!(
+        "uncore_max_freq_mhz",
+        "Maximum uncore (ring bus and cache) frequency in MHz on Intel CPUs",
+        "1600"
+    ),
+    key!(
+        "gpu_min_freq_mhz",
+        "Minimum Intel GPU frequency in MHz (i915 and xe)",
+        "300"
+    ),
+    key!(
+        "gpu_max_freq_mhz",
+        "Maximum Intel GPU frequency in MHz (i915 and xe)",
+        "900"
+    ),
+    key!(
+        "gpu_boost_freq_mhz",
+        "Intel GPU boost frequency in MHz (i915 only)",
+        "900"
+    ),
+    key!(
+        "gpu_dpm_level",
+        "Forced DPM performance level of amdgpu GPUs (auto, low, high, manual, ...)",
+        r#""low""#
+    ),
+    key!(
+        "gpu_power_profile",
+        "amdgpu power profile mode (see pp_power_profile_mode)",
+        r#""POWER_SAVING""#
+    ),
+    key!(
+        "cpuidle_governor",
+        "cpuidle governor (see /sys/devices/system/cpu/cpuidle/available_governors)",
+        r#""teo""#
+    ),
+    key!(
+        "disabled_idle_states",
+        "Names of the idle states (C-states) to disable on every core",
+        r#"["C6", "C8"]"#
+    ),
+    key!(
+        "platform_profile",
+        "ACPI platform profile (see /sys/firmware/acpi/platform_profile_choices)",
+        r#""low-power""#
+    ),
+    key!(
+        "online_cores",
+        "Number of cores, or a CPU list such as \"0-3\", to keep online, parking the others",
+        "4"
+    ),
+    key!(
+        "smt",
+        "Simultaneous multithreading: \"on\", \"off\" or \"auto\" to leave it unchanged",
+        r#""off""#
+    ),
+    key!(
+        "package_power_limit_w",
+        "Sustained CPU package power limit (RAPL PL1) in watts",
+        "15"
+    ),
+    key!(
+        "package_power_window_sec",
+        "Time window of the sustained power limit in seconds",
+        "28"
+    ),
+    key!(
+        "package_power_limit_short_w",
+        "Short-term CPU package power limit (RAPL PL2) in watts",
+        "25"
+    ),
+    key!(
+        "package_power_window_short_sec",
+        "Time window of the short-term power limit in seconds",
+        "0.00244"
+    ),
+    key!(
+        "battery_charge_thresholds",
+        "Battery charge thresholds for this profile as [start, stop] percentages",
+        "[40, 80]"
+    ),
+    key!(
+        "turbo_auto_settings",
+        r#"Thresholds for dynamic turbo management when turbo = "auto""#,
+        table
+    ),
+    key!(
+        "p_cores",
+        "Settings for the performance cores of hybrid CPUs",
+        table
+    ),
+    key!(
+        "e_cores",
+        "Settings for the efficiency cores of hybrid CPUs",
+        table
+    ),
+    key!(
+        "intel_pstate",
+        "Settings of the intel_pstate scaling driver",
+        table
+    ),
+    key!(
+        "amd_pstate",
+        "Settings of the amd_pstate scaling driver",
+        table
+    ),
+    key!("schedutil", "Tunables of the schedutil governor", table),
+    key!("ondemand", "Tunables of the ondemand governor", table),
+    key!(
+        "conservative",
+        "Tunables of the conservative governor",
+        table
+    ),
+];
+
+/// Keys accepted in `[profiles.<name>]` sections in addition to [`PROFILE_KEYS`]
+pub const NAMED_PROFILE_KEYS: &[KeyDoc] = &[key!(
+    "extends",
+    "Profile to inherit unset values from, e.g. \"battery\" or another named profile",
+    r#""battery""#
+)];
+
+/// Keys accepted in a `[[battery_tiers]]` entry in addition to
+/// [`PROFILE_OVERRIDE_KEYS`]
+pub const BATTERY_TIER_KEYS: &[KeyDoc] = &[
+    key!(
+        "below",
+        "Average battery capacity in percent below which the tier is active",
+        "30"
+    ),
+    key!(
+        "hysteresis",
+        "Percentage points the capacity must rise above `below` to leave the tier",
+        "3"
+    ),
+];
+
+/// Keys accepted in a `[[schedules]]` entry in addition to
+/// [`PROFILE_OVERRIDE_KEYS`]
+pub const SCHEDULE_KEYS: &[KeyDoc] = &[
+    key!(
+        "name",
+        "Name shown in logs and `watt explain`",
+        r#""night""#
+    ),
+    key!(
+        "days",
+        r#"Days a window starts on: "mon" to "sun", "weekdays" or "weekends", every day if unset"#,
+        r#"["weekdays"]"#
+    ),
+    key!("from", "Start of the window in local time", r#""19:00""#),
+    key!(
+        "to",
+        "End of the window in local time, a window ending before it starts runs past midnight",
+        r#""07:00""#
+    ),
+    key!(
+        "profile",
+        "Profile to select while the schedule is active",
+        r#""quiet""#
+    ),
+];
+
+/// Keys accepted in a `[[process_triggers]]` entry in addition to
+/// [`PROFILE_OVERRIDE_KEYS`]
+pub const PROCESS_TRIGGER_KEYS: &[KeyDoc] = &[
+    key!(
+        "name",
+        "Name shown in logs and `watt explain`",
+        r#""compile""#
+    ),
+    key!(
+        "processes",
+        "Command or executable names to match, with `*` and `?` wildcards",
+        r#"["cargo", "rustc", "ld.lld"]"#
+    ),
+    key!(
+        "cpu_above",
+        "Combined CPU usage of the matching processes above which the trigger is active, in percent of one CPU (200 is two busy CPUs)",
+        "50"
+    ),
+    key!(
+        "hold_sec",
+        "How long the trigger stays active after it stops matching",
+        "10"
+    ),
+    key!(
+        "profile",
+        "Profile to select while the trigger is active",
+        r#""compile""#
+    ),
+];
+
+/// Keys accepted under their former name, as (former name, current name)
+pub const RENAMED_KEYS: &[(&str, &str)] =
+    &[("min_freq_mhz", "min_freq"), ("max_freq_mhz", "max_freq")];
+
+/// Profile keys that can be overridden by battery tiers, schedules and
+/// process triggers
+pub const PROFILE_OVERRIDE_KEYS: &[&str] = &[
+    "governor",
+    "turbo",
+    "epp",
+    "epb",
+    "min_freq",
+    "max_freq",
+    "uncore_min_freq_mhz",
+    "uncore_max_freq_mhz",
+    "gpu_min_freq_mhz",
+    "gpu_max_freq_mhz",
+    "gpu_boost_freq_mhz",
+    "gpu_dpm_level",
+    "gpu_power_profile",
+    "cpuidle_governor",
+    "disabled_idle_states",
+    "platform_profile",
+    "online_cores",
+    "smt",
+    "package_power_limit_w",
+    "package_power_window_sec",
+    "package_power_limit_short_w",
+    "package_power_window_short_sec",
+];
+
+/// Keys accepted in a `[[rules]]` entry
+pub const RULE_KEYS: &[KeyDoc] = &[
+    key!(
+        "name",
+        "Name shown in logs and `watt explain`",
+        r#""critical""#
+    ),
+    key!(
+        "profile",
+        "Profile to apply while the rule matches",
+        r#""quiet""#
+    ),
+    key!(
+        "priority",
+        "Rules with a higher priority are evaluated first",
+        "0"
+    ),
+    key!(
+        "debounce_sec",
+        "Seconds the conditions must hold, or stop holding, before the rule takes effect or is released",
+        "0"
+    ),
+    key!(
+        "when",
+        "Conditions that must all hold, an empty table always matches",
+        "{ ac = false }"
+    ),
+];
+
+/// Conditions accepted in a rule's `when` table
+pub const CONDITION_KEYS: &[KeyDoc] = &[
+    key!("ac", "Whether the system is on AC power", "false"),
+    key!(
+        "battery_below",
+        "Average battery capacity is below this percentage",
+        "20"
+    ),
+    key!(
+        "battery_above",
+        "Average battery capacity is above this percentage",
+        "80"
+    ),
+    key!(
+        "temp_above",
+        "Average CPU temperature is above this many °C",
+        "85.0"
+    ),
+    key!(
+        "temp_below",
+        "Average CPU temperature is below this many °C",
+        "60.0"
+    ),
+    key!(
+        "usage_above",
+        "Average CPU usage is above this percentage",
+        "80.0"
+    ),
+    key!(
+        "usage_below",
+        "Average CPU usage is below this percentage",
+        "10.0"
+    ),
+    key!(
+        "gpu_busy_above",
+        "Highest GPU busy percentage is above this value",
+        "50.0"
+    ),
+    key!(
+        "gpu_busy_below",
+        "Highest GPU busy percentage is below this value",
+        "5.0"
+    ),
+    key!(
+        "load_above",
+        "1-minute load average is above this value",
+        "4.0"
+    ),
+    key!(
+        "load_below",
+        "1-minute load average is below this value",
+        "0.5"
+    ),
+    key!(
+        "idle_for_sec",
+        "The system has been idle for at least this many seconds",
+        "300"
+    ),
+    key!(
+        "discharge_above",
+        "Battery discharge rate is above this many percent per hour",
+        "20.0"
+    ),
+];
+
+/// Keys accepted in a profile's `turbo_auto_settings` table
+pub const TURBO_AUTO_KEYS: &[KeyDoc] = &[
+    key!(
+        "load_threshold_high",
+        "Enable turbo when average CPU load is at or above this percentage",
+        "70.0"
+    ),
+    key!(
+        "load_threshold_low",
+        "Disable turbo when average CPU load is at or below this percentage",
+        "30.0"
+    ),
+    key!(
+        "temp_threshold_high",
+        "Disable turbo when CPU temperature is at or above this many °C",
+        "75.0"
+    ),
+    key!(
+        "initial_turbo_state",
+        "Turbo state to start with before any load data is available",
+        "false"
+    ),
+];
+
+/// Keys accepted in a profile's `p_cores` and `e_cores` tables
+pub const CORE_TYPE_KEYS: &[KeyDoc] = &[
+    key!("governor", "CPU frequency governor", r#""powersave""#),
+    key!(
+        "epp",
+        "Energy Performance Preference (see energy_performance_available_preferences)",
+        r#""balance_power""#
+    ),
+    key!(
+        "epb",
+        "Energy Performance Bias, 0-15 or a named value",
+        r#""balance_power""#
+    ),
+    key!(
+        "min_freq",
+        r#"Minimum CPU frequency in MHz, or e.g. "2.4GHz", "70%", "min" or "base""#,
+        "800"
+    ),
+    key!(
+        "max_freq",
+        r#"Maximum CPU frequency in MHz, or e.g. "2.4GHz", "70%", "base" or "max""#,
+        r#""70%""#
+    ),
+];
+
+/// Keys accepted in a profile's `intel_pstate` table
+pub const INTEL_PSTATE_KEYS: &[KeyDoc] = &[
+    key!(
+        "status",
+        r#"Driver mode: "active", "passive" or "off""#,
+        r#""active""#
+    ),
+    key!(
+        "min_perf_pct",
+        "Lowest P-state in percent of the maximum performance",
+        "20"
+    ),
+    key!(
+        "max_perf_pct",
+        "Highest P-state in percent of the maximum performance",
+        "80"
+    ),
+    key!(
+        "hwp_dynamic_boost",
+        "Boost cores waking up from I/O waits (active mode with HWP only)",
+        "false"
+    ),
+    key!(
+        "energy_efficiency",
+        "Prefer energy efficiency in the P-state selection of supported CPUs",
+        "true"
+    ),
+];
+
+/// Keys accepted in a profile's `amd_pstate` table
+pub const AMD_PSTATE_KEYS: &[KeyDoc] = &[key!(
+    "status",
+    r#"Driver mode: "active", "guided" or "passive""#,
+    r#""active""#
+)];
+
+/// Keys accepted in a profile's `schedutil` table
+pub const SCHEDUTIL_KEYS: &[KeyDoc] = &[key!(
+    "rate_limit_us",
+    "Minimum time between frequency changes in microseconds",
+    "2000"
+)];
+
+/// Keys accepted in a profile's `ondemand` table
+pub const ONDEMAND_KEYS: &[KeyDoc] = &[
+    key!(
+        "up_threshold",
+        "CPU load in percent above which the frequency is raised",
+        "80"
+    ),
+    key!(
+        "sampling_rate",
+        "How often the CPU load is evaluated, in microseconds",
+        "10000"
+    ),
+    key!(
+        "powersave_bias",
+        "Lower the selected frequencies by this many tenths of a percent (0-1000)",
+        "100"
+    ),
+    key!(
+        "io_is_busy",
+        "Count time waiting for I/O as CPU load",
+        "false"
+    ),
+];
+
+/// Keys accepted in a profile's `conservative` table
+pub const CONSERVATIVE_KEYS: &[KeyDoc] = &[
+    key!(
+        "freq_step",
+        "Frequency step in percent of the maximum frequency",
+        "5"
+    ),
+    key!(
+        "down_threshold",
+        "CPU load in percent below which the frequency is lowered",
+        "20"
+    ),
+];
+
+/// Keys accepted when `battery_charge_thresholds` is written as a table
+pub const THRESHOLD_KEYS: &[KeyDoc] = &[
+    key!("start", "Percentage below which charging starts", "40"),
+    key!("stop", "Percentage at which charging stops", "80"),
+];
+
+/// Keys accepted in the `[daemon]` section
+pub const DAEMON_KEYS: &[KeyDoc] = &[
+    key!("poll_interval_sec", "Base polling interval in seconds", "5"),
+    key!(
+        "adaptive_interval",
+        "Adapt the polling interval to system activity",
+        "false"
+    ),
+    key!(
+        "min_poll_interval_sec",
+        "Lower bound for the adaptive polling interval in seconds",
+        "1"
+    ),
+    key!(
+        "max_poll_interval_sec",
+        "Upper bound for the adaptive polling interval in seconds",
+        "30"
+    ),
+    key!(
+        "throttle_on_battery",
+        "Poll half as often while on battery power",
+        "true"
+    ),
+    key!(
+        "log_level",
+        r#"Log level: "Error", "Warning", "Info" or "Debug""#,
+        r#""Info""#
+    ),
+    key!(
+        "stats_file_path",
+        "File the daemon writes its current statistics to",
+        r#""/var/run/watt-stats""#
+    ),
+];
+
+/// Get the keys accepted in a section, given its dotted path (`""` being the top level)
+pub fn section_keys(section: &str) -> Option<&'static [KeyDoc]> {
+    match section {
+        "" => Some(TOP_LEVEL_KEYS),
+        "charger" | "battery" => Some(PROFILE_KEYS),
+        "daemon" => Some(DAEMON_KEYS),
+        _ if section.ends_with(".turbo_auto_settings") => Some(TURBO_AUTO_KEYS),
+        _ if section.ends_with(".p_cores") || section.ends_with(".e_cores") => Some(CORE_TYPE_KEYS),
+        _ if section.ends_with(".intel_pstate") => Some(INTEL_PSTATE_KEYS),
+        _ if section.ends_with(".amd_pstate") => Some(AMD_PSTATE_KEYS),
+        _ if section.ends_with(".schedutil") => Some(SCHEDUTIL_KEYS),
+        _ if section.ends_with(".ondemand") => Some(ONDEMAND_KEYS),
+        _ if section.ends_with(".conservative") => Some(CONSERVATIVE_KEYS),
+        _ => None,
+    }
+}
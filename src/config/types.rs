@@ -1,7 +1,11 @@
 // Configuration types and structures for Watt
-use crate::core::TurboSetting;
+use crate::core::{AmdPstateStatus, IntelPstateStatus, SmtSetting, TurboSetting};
+use crate::topology;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::convert::TryFrom;
+use std::fmt;
+use std::path::PathBuf;
 
 /// Defines constant-returning functions used for default values.
 /// This hopefully reduces repetition since we have way too many default functions
@@ -58,15 +62,298 @@ pub struct ProfileConfig {
     pub turbo: Option<TurboSetting>,
     pub epp: Option<String>, // Energy Performance Preference (EPP)
     pub epb: Option<String>, // Energy Performance Bias (EPB) - usually an integer, but string for flexibility from sysfs
-    pub min_freq_mhz: Option<u32>,
-    pub max_freq_mhz: Option<u32>,
+    #[serde(alias = "min_freq_mhz")]
+    pub min_freq: Option<FrequencyLimit>,
+    #[serde(alias = "max_freq_mhz")]
+    pub max_freq: Option<FrequencyLimit>,
+    /// Uncore (ring bus and last level cache) frequency limits on Intel CPUs
+    pub uncore_min_freq_mhz: Option<u32>,
+    pub uncore_max_freq_mhz: Option<u32>,
+    /// Frequency limits of Intel GPUs
+    pub gpu_min_freq_mhz: Option<u32>,
+    pub gpu_max_freq_mhz: Option<u32>,
+    pub gpu_boost_freq_mhz: Option<u32>,
+    /// Forced DPM performance level of amdgpu GPUs
+    pub gpu_dpm_level: Option<String>,
+    /// Power profile mode of amdgpu GPUs
+    pub gpu_power_profile: Option<String>,
     pub platform_profile: Option<String>,
+    /// cpuidle governor, e.g. `menu` or `teo`
+    pub cpuidle_governor: Option<String>,
+    /// Names of the idle states (C-states) to disable on every core
+    pub disabled_idle_states: Option<Vec<String>>,
+    /// Cores to keep online, parking the rest
+    pub online_cores: Option<OnlineCores>,
+    pub smt: Option<SmtSetting>,
+    /// Sustained package power limit (PL1) in watts
+    pub package_power_limit_w: Option<f32>,
+    /// Time window of the sustained power limit in seconds
+    pub package_power_window_sec: Option<f32>,
+    /// Short-term package power limit (PL2) in watts
+    pub package_power_limit_short_w: Option<f32>,
+    /// Time window of the short-term power limit in seconds
+    pub package_power_window_short_sec: Option<f32>,
     #[serde(default)]
     pub turbo_auto_settings: TurboAutoSettings,
     #[serde(default)]
     pub enable_auto_turbo: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub battery_charge_thresholds: Option<BatteryChargeThresholds>,
+    /// Settings for performance cores on hybrid CPUs, taking precedence over
+    /// the profile-wide ones
+    #[serde(default)]
+    pub p_cores: CoreTypeSettings,
+    /// Settings for efficiency cores on hybrid CPUs, taking precedence over
+    /// the profile-wide ones
+    #[serde(default)]
+    pub e_cores: CoreTypeSettings,
+    /// Settings of the intel_pstate driver
+    #[serde(default)]
+    pub intel_pstate: IntelPstateSettings,
+    /// Settings of the amd_pstate driver
+    #[serde(default)]
+    pub amd_pstate: AmdPstateSettings,
+    /// Tunables of the schedutil governor
+    #[serde(default)]
+    pub schedutil: SchedutilSettings,
+    /// Tunables of the ondemand governor
+    #[serde(default)]
+    pub ondemand: OndemandSettings,
+    /// Tunables of the conservative governor
+    #[serde(default)]
+    pub conservative: ConservativeSettings,
+}
+
+impl ProfileConfig {
+    /// Replace every setting that is set in `overrides`
+    /// Core type settings for a setting that is overridden are dropped, so the
+    /// override applies to every core.
+    pub fn apply_overrides(&mut self, overrides: &ProfileOverrides) {
+        self.p_cores.clear_overridden(overrides);
+        self.e_cores.clear_overridden(overrides);
+
+        if let Some(governor) = &overrides.governor {
+            self.governor = Some(governor.clone());
+        }
+        if let Some(turbo) = overrides.turbo {
+            self.turbo = Some(turbo);
+        }
+        if let Some(epp) = &overrides.epp {
+            self.epp = Some(epp.clone());
+        }
+        if let Some(epb) = &overrides.epb {
+            self.epb = Some(epb.clone());
+        }
+        if let Some(min_freq) = overrides.min_freq {
+            self.min_freq = Some(min_freq);
+        }
+        if let Some(max_freq) = overrides.max_freq {
+            self.max_freq = Some(max_freq);
+        }
+        if let Some(min_freq) = overrides.uncore_min_freq_mhz {
+            self.uncore_min_freq_mhz = Some(min_freq);
+        }
+        if let Some(max_freq) = overrides.uncore_max_freq_mhz {
+            self.uncore_max_freq_mhz = Some(max_freq);
+        }
+        if let Some(min_freq) = overrides.gpu_min_freq_mhz {
+            self.gpu_min_freq_mhz = Some(min_freq);
+        }
+        if let Some(max_freq) = overrides.gpu_max_freq_mhz {
+            self.gpu_max_freq_mhz = Some(max_freq);
+        }
+        if let Some(boost_freq) = overrides.gpu_boost_freq_mhz {
+            self.gpu_boost_freq_mhz = Some(boost_freq);
+        }
+        if let Some(dpm_level) = &overrides.gpu_dpm_level {
+            self.gpu_dpm_level = Some(dpm_level.clone());
+        }
+        if let Some(power_profile) = &overrides.gpu_power_profile {
+            self.gpu_power_profile = Some(power_profile.clone());
+        }
+        if let Some(platform_profile) = &overrides.platform_profile {
+            self.platform_profile = Some(platform_profile.clone());
+        }
+        if let Some(governor) = &overrides.cpuidle_governor {
+            self.cpuidle_governor = Some(governor.clone());
+        }
+        if let Some(states) = &overrides.disabled_idle_states {
+            self.disabled_idle_states = Some(states.clone());
+        }
+        if let Some(online_cores) = &overrides.online_cores {
+            self.online_cores = Some(online_cores.clone());
+        }
+        if let Some(smt) = overrides.smt {
+            self.smt = Some(smt);
+        }
+        if let Some(limit) = overrides.package_power_limit_w {
+            self.package_power_limit_w = Some(limit);
+        }
+        if let Some(window) = overrides.package_power_window_sec {
+            self.package_power_window_sec = Some(window);
+        }
+        if let Some(limit) = overrides.package_power_limit_short_w {
+            self.package_power_limit_short_w = Some(limit);
+        }
+        if let Some(window) = overrides.package_power_window_short_sec {
+            self.package_power_window_short_sec = Some(window);
+        }
+    }
+}
+
+/// Per-core settings for one core type, from `[<profile>.p_cores]` and
+/// `[<profile>.e_cores]`
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct CoreTypeSettings {
+    pub governor: Option<String>,
+    pub epp: Option<String>,
+    pub epb: Option<String>,
+    #[serde(alias = "min_freq_mhz")]
+    pub min_freq: Option<FrequencyLimit>,
+    #[serde(alias = "max_freq_mhz")]
+    pub max_freq: Option<FrequencyLimit>,
+}
+
+impl CoreTypeSettings {
+    fn clear_overridden(&mut self, overrides: &ProfileOverrides) {
+        if overrides.governor.is_some() {
+            self.governor = None;
+        }
+        if overrides.epp.is_some() {
+            self.epp = None;
+        }
+        if overrides.epb.is_some() {
+            self.epb = None;
+        }
+        if overrides.min_freq.is_some() {
+            self.min_freq = None;
+        }
+        if overrides.max_freq.is_some() {
+            self.max_freq = None;
+        }
+    }
+}
+
+/// Settings of the intel_pstate driver, from `[<profile>.intel_pstate]`
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct IntelPstateSettings {
+    pub status: Option<IntelPstateStatus>,
+    pub min_perf_pct: Option<u8>,
+    pub max_perf_pct: Option<u8>,
+    pub hwp_dynamic_boost: Option<bool>,
+    pub energy_efficiency: Option<bool>,
+}
+
+/// Settings of the amd_pstate driver, from `[<profile>.amd_pstate]`
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct AmdPstateSettings {
+    pub status: Option<AmdPstateStatus>,
+}
+
+/// Tunables of the schedutil governor, from `[<profile>.schedutil]`
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct SchedutilSettings {
+    pub rate_limit_us: Option<u32>,
+}
+
+impl SchedutilSettings {
+    /// The tunables that are set, as written to sysfs
+    pub fn tunables(&self) -> Vec<(&'static str, String)> {
+        [("rate_limit_us", self.rate_limit_us.map(|us| us.to_string()))]
+            .into_iter()
+            .filter_map(|(tunable, value)| Some((tunable, value?)))
+            .collect()
+    }
+}
+
+/// Tunables of the ondemand governor, from `[<profile>.ondemand]`
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct OndemandSettings {
+    pub up_threshold: Option<u8>,
+    pub sampling_rate: Option<u32>,
+    pub powersave_bias: Option<u16>,
+    pub io_is_busy: Option<bool>,
+}
+
+impl OndemandSettings {
+    /// The tunables that are set, as written to sysfs
+    pub fn tunables(&self) -> Vec<(&'static str, String)> {
+        [
+            ("up_threshold", self.up_threshold.map(|t| t.to_string())),
+            ("sampling_rate", self.sampling_rate.map(|us| us.to_string())),
+            ("powersave_bias", self.powersave_bias.map(|b| b.to_string())),
+            (
+                "io_is_busy",
+                self.io_is_busy.map(|busy| u8::from(busy).to_string()),
+            ),
+        ]
+        .into_iter()
+        .filter_map(|(tunable, value)| Some((tunable, value?)))
+        .collect()
+    }
+}
+
+/// Tunables of the conservative governor, from `[<profile>.conservative]`
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct ConservativeSettings {
+    pub freq_step: Option<u8>,
+    pub down_threshold: Option<u8>,
+}
+
+impl ConservativeSettings {
+    /// The tunables that are set, as written to sysfs
+    pub fn tunables(&self) -> Vec<(&'static str, String)> {
+        [
+            ("freq_step", self.freq_step.map(|step| step.to_string())),
+            ("down_threshold", self.down_threshold.map(|t| t.to_string())),
+        ]
+        .into_iter()
+        .filter_map(|(tunable, value)| Some((tunable, value?)))
+        .collect()
+    }
+}
+
+/// Profile settings layered on top of the selected profile, e.g. by battery tiers
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct ProfileOverrides {
+    pub governor: Option<String>,
+    pub turbo: Option<TurboSetting>,
+    pub epp: Option<String>,
+    pub epb: Option<String>,
+    #[serde(alias = "min_freq_mhz")]
+    pub min_freq: Option<FrequencyLimit>,
+    #[serde(alias = "max_freq_mhz")]
+    pub max_freq: Option<FrequencyLimit>,
+    pub uncore_min_freq_mhz: Option<u32>,
+    pub uncore_max_freq_mhz: Option<u32>,
+    pub gpu_min_freq_mhz: Option<u32>,
+    pub gpu_max_freq_mhz: Option<u32>,
+    pub gpu_boost_freq_mhz: Option<u32>,
+    pub gpu_dpm_level: Option<String>,
+    pub gpu_power_profile: Option<String>,
+    pub platform_profile: Option<String>,
+    pub cpuidle_governor: Option<String>,
+    pub disabled_idle_states: Option<Vec<String>>,
+    pub online_cores: Option<OnlineCores>,
+    pub smt: Option<SmtSetting>,
+    pub package_power_limit_w: Option<f32>,
+    pub package_power_window_sec: Option<f32>,
+    pub package_power_limit_short_w: Option<f32>,
+    pub package_power_window_short_sec: Option<f32>,
+}
+
+/// A `[[battery_tiers]]` entry, applied on battery power while the average
+/// battery capacity is below `below`
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct BatteryTierConfig {
+    /// Capacity in percent below which the tier becomes active
+    pub below: u8,
+    /// The tier is left once capacity reaches `below + hysteresis`
+    #[serde(default = "default_tier_hysteresis")]
+    pub hysteresis: u8,
+    /// Settings applied while the tier is active
+    #[serde(flatten)]
+    pub settings: ProfileOverrides,
 }
 
 impl Default for ProfileConfig {
@@ -74,14 +361,36 @@ impl Default for ProfileConfig {
         Self {
             governor: Some("schedutil".to_string()), // common sensible default (?)
             turbo: Some(TurboSetting::Auto),
-            epp: None,              // defaults depend on governor and system
-            epb: None,              // defaults depend on governor and system
-            min_freq_mhz: None,     // no override
-            max_freq_mhz: None,     // no override
+            epp: None,      // defaults depend on governor and system
+            epb: None,      // defaults depend on governor and system
+            min_freq: None, // no override
+            max_freq: None, // no override
+            uncore_min_freq_mhz: None,
+            uncore_max_freq_mhz: None,
+            gpu_min_freq_mhz: None,
+            gpu_max_freq_mhz: None,
+            gpu_boost_freq_mhz: None,
+            gpu_dpm_level: None,
+            gpu_power_profile: None,
+            cpuidle_governor: None,
+            disabled_idle_states: None,
             platform_profile: None, // no override
+            online_cores: None,     // keep every core online
+            smt: None,              // leave SMT as configured by the system
+            package_power_limit_w: None,
+            package_power_window_sec: None,
+            package_power_limit_short_w: None,
+            package_power_window_short_sec: None,
             turbo_auto_settings: TurboAutoSettings::default(),
             enable_auto_turbo: default_enable_auto_turbo(),
             battery_charge_thresholds: None,
+            p_cores: CoreTypeSettings::default(),
+            e_cores: CoreTypeSettings::default(),
+            intel_pstate: IntelPstateSettings::default(),
+            amd_pstate: AmdPstateSettings::default(),
+            schedutil: SchedutilSettings::default(),
+            ondemand: OndemandSettings::default(),
+            conservative: ConservativeSettings::default(),
         }
     }
 }
@@ -92,9 +401,459 @@ pub struct AppConfig {
     pub charger: ProfileConfig,
     #[serde(default)]
     pub battery: ProfileConfig,
+    /// Named profiles from `[profiles.<name>]`, with `extends` already resolved
+    #[serde(default)]
+    pub profiles: BTreeMap<String, ProfileConfig>,
+    /// Profile selection rules, highest priority first
+    #[serde(default)]
+    pub rules: Vec<RuleConfig>,
+    /// Battery capacity tiers, highest threshold first
+    #[serde(default)]
+    pub battery_tiers: Vec<BatteryTierConfig>,
+    /// Time-of-day schedules, the first active one applies
+    #[serde(default)]
+    pub schedules: Vec<ScheduleConfig>,
+    /// Triggers matching running processes, the first active one applies
+    #[serde(default)]
+    pub process_triggers: Vec<ProcessTriggerConfig>,
     pub ignored_power_supplies: Option<Vec<String>>,
     #[serde(default)]
     pub daemon: DaemonConfig,
+    /// Where each configured value came from
+    #[serde(skip)]
+    pub sources: ConfigSources,
+}
+
+/// Names of the built-in profiles used for AC and battery power
+pub const BUILTIN_PROFILES: [&str; 2] = ["charger", "battery"];
+
+impl AppConfig {
+    /// Look up a profile by name, including the built-in `charger` and `battery` profiles
+    pub fn profile(&self, name: &str) -> Option<&ProfileConfig> {
+        match name {
+            "charger" => Some(&self.charger),
+            "battery" => Some(&self.battery),
+            _ => self.profiles.get(name),
+        }
+    }
+
+    /// Names of every profile, built-in profiles first
+    pub fn profile_names(&self) -> impl Iterator<Item = &str> {
+        BUILTIN_PROFILES
+            .into_iter()
+            .chain(self.profiles.keys().map(String::as_str))
+    }
+}
+
+/// A `[[rules]]` entry selecting a profile when all of its conditions hold
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct RuleConfig {
+    /// Name shown in logs and `watt explain`. Unnamed rules are named after
+    /// their position (`rule #1`) when the configuration is loaded.
+    pub name: Option<String>,
+    /// Profile to apply while the rule matches
+    pub profile: String,
+    /// Rules with a higher priority are evaluated first
+    #[serde(default)]
+    pub priority: i32,
+    /// How long the conditions must hold, or stop holding, before the rule
+    /// takes effect or is released
+    #[serde(default)]
+    pub debounce_sec: u64,
+    /// Conditions that must all hold, an empty table always matches
+    #[serde(default)]
+    pub when: RuleConditions,
+}
+
+/// A `[[schedules]]` entry, active on `days` between `from` and `to` in local time
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct ScheduleConfig {
+    /// Name shown in logs and `watt explain`. Unnamed schedules are named after
+    /// their position (`schedule #1`) when the configuration is loaded.
+    pub name: Option<String>,
+    /// Days the window starts on, every day if empty
+    #[serde(default)]
+    pub days: Vec<ScheduleDay>,
+    /// Start of the window
+    pub from: ClockTime,
+    /// End of the window. A window ending at or before its start runs past midnight.
+    pub to: ClockTime,
+    /// Profile to select while the schedule is active
+    pub profile: Option<String>,
+    /// Settings layered on top of the selected profile while the schedule is active
+    #[serde(flatten)]
+    pub settings: ProfileOverrides,
+}
+
+/// A `[[process_triggers]]` entry, active while a process matching one of
+/// `processes` runs
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct ProcessTriggerConfig {
+    /// Name shown in logs and `watt explain`. Unnamed triggers are named after
+    /// their position (`process trigger #1`) when the configuration is loaded.
+    pub name: Option<String>,
+    /// Patterns with `*` and `?` wildcards, matched against the command name
+    /// and the executable name of every process
+    pub processes: Vec<String>,
+    /// Combined CPU usage of the matching processes above which the trigger
+    /// becomes active, in percent of one CPU. 100 is one fully busy CPU, so
+    /// the value may exceed 100 on machines with several CPUs.
+    pub cpu_above: Option<f32>,
+    /// How long the trigger stays active after it stops matching
+    #[serde(default)]
+    pub hold_sec: u64,
+    /// Profile to select while the trigger is active
+    pub profile: Option<String>,
+    /// Settings layered on top of the selected profile while the trigger is active
+    #[serde(flatten)]
+    pub settings: ProfileOverrides,
+}
+
+/// Cores kept online by a profile, from `online_cores = 4` or
+/// `online_cores = "0-3"`
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(try_from = "OnlineCoresValue", into = "OnlineCoresValue")]
+pub enum OnlineCores {
+    /// Number of cores to keep online, parking the others
+    Count(u32),
+    /// IDs of the cores to keep online
+    Cpus(Vec<u32>),
+}
+
+impl fmt::Display for OnlineCores {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Count(count) => write!(f, "{count} cores"),
+            Self::Cpus(cpus) => write!(f, "cores {}", topology::format_cpu_list(cpus)),
+        }
+    }
+}
+
+/// How `online_cores` is written in the configuration
+#[derive(Deserialize, Serialize)]
+#[serde(untagged)]
+enum OnlineCoresValue {
+    Count(i64),
+    List(String),
+}
+
+impl TryFrom<OnlineCoresValue> for OnlineCores {
+    type Error = String;
+
+    fn try_from(value: OnlineCoresValue) -> Result<Self, Self::Error> {
+        match value {
+            OnlineCoresValue::Count(count) => u32::try_from(count)
+                .ok()
+                .filter(|count| *count > 0)
+                .map(Self::Count)
+                .ok_or_else(|| format!("online_cores ({count}) must be at least 1")),
+            OnlineCoresValue::List(list) => topology::parse_cpu_list(&list)
+                .filter(|cpus| !cpus.is_empty())
+                .map(Self::Cpus)
+                .ok_or_else(|| format!("invalid CPU list `{list}`, expected e.g. \"0-3,6\"")),
+        }
+    }
+}
+
+impl From<OnlineCores> for OnlineCoresValue {
+    fn from(cores: OnlineCores) -> Self {
+        match cores {
+            OnlineCores::Count(count) => Self::Count(i64::from(count)),
+            OnlineCores::Cpus(cpus) => Self::List(topology::format_cpu_list(&cpus)),
+        }
+    }
+}
+
+/// A CPU frequency limit, either absolute or relative to the hardware limits
+/// of each core, from e.g. `max_freq = 2400`, `"2.4GHz"`, `"70%"` or `"base"`
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(try_from = "FrequencyLimitValue", into = "FrequencyLimitValue")]
+pub enum FrequencyLimit {
+    /// Frequency in MHz
+    Mhz(u32),
+    /// Percentage of the core's highest frequency (`cpuinfo_max_freq`)
+    Percent(f32),
+    /// The core's lowest frequency (`cpuinfo_min_freq`)
+    Min,
+    /// The core's base frequency (`base_frequency`)
+    Base,
+    /// The core's highest frequency (`cpuinfo_max_freq`)
+    Max,
+}
+
+impl FrequencyLimit {
+    fn from_mhz(mhz: f64) -> Result<Self, String> {
+        // Extremely high values are unlikely to be valid
+        if !(1.0..=10000.0).contains(&mhz) {
+            return Err(format!(
+                "frequency ({mhz} MHz) must be between 1 and 10000 MHz"
+            ));
+        }
+        Ok(Self::Mhz(mhz.round() as u32))
+    }
+}
+
+impl fmt::Display for FrequencyLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Mhz(mhz) => write!(f, "{mhz} MHz"),
+            Self::Percent(percent) => write!(f, "{percent}%"),
+            Self::Min => write!(f, "min"),
+            Self::Base => write!(f, "base"),
+            Self::Max => write!(f, "max"),
+        }
+    }
+}
+
+impl std::str::FromStr for FrequencyLimit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let value = s.trim().to_ascii_lowercase();
+        let number = |number: &str| {
+            number.trim().parse::<f64>().map_err(|_| {
+                format!(
+                    "invalid frequency `{s}`, expected MHz or e.g. \"2.4GHz\", \"70%\", \"base\" or \"max\""
+                )
+            })
+        };
+
+        match value.as_str() {
+            "min" => Ok(Self::Min),
+            "base" => Ok(Self::Base),
+            "max" => Ok(Self::Max),
+            _ => {
+                if let Some(percent) = value.strip_suffix('%') {
+                    let percent = number(percent)?;
+                    if percent.is_nan() || percent <= 0.0 || percent > 100.0 {
+                        return Err(format!(
+                            "frequency percentage ({percent}%) must be between 0 and 100"
+                        ));
+                    }
+                    Ok(Self::Percent(percent as f32))
+                } else if let Some(ghz) = value.strip_suffix("ghz") {
+                    Self::from_mhz(number(ghz)? * 1000.0)
+                } else if let Some(mhz) = value.strip_suffix("mhz") {
+                    Self::from_mhz(number(mhz)?)
+                } else {
+                    Self::from_mhz(number(&value)?)
+                }
+            }
+        }
+    }
+}
+
+/// How a frequency limit is written in the configuration
+#[derive(Deserialize, Serialize)]
+#[serde(untagged)]
+enum FrequencyLimitValue {
+    Mhz(i64),
+    Text(String),
+}
+
+impl TryFrom<FrequencyLimitValue> for FrequencyLimit {
+    type Error = String;
+
+    fn try_from(value: FrequencyLimitValue) -> Result<Self, Self::Error> {
+        match value {
+            FrequencyLimitValue::Mhz(mhz) => Self::from_mhz(mhz as f64),
+            FrequencyLimitValue::Text(text) => text.parse(),
+        }
+    }
+}
+
+impl From<FrequencyLimit> for FrequencyLimitValue {
+    fn from(limit: FrequencyLimit) -> Self {
+        match limit {
+            FrequencyLimit::Mhz(mhz) => Self::Mhz(i64::from(mhz)),
+            other => Self::Text(other.to_string()),
+        }
+    }
+}
+
+/// A day, or group of days, in a schedule's `days` list
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ScheduleDay {
+    Mon,
+    Tue,
+    Wed,
+    Thu,
+    Fri,
+    Sat,
+    Sun,
+    /// Monday to Friday
+    Weekdays,
+    /// Saturday and Sunday
+    Weekends,
+}
+
+impl ScheduleDay {
+    pub fn contains(self, weekday: jiff::civil::Weekday) -> bool {
+        use jiff::civil::Weekday;
+
+        match self {
+            Self::Weekdays => !matches!(weekday, Weekday::Saturday | Weekday::Sunday),
+            Self::Weekends => matches!(weekday, Weekday::Saturday | Weekday::Sunday),
+            day => {
+                let offset = day as i8 + 1;
+                weekday.to_monday_one_offset() == offset
+            }
+        }
+    }
+}
+
+/// A time of day written as `"HH:MM"` or `"HH:MM:SS"`
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(try_from = "String", into = "String")]
+pub struct ClockTime(pub jiff::civil::Time);
+
+impl TryFrom<String> for ClockTime {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value
+            .parse()
+            .map(Self)
+            .map_err(|_| format!("invalid time of day `{value}`, expected \"HH:MM\""))
+    }
+}
+
+impl From<ClockTime> for String {
+    fn from(time: ClockTime) -> Self {
+        time.to_string()
+    }
+}
+
+impl fmt::Display for ClockTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0.second() == 0 {
+            write!(f, "{:02}:{:02}", self.0.hour(), self.0.minute())
+        } else {
+            write!(
+                f,
+                "{:02}:{:02}:{:02}",
+                self.0.hour(),
+                self.0.minute(),
+                self.0.second()
+            )
+        }
+    }
+}
+
+/// Conditions of a rule, each compared against the current system state
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct RuleConditions {
+    /// Whether the system is on AC power
+    pub ac: Option<bool>,
+    /// Battery capacity in percent is below this value
+    pub battery_below: Option<u8>,
+    /// Battery capacity in percent is above this value
+    pub battery_above: Option<u8>,
+    /// Average CPU temperature in °C is above this value
+    pub temp_above: Option<f32>,
+    /// Average CPU temperature in °C is below this value
+    pub temp_below: Option<f32>,
+    /// Average CPU usage in percent is above this value
+    pub usage_above: Option<f32>,
+    /// Average CPU usage in percent is below this value
+    pub usage_below: Option<f32>,
+    /// Highest GPU busy percentage is above this value
+    pub gpu_busy_above: Option<f32>,
+    /// Highest GPU busy percentage is below this value
+    pub gpu_busy_below: Option<f32>,
+    /// 1-minute load average is above this value
+    pub load_above: Option<f32>,
+    /// 1-minute load average is below this value
+    pub load_below: Option<f32>,
+    /// The system has been idle for at least this many seconds (daemon only)
+    pub idle_for_sec: Option<u64>,
+    /// Battery discharge rate in %/hour is above this value (daemon only)
+    pub discharge_above: Option<f32>,
+}
+
+/// Where a configuration value came from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigSource {
+    /// Built-in default value
+    Default,
+    /// A configuration file found on one of the default paths
+    File(PathBuf),
+    /// A configuration file selected through an environment variable
+    Env {
+        variable: &'static str,
+        path: PathBuf,
+    },
+}
+
+impl fmt::Display for ConfigSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Default => write!(f, "default"),
+            Self::File(path) => write!(f, "{}", path.display()),
+            Self::Env { variable, path } => write!(f, "{} (${variable})", path.display()),
+        }
+    }
+}
+
+static DEFAULT_SOURCE: ConfigSource = ConfigSource::Default;
+
+/// Tracks the configuration layers that were loaded and the source of every
+/// value they set, keyed by its dotted path (e.g. `charger.governor`).
+/// Unrecorded values are defaults.
+#[derive(Debug, Clone, Default)]
+pub struct ConfigSources {
+    layers: Vec<ConfigSource>,
+    keys: BTreeMap<String, ConfigSource>,
+}
+
+impl ConfigSources {
+    /// Record every value in `table` as coming from `source`
+    pub fn record_table(&mut self, prefix: &str, table: &toml::Table, source: &ConfigSource) {
+        for (key, value) in table {
+            let path = if prefix.is_empty() {
+                key.clone()
+            } else {
+                format!("{prefix}.{key}")
+            };
+
+            match value {
+                toml::Value::Table(table) => self.record_table(&path, table, source),
+                _ => self.record(path, source.clone()),
+            }
+        }
+    }
+
+    /// Record the value at `path`, replacing anything recorded below it
+    pub fn record(&mut self, path: String, source: ConfigSource) {
+        let prefix = format!("{path}.");
+        self.keys.retain(|key, _| !key.starts_with(&prefix));
+        self.keys.insert(path, source);
+    }
+
+    pub fn add_layer(&mut self, source: ConfigSource) {
+        self.layers.push(source);
+    }
+
+    /// The configuration files that were loaded, lowest precedence first
+    pub fn layers(&self) -> &[ConfigSource] {
+        &self.layers
+    }
+
+    /// Get the source of the value at `path`, falling back to the closest
+    /// recorded parent for values set as a whole (e.g. `[40, 80]` thresholds)
+    pub fn source_of(&self, path: &str) -> &ConfigSource {
+        let mut path = path;
+        loop {
+            if let Some(source) = self.keys.get(path) {
+                return source;
+            }
+            match path.rfind('.') {
+                Some(dot) => path = &path[..dot],
+                None => return &DEFAULT_SOURCE,
+            }
+        }
+    }
 }
 
 // Error type for config loading
@@ -108,23 +867,50 @@ pub enum ConfigError {
 
     #[error("Configuration validation error: {0}")]
     Validation(String),
+
+    #[error("JSON serialization error: {0}")]
+    Json(#[from] serde_json::Error),
 }
 
 // Intermediate structs for TOML parsing
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct ProfileConfigToml {
     pub governor: Option<String>,
-    pub turbo: Option<String>, // "always", "auto", "never"
+    pub turbo: Option<TurboSetting>,
     pub epp: Option<String>,
     pub epb: Option<String>,
-    pub min_freq_mhz: Option<u32>,
-    pub max_freq_mhz: Option<u32>,
+    #[serde(alias = "min_freq_mhz")]
+    pub min_freq: Option<FrequencyLimit>,
+    #[serde(alias = "max_freq_mhz")]
+    pub max_freq: Option<FrequencyLimit>,
+    pub uncore_min_freq_mhz: Option<u32>,
+    pub uncore_max_freq_mhz: Option<u32>,
+    pub gpu_min_freq_mhz: Option<u32>,
+    pub gpu_max_freq_mhz: Option<u32>,
+    pub gpu_boost_freq_mhz: Option<u32>,
+    pub gpu_dpm_level: Option<String>,
+    pub gpu_power_profile: Option<String>,
     pub platform_profile: Option<String>,
+    pub cpuidle_governor: Option<String>,
+    pub disabled_idle_states: Option<Vec<String>>,
+    pub online_cores: Option<OnlineCores>,
+    pub smt: Option<SmtSetting>,
+    pub package_power_limit_w: Option<f32>,
+    pub package_power_window_sec: Option<f32>,
+    pub package_power_limit_short_w: Option<f32>,
+    pub package_power_window_short_sec: Option<f32>,
     pub turbo_auto_settings: Option<TurboAutoSettings>,
     #[serde(default = "default_enable_auto_turbo")]
     pub enable_auto_turbo: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub battery_charge_thresholds: Option<BatteryChargeThresholds>,
+    pub p_cores: Option<CoreTypeSettings>,
+    pub e_cores: Option<CoreTypeSettings>,
+    pub intel_pstate: Option<IntelPstateSettings>,
+    pub amd_pstate: Option<AmdPstateSettings>,
+    pub schedutil: Option<SchedutilSettings>,
+    pub ondemand: Option<OndemandSettings>,
+    pub conservative: Option<ConservativeSettings>,
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone, Default)]
@@ -133,6 +919,16 @@ pub struct AppConfigToml {
     pub charger: ProfileConfigToml,
     #[serde(default)]
     pub battery: ProfileConfigToml,
+    #[serde(default)]
+    pub profiles: BTreeMap<String, ProfileConfigToml>,
+    #[serde(default)]
+    pub rules: Vec<RuleConfig>,
+    #[serde(default)]
+    pub battery_tiers: Vec<BatteryTierConfig>,
+    #[serde(default)]
+    pub schedules: Vec<ScheduleConfig>,
+    #[serde(default)]
+    pub process_triggers: Vec<ProcessTriggerConfig>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub battery_charge_thresholds: Option<BatteryChargeThresholds>,
     pub ignored_power_supplies: Option<Vec<String>>,
@@ -144,15 +940,37 @@ impl Default for ProfileConfigToml {
     fn default() -> Self {
         Self {
             governor: Some("schedutil".to_string()),
-            turbo: Some("auto".to_string()),
+            turbo: Some(TurboSetting::Auto),
             epp: None,
             epb: None,
-            min_freq_mhz: None,
-            max_freq_mhz: None,
+            min_freq: None,
+            max_freq: None,
+            uncore_min_freq_mhz: None,
+            uncore_max_freq_mhz: None,
+            gpu_min_freq_mhz: None,
+            gpu_max_freq_mhz: None,
+            gpu_boost_freq_mhz: None,
+            gpu_dpm_level: None,
+            gpu_power_profile: None,
+            cpuidle_governor: None,
+            disabled_idle_states: None,
             platform_profile: None,
+            online_cores: None,
+            smt: None,
+            package_power_limit_w: None,
+            package_power_window_sec: None,
+            package_power_limit_short_w: None,
+            package_power_window_short_sec: None,
             turbo_auto_settings: None,
             enable_auto_turbo: default_enable_auto_turbo(),
             battery_charge_thresholds: None,
+            p_cores: None,
+            e_cores: None,
+            intel_pstate: None,
+            amd_pstate: None,
+            schedutil: None,
+            ondemand: None,
+            conservative: None,
         }
     }
 }
@@ -210,22 +1028,37 @@ impl From<ProfileConfigToml> for ProfileConfig {
     fn from(toml_config: ProfileConfigToml) -> Self {
         Self {
             governor: toml_config.governor,
-            turbo: toml_config
-                .turbo
-                .and_then(|s| match s.to_lowercase().as_str() {
-                    "always" => Some(TurboSetting::Always),
-                    "auto" => Some(TurboSetting::Auto),
-                    "never" => Some(TurboSetting::Never),
-                    _ => None,
-                }),
+            turbo: toml_config.turbo,
             epp: toml_config.epp,
             epb: toml_config.epb,
-            min_freq_mhz: toml_config.min_freq_mhz,
-            max_freq_mhz: toml_config.max_freq_mhz,
+            min_freq: toml_config.min_freq,
+            max_freq: toml_config.max_freq,
+            uncore_min_freq_mhz: toml_config.uncore_min_freq_mhz,
+            uncore_max_freq_mhz: toml_config.uncore_max_freq_mhz,
+            gpu_min_freq_mhz: toml_config.gpu_min_freq_mhz,
+            gpu_max_freq_mhz: toml_config.gpu_max_freq_mhz,
+            gpu_boost_freq_mhz: toml_config.gpu_boost_freq_mhz,
+            gpu_dpm_level: toml_config.gpu_dpm_level,
+            gpu_power_profile: toml_config.gpu_power_profile,
+            cpuidle_governor: toml_config.cpuidle_governor,
+            disabled_idle_states: toml_config.disabled_idle_states,
             platform_profile: toml_config.platform_profile,
+            online_cores: toml_config.online_cores,
+            smt: toml_config.smt,
+            package_power_limit_w: toml_config.package_power_limit_w,
+            package_power_window_sec: toml_config.package_power_window_sec,
+            package_power_limit_short_w: toml_config.package_power_limit_short_w,
+            package_power_window_short_sec: toml_config.package_power_window_short_sec,
             turbo_auto_settings: toml_config.turbo_auto_settings.unwrap_or_default(),
             enable_auto_turbo: toml_config.enable_auto_turbo,
             battery_charge_thresholds: toml_config.battery_charge_thresholds,
+            p_cores: toml_config.p_cores.unwrap_or_default(),
+            e_cores: toml_config.e_cores.unwrap_or_default(),
+            intel_pstate: toml_config.intel_pstate.unwrap_or_default(),
+            amd_pstate: toml_config.amd_pstate.unwrap_or_default(),
+            schedutil: toml_config.schedutil.unwrap_or_default(),
+            ondemand: toml_config.ondemand.unwrap_or_default(),
+            conservative: toml_config.conservative.unwrap_or_default(),
         }
     }
 }
@@ -278,6 +1111,7 @@ default_const!(default_throttle_on_battery, bool, true);
 default_const!(default_log_level, LogLevel, LogLevel::Info);
 default_const!(default_stats_file_path, Option<String>, None);
 default_const!(default_enable_auto_turbo, bool, true);
+default_const!(default_tier_hysteresis, u8, 3);
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct DaemonConfigToml {
@@ -310,3 +1144,48 @@ impl Default for DaemonConfigToml {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(value: &str) -> Result<FrequencyLimit, String> {
+        value.parse()
+    }
+
+    #[test]
+    fn frequencies_are_read_in_mhz_or_ghz() {
+        assert_eq!(parse("2400"), Ok(FrequencyLimit::Mhz(2400)));
+        assert_eq!(parse("2400MHz"), Ok(FrequencyLimit::Mhz(2400)));
+        assert_eq!(parse(" 2400 mhz "), Ok(FrequencyLimit::Mhz(2400)));
+        assert_eq!(parse("2.4GHz"), Ok(FrequencyLimit::Mhz(2400)));
+        assert_eq!(parse("1.25ghz"), Ok(FrequencyLimit::Mhz(1250)));
+    }
+
+    #[test]
+    fn percentages_and_names_are_relative_to_the_core() {
+        assert_eq!(parse("70%"), Ok(FrequencyLimit::Percent(70.0)));
+        assert_eq!(parse("100%"), Ok(FrequencyLimit::Percent(100.0)));
+        assert_eq!(parse("min"), Ok(FrequencyLimit::Min));
+        assert_eq!(parse("Base"), Ok(FrequencyLimit::Base));
+        assert_eq!(parse("MAX"), Ok(FrequencyLimit::Max));
+    }
+
+    #[test]
+    fn out_of_range_frequencies_are_rejected() {
+        assert!(parse("0").is_err());
+        assert!(parse("0%").is_err());
+        assert!(parse("101%").is_err());
+        assert!(parse("20GHz").is_err());
+        assert!(parse("-800").is_err());
+    }
+
+    #[test]
+    fn malformed_frequencies_are_rejected() {
+        assert!(parse("").is_err());
+        assert!(parse("fast").is_err());
+        assert!(parse("GHz").is_err());
+        assert!(parse("%").is_err());
+        assert!(parse("2.4 THz").is_err());
+    }
+}
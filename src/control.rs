@@ -0,0 +1,197 @@
+// Control socket through which the CLI hands requests to a running daemon
+use crate::util::error::ControlError;
+use log::{debug, warn};
+use std::fmt;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+pub type Result<T, E = ControlError> = std::result::Result<T, E>;
+
+/// Socket the daemon listens on. Only root may connect to it.
+pub const CONTROL_SOCKET_PATH: &str = "/run/watt/control.sock";
+
+/// How long either side waits for the other before giving up
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A request to the daemon, sent as a single line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Request {
+    /// Select a profile persistently and apply it
+    SetProfile(String),
+    /// Return to automatic profile selection and apply the selected profile
+    ResetProfile,
+}
+
+impl Request {
+    fn parse(line: &str) -> Option<Self> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            ["profile", "set", name] => Some(Self::SetProfile((*name).to_string())),
+            ["profile", "reset"] => Some(Self::ResetProfile),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Request {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::SetProfile(name) => write!(f, "profile set {name}"),
+            Self::ResetProfile => write!(f, "profile reset"),
+        }
+    }
+}
+
+/// The daemon's answer to a request. It is sent as `ok` or `error` on the
+/// first line, followed by lines to show to the user.
+#[derive(Debug, Clone)]
+pub struct Reply {
+    pub ok: bool,
+    pub lines: Vec<String>,
+}
+
+impl Reply {
+    pub fn error(message: impl Into<String>) -> Self {
+        Self {
+            ok: false,
+            lines: vec![message.into()],
+        }
+    }
+}
+
+/// Listen on the control socket in a background thread, answering every
+/// request with `handle`
+///
+/// # Errors
+///
+/// Fails if the socket cannot be created, or another daemon is listening on it.
+pub fn serve(handle: impl Fn(Request) -> Reply + Send + 'static) -> io::Result<()> {
+    let path = Path::new(CONTROL_SOCKET_PATH);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    if path.exists() {
+        if UnixStream::connect(path).is_ok() {
+            return Err(io::Error::new(
+                io::ErrorKind::AddrInUse,
+                format!("another daemon is listening on {CONTROL_SOCKET_PATH}"),
+            ));
+        }
+        // Left behind by a daemon that did not exit cleanly
+        fs::remove_file(path)?;
+    }
+
+    let listener = UnixListener::bind(path)?;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    if let Err(e) = answer(&stream, &handle) {
+                        debug!("Control connection failed: {e}");
+                    }
+                }
+                Err(e) => warn!("Failed to accept control connection: {e}"),
+            }
+        }
+    });
+    Ok(())
+}
+
+fn answer(stream: &UnixStream, handle: &impl Fn(Request) -> Reply) -> io::Result<()> {
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    let mut line = String::new();
+    BufReader::new(stream).read_line(&mut line)?;
+
+    let reply = match Request::parse(&line) {
+        Some(request) => {
+            debug!("Control request: {request}");
+            handle(request)
+        }
+        None => Reply::error(format!("Unknown request '{}'", line.trim())),
+    };
+
+    let mut writer = io::BufWriter::new(stream);
+    writeln!(writer, "{}", if reply.ok { "ok" } else { "error" })?;
+    for line in &reply.lines {
+        writeln!(writer, "{line}")?;
+    }
+    writer.flush()
+}
+
+/// Remove the control socket when the daemon exits
+pub fn remove_socket() {
+    if let Err(e) = fs::remove_file(CONTROL_SOCKET_PATH) {
+        if e.kind() != io::ErrorKind::NotFound {
+            warn!("Failed to remove {CONTROL_SOCKET_PATH}: {e}");
+        }
+    }
+}
+
+/// Send a request to the running daemon and wait for its reply. Returns
+/// `Ok(None)` if no daemon is listening.
+pub fn send(request: &Request) -> Result<Option<Reply>> {
+    let stream = match UnixStream::connect(CONTROL_SOCKET_PATH) {
+        Ok(stream) => stream,
+        Err(e)
+            if matches!(
+                e.kind(),
+                io::ErrorKind::NotFound | io::ErrorKind::ConnectionRefused
+            ) =>
+        {
+            return Ok(None);
+        }
+        Err(e) if e.kind() == io::ErrorKind::PermissionDenied => {
+            return Err(ControlError::PermissionDenied(format!(
+                "Permission denied connecting to the daemon at {CONTROL_SOCKET_PATH}"
+            )));
+        }
+        Err(e) => return Err(ControlError::Io(e)),
+    };
+    stream.set_read_timeout(Some(TIMEOUT))?;
+
+    writeln!(&stream, "{request}")?;
+    let mut lines = BufReader::new(&stream).lines();
+    let ok = match lines.next().transpose()?.as_deref() {
+        Some("ok") => true,
+        Some("error") => false,
+        other => {
+            return Err(ControlError::ParseError(format!(
+                "Unexpected reply from the daemon: {other:?}"
+            )));
+        }
+    };
+    Ok(Some(Reply {
+        ok,
+        lines: lines.collect::<io::Result<_>>()?,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requests_round_trip_through_their_line_form() {
+        for request in [
+            Request::SetProfile("quiet".to_string()),
+            Request::ResetProfile,
+        ] {
+            assert_eq!(Request::parse(&request.to_string()), Some(request));
+        }
+    }
+
+    #[test]
+    fn malformed_requests_are_rejected() {
+        assert_eq!(Request::parse(""), None);
+        assert_eq!(Request::parse("profile set"), None);
+        assert_eq!(Request::parse("profile set a b"), None);
+        assert_eq!(Request::parse("reboot"), None);
+    }
+}
@@ -1,14 +1,72 @@
+use crate::topology::{CoreType, CpuTopology};
 use clap::ValueEnum;
 use serde::{Deserialize, Serialize};
 use std::fmt;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
 pub enum TurboSetting {
     Always, // turbo is forced on (if possible)
     Auto,   // system or driver controls turbo
     Never,  // turbo is forced off
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtSetting {
+    On,   // simultaneous multithreading is enabled
+    Off,  // sibling threads are taken offline
+    Auto, // SMT is left as the system configured it
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum IntelPstateStatus {
+    Active,  // intel_pstate selects P-states itself
+    Passive, // intel_pstate acts as a driver for the generic cpufreq governors
+    Off,     // intel_pstate is unregistered
+}
+
+impl IntelPstateStatus {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Active => "active",
+            Self::Passive => "passive",
+            Self::Off => "off",
+        }
+    }
+}
+
+impl fmt::Display for IntelPstateStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum AmdPstateStatus {
+    Active,  // the firmware selects frequencies guided by EPP
+    Guided,  // the governor sets a range and the firmware picks within it
+    Passive, // the governor requests frequencies directly
+}
+
+impl AmdPstateStatus {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Active => "active",
+            Self::Guided => "guided",
+            Self::Passive => "passive",
+        }
+    }
+}
+
+impl fmt::Display for AmdPstateStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
 #[derive(Debug, Clone, Copy, ValueEnum)]
 pub enum GovernorOverrideMode {
     Performance,
@@ -36,6 +94,7 @@ pub struct SystemInfo {
 pub struct CpuCoreInfo {
     // Per-core data
     pub core_id: u32,
+    pub core_type: Option<CoreType>, // only set on hybrid CPUs
     pub current_frequency_mhz: Option<u32>,
     pub min_frequency_mhz: Option<u32>,
     pub max_frequency_mhz: Option<u32>,
@@ -51,7 +110,48 @@ pub struct CpuGlobalInfo {
     pub epp: Option<String>,        // Energy Performance Preference
     pub epb: Option<String>,        // Energy Performance Bias
     pub platform_profile: Option<String>,
+    pub smt_control: Option<String>, // e.g. "on", "off", "forceoff" or "notsupported"
+    pub smt_active: Option<bool>,    // whether sibling threads are running
+    pub package_power_watts: Option<f32>, // CPU package power from the RAPL energy counters
+    pub core_power_watts: Option<f32>, // power of the CPU cores alone
+    pub uncore_power_watts: Option<f32>, // power of the integrated GPU and other uncore parts
+    pub dram_power_watts: Option<f32>, // power of the memory
     pub average_temperature_celsius: Option<f32>, // Average temperature across all cores
+    pub policies: Vec<CpufreqPolicyInfo>, // state of every cpufreq policy
+}
+
+pub struct CpufreqPolicyInfo {
+    pub id: u32,
+    pub cpus: Vec<u32>,         // online CPUs sharing the policy
+    pub driver: Option<String>, // scaling driver, e.g. "intel_pstate"
+    pub governor: Option<String>,
+    pub epp: Option<String>,
+    pub min_freq_mhz: Option<u32>, // scaling limits
+    pub max_freq_mhz: Option<u32>,
+}
+
+impl CpufreqPolicyInfo {
+    /// Whether two policies are set up the same way, regardless of their CPUs
+    pub fn same_settings(&self, other: &Self) -> bool {
+        self.driver == other.driver
+            && self.governor == other.governor
+            && self.epp == other.epp
+            && self.min_freq_mhz == other.min_freq_mhz
+            && self.max_freq_mhz == other.max_freq_mhz
+    }
+}
+
+pub struct GpuInfo {
+    pub card: String,                    // DRM card name, e.g. "card1"
+    pub driver: String,                  // kernel driver, e.g. "i915" or "xe"
+    pub current_freq_mhz: Option<u32>,   // actual frequency
+    pub requested_freq_mhz: Option<u32>, // frequency requested by the driver
+    pub min_freq_mhz: Option<u32>,
+    pub max_freq_mhz: Option<u32>,
+    pub boost_freq_mhz: Option<u32>,   // i915 only
+    pub busy_percent: Option<u8>,      // GPU load, amdgpu only
+    pub dpm_level: Option<String>,     // amdgpu power_dpm_force_performance_level
+    pub power_profile: Option<String>, // active amdgpu pp_power_profile_mode
 }
 
 pub struct BatteryInfo {
@@ -77,13 +177,9 @@ pub struct SystemReport {
     pub system_info: SystemInfo,
     pub cpu_cores: Vec<CpuCoreInfo>,
     pub cpu_global: CpuGlobalInfo,
+    pub topology: CpuTopology,
+    pub gpus: Vec<GpuInfo>,
     pub batteries: Vec<BatteryInfo>,
     pub system_load: SystemLoad,
     pub timestamp: std::time::SystemTime, // so we know when the report was generated
 }
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum OperationalMode {
-    Powersave,
-    Performance,
-}
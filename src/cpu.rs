@@ -1,8 +1,17 @@
-use crate::core::{GovernorOverrideMode, TurboSetting};
+use crate::config::{FrequencyLimit, OnlineCores};
+use crate::core::{GovernorOverrideMode, SmtSetting, TurboSetting};
+use crate::restore;
+use crate::topology::{self, CoreType, CpuTopology};
 use crate::util::error::ControlError;
 use core::str;
-use log::debug;
-use std::{fs, io, path::Path, string::ToString};
+use log::{debug, info, warn};
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Mutex;
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    string::ToString,
+};
 
 pub type Result<T, E = ControlError> = std::result::Result<T, E>;
 
@@ -30,6 +39,7 @@ const EPP_FALLBACK_VALUES: &[&str] = &[
 // Write a value to a sysfs file
 fn write_sysfs_value(path: impl AsRef<Path>, value: &str) -> Result<()> {
     let p = path.as_ref();
+    restore::journal_write(p);
 
     fs::write(p, value).map_err(|e| {
         let error_msg = format!("Path: {:?}, Value: '{}', Error: {}", p.display(), value, e);
@@ -43,64 +53,155 @@ fn write_sysfs_value(path: impl AsRef<Path>, value: &str) -> Result<()> {
     })
 }
 
-pub fn get_logical_core_count() -> Result<u32> {
-    // Using num_cpus::get() for a reliable count of logical cores accessible.
-    // The monitor module's get_logical_core_count might be more specific to cpufreq-capable cores,
-    // but for applying settings, we might want to iterate over all reported by OS.
-    // However, settings usually apply to cores with cpufreq.
-    // Let's use a similar discovery to monitor's get_logical_core_count
-    let mut num_cores: u32 = 0;
-    let path = Path::new("/sys/devices/system/cpu");
-    if !path.exists() {
-        return Err(ControlError::NotSupported(format!(
-            "No logical cores found at {}.",
-            path.display()
-        )));
+/// IDs of the online CPUs. IDs may have gaps when cores are parked or were
+/// never brought up, so callers must not assume they are contiguous.
+pub fn get_online_cpus() -> Result<Vec<u32>> {
+    let path = "/sys/devices/system/cpu/online";
+    if let Some(cpus) = fs::read_to_string(path)
+        .ok()
+        .and_then(|list| topology::parse_cpu_list(&list))
+        .filter(|cpus| !cpus.is_empty())
+    {
+        return Ok(cpus);
     }
 
-    let entries = fs::read_dir(path)
-        .map_err(|_| {
-            ControlError::PermissionDenied(format!("Cannot read contents of {}.", path.display()))
-        })?
-        .flatten();
+    // Fall back to every CPU directory for kernels without hotplug support
+    let cpus = topology::present_cpus();
+    if cpus.is_empty() {
+        return Err(ControlError::NotSupported(
+            "No logical cores found at /sys/devices/system/cpu.".to_string(),
+        ));
+    }
+    Ok(cpus)
+}
 
-    for entry in entries {
-        let entry_file_name = entry.file_name();
-        let Some(name) = entry_file_name.to_str() else {
-            continue;
-        };
+/// Run a cpufreq write for the given core, or once for every policy
+fn for_each_policy<F>(core_id: Option<u32>, mut action: F) -> Result<()>
+where
+    F: FnMut(u32) -> Result<()>,
+{
+    for (core_id, _) in cpufreq_targets(core_id)? {
+        action(core_id)?;
+    }
+    Ok(())
+}
 
-        // Skip non-CPU directories (e.g., cpuidle, cpufreq)
-        if !name.starts_with("cpu") || name.len() <= 3 || !name[3..].chars().all(char::is_numeric) {
-            continue;
-        }
+/// IDs of the cpufreq policies, in ascending order
+pub fn get_policy_ids() -> Vec<u32> {
+    let Ok(entries) = fs::read_dir("/sys/devices/system/cpu/cpufreq") else {
+        return Vec::new();
+    };
 
-        if !entry.path().join("cpufreq").exists() {
-            continue;
-        }
+    let mut ids: Vec<u32> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name();
+            name.to_str()?.strip_prefix("policy")?.parse().ok()
+        })
+        .collect();
+    ids.sort_unstable();
+    ids
+}
+
+/// A cpufreq policy, i.e. a group of CPUs that share their frequency settings
+#[derive(Debug, Clone)]
+pub struct CpufreqPolicy {
+    pub id: u32,
+    /// Every CPU of the policy, online or not
+    pub related_cpus: Vec<u32>,
+    /// The online CPUs of the policy
+    pub affected_cpus: Vec<u32>,
+}
+
+/// The cpufreq policies, in ascending order
+pub fn get_policies() -> Vec<CpufreqPolicy> {
+    let cpus = |id: u32, file: &str| {
+        fs::read_to_string(format!("/sys/devices/system/cpu/cpufreq/policy{id}/{file}"))
+            .ok()
+            .and_then(|list| topology::parse_cpu_list(list.trim()))
+            .unwrap_or_default()
+    };
+
+    get_policy_ids()
+        .into_iter()
+        .map(|id| CpufreqPolicy {
+            id,
+            related_cpus: cpus(id, "related_cpus"),
+            affected_cpus: cpus(id, "affected_cpus"),
+        })
+        .collect()
+}
 
-        if name[3..].parse::<u32>().is_ok() {
-            num_cores += 1;
+/// The CPUs to write cpufreq settings through, with the online CPUs each one
+/// covers. `cpuN/cpufreq` is the directory of the policy of `cpuN`, so every
+/// policy is written once, through its first online CPU. Kernels without
+/// policies get a write for every online CPU.
+fn cpufreq_targets(core_id: Option<u32>) -> Result<Vec<(u32, Vec<u32>)>> {
+    let policies = get_policies();
+
+    match core_id {
+        Some(id) => {
+            let cpus = policies
+                .into_iter()
+                .find(|policy| policy.related_cpus.contains(&id))
+                .map_or_else(|| vec![id], |policy| policy.affected_cpus);
+            Ok(vec![(id, cpus)])
         }
+        None if policies.is_empty() => Ok(get_online_cpus()?
+            .into_iter()
+            .map(|id| (id, vec![id]))
+            .collect()),
+        None => Ok(policies
+            .into_iter()
+            .filter_map(|policy| Some((*policy.affected_cpus.first()?, policy.affected_cpus)))
+            .collect()),
     }
-    if num_cores == 0 {
-        // Fallback if sysfs iteration above fails to find any cpufreq cores
-        num_cores = num_cpus::get() as u32;
+}
+
+/// Reduce a set of CPUs to one online CPU per cpufreq policy, so per-core
+/// writes reach each policy once. The CPU is taken from the policy's
+/// `affected_cpus`, as its first related CPU may be offline. Online CPUs
+/// without a policy are kept.
+pub fn one_cpu_per_policy(cpus: &[u32]) -> Vec<u32> {
+    let policies = get_policies();
+    let mut seen = BTreeSet::new();
+    let mut targets = Vec::new();
+
+    for &cpu in cpus {
+        match policies
+            .iter()
+            .find(|policy| policy.related_cpus.contains(&cpu))
+        {
+            Some(policy) => {
+                if !seen.insert(policy.id) {
+                    continue;
+                }
+                // Policies whose CPUs are all offline cannot be written
+                let online = policy
+                    .affected_cpus
+                    .iter()
+                    .find(|affected| cpus.contains(affected))
+                    .or(policy.affected_cpus.first());
+                targets.extend(online.copied());
+            }
+            None => targets.push(cpu),
+        }
     }
 
-    Ok(num_cores)
+    online_cpus_of(&targets)
 }
 
-fn for_each_cpu_core<F>(mut action: F) -> Result<()>
-where
-    F: FnMut(u32) -> Result<()>,
-{
-    let num_cores: u32 = get_logical_core_count()?;
-
-    for core_id in 0u32..num_cores {
-        action(core_id)?;
+/// The CPUs of a set that are online. The set is kept as is if the online
+/// CPUs cannot be read.
+pub fn online_cpus_of(cpus: &[u32]) -> Vec<u32> {
+    match get_online_cpus() {
+        Ok(online) => cpus
+            .iter()
+            .copied()
+            .filter(|cpu| online.contains(cpu))
+            .collect(),
+        Err(_) => cpus.to_vec(),
     }
-    Ok(())
 }
 
 pub fn set_governor(governor: &str, core_id: Option<u32>) -> Result<()> {
@@ -127,7 +228,7 @@ pub fn set_governor(governor: &str, core_id: Option<u32>) -> Result<()> {
         }
     };
 
-    core_id.map_or_else(|| for_each_cpu_core(action), action)
+    for_each_policy(core_id, action)
 }
 
 /// Check if the provided governor is available in the system
@@ -146,7 +247,7 @@ fn is_governor_valid(governor: &str) -> Result<(bool, Vec<String>)> {
 }
 
 /// Get available CPU governors from the system
-fn get_available_governors() -> Result<Vec<String>> {
+pub fn get_available_governors() -> Result<Vec<String>> {
     let cpu_base_path = Path::new("/sys/devices/system/cpu");
 
     // First try the traditional path with cpu0. This is the most common case
@@ -265,9 +366,8 @@ pub fn set_turbo(setting: TurboSetting) -> Result<()> {
 /// Try to set boost on a per-core basis for systems that support it
 fn try_set_per_core_boost(value: &str) -> Result<bool> {
     let mut success = false;
-    let num_cores = get_logical_core_count()?;
 
-    for core_id in 0..num_cores {
+    for (core_id, _) in cpufreq_targets(None)? {
         let boost_path = format!("/sys/devices/system/cpu/cpu{core_id}/cpufreq/boost");
 
         if Path::new(&boost_path).exists() {
@@ -298,11 +398,11 @@ pub fn set_epp(epp: &str, core_id: Option<u32>) -> Result<()> {
             Ok(())
         }
     };
-    core_id.map_or_else(|| for_each_cpu_core(action), action)
+    for_each_policy(core_id, action)
 }
 
 /// Get available EPP values from the system
-fn get_available_epp_values() -> Result<Vec<String>> {
+pub fn get_available_epp_values() -> Result<Vec<String>> {
     let path = "/sys/devices/system/cpu/cpu0/cpufreq/energy_performance_available_preferences";
 
     if !Path::new(path).exists() {
@@ -322,22 +422,33 @@ fn get_available_epp_values() -> Result<Vec<String>> {
         .collect())
 }
 
+/// Set the Energy Performance Bias through `cpuN/power/energy_perf_bias`.
+/// EPB is a per-CPU MSR rather than a cpufreq policy setting, so without a
+/// core ID it is written for every online CPU.
 pub fn set_epb(epb: &str, core_id: Option<u32>) -> Result<()> {
     // Validate EPB value - should be a number 0-15 or a recognized string value
     validate_epb_value(epb)?;
 
-    let action = |id: u32| {
-        let path = format!("/sys/devices/system/cpu/cpu{id}/cpufreq/energy_performance_bias");
+    // The kernel only accepts the hyphenated names
+    let epb = epb.to_ascii_lowercase().replace('_', "-");
+    let cpus = match core_id {
+        Some(id) => vec![id],
+        None => get_online_cpus()?,
+    };
+    for id in cpus {
+        let path = epb_path(id);
         if Path::new(&path).exists() {
-            write_sysfs_value(&path, epb)
-        } else {
-            Ok(())
+            write_sysfs_value(&path, &epb)?;
         }
-    };
-    core_id.map_or_else(|| for_each_cpu_core(action), action)
+    }
+    Ok(())
+}
+
+pub fn epb_path(core_id: u32) -> String {
+    format!("/sys/devices/system/cpu/cpu{core_id}/power/energy_perf_bias")
 }
 
-fn validate_epb_value(epb: &str) -> Result<()> {
+pub fn validate_epb_value(epb: &str) -> Result<()> {
     // EPB can be a number from 0-15 or a recognized string
     // Try parsing as a number first
     if let Ok(value) = epb.parse::<u8>() {
@@ -365,58 +476,248 @@ fn validate_epb_value(epb: &str) -> Result<()> {
     }
 }
 
-pub fn set_min_frequency(freq_mhz: u32, core_id: Option<u32>) -> Result<()> {
-    // Check if the new minimum frequency would be greater than current maximum
-    if let Some(id) = core_id {
-        validate_min_frequency(id, freq_mhz)?;
-    } else {
-        // Check for all cores
-        let num_cores = get_logical_core_count()?;
-        for id in 0..num_cores {
-            validate_min_frequency(id, freq_mhz)?;
+pub fn set_min_frequency(limit: FrequencyLimit, core_id: Option<u32>) -> Result<()> {
+    set_frequency_limits(Some(limit), None, core_id)
+}
+
+pub fn set_max_frequency(limit: FrequencyLimit, core_id: Option<u32>) -> Result<()> {
+    set_frequency_limits(None, Some(limit), core_id)
+}
+
+/// Which scaling limit of a core a frequency is written to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum FrequencyBound {
+    Min,
+    Max,
+}
+
+impl FrequencyBound {
+    const fn label(self) -> &'static str {
+        match self {
+            Self::Min => "Minimum",
+            Self::Max => "Maximum",
         }
     }
 
-    // XXX: We use u64 for the intermediate calculation to prevent overflow
-    let freq_khz = u64::from(freq_mhz) * 1000;
-    let freq_khz_str = freq_khz.to_string();
-
-    let action = |id: u32| {
-        let path = format!("/sys/devices/system/cpu/cpu{id}/cpufreq/scaling_min_freq");
-        if Path::new(&path).exists() {
-            write_sysfs_value(&path, &freq_khz_str)
-        } else {
-            Ok(())
+    const fn file(self) -> &'static str {
+        match self {
+            Self::Min => "scaling_min_freq",
+            Self::Max => "scaling_max_freq",
         }
-    };
-    core_id.map_or_else(|| for_each_cpu_core(action), action)
+    }
 }
 
-pub fn set_max_frequency(freq_mhz: u32, core_id: Option<u32>) -> Result<()> {
-    // Check if the new maximum frequency would be less than current minimum
-    if let Some(id) = core_id {
-        validate_max_frequency(id, freq_mhz)?;
-    } else {
-        // Check for all cores
-        let num_cores = get_logical_core_count()?;
-        for id in 0..num_cores {
-            validate_max_frequency(id, freq_mhz)?;
+/// New limits of a cpufreq policy, in kHz
+struct PolicyLimits {
+    cpus: Vec<u32>,
+    min_khz: Option<u32>,
+    max_khz: Option<u32>,
+}
+
+/// Set the minimum and maximum frequency, or one of them. Both limits are
+/// checked as a pair before writing to any policy, and written in the order
+/// that keeps the minimum below the maximum: raising the minimum above the
+/// current maximum needs the maximum first.
+pub fn set_frequency_limits(
+    min: Option<FrequencyLimit>,
+    max: Option<FrequencyLimit>,
+    core_id: Option<u32>,
+) -> Result<()> {
+    let mut policies: BTreeMap<u32, PolicyLimits> = BTreeMap::new();
+    for (bound, limit) in [(FrequencyBound::Min, min), (FrequencyBound::Max, max)] {
+        let Some(limit) = limit else {
+            continue;
+        };
+        for (id, cpus, freq_khz) in resolve_frequency_limit(bound, limit, core_id)? {
+            let policy = policies.entry(id).or_insert(PolicyLimits {
+                cpus,
+                min_khz: None,
+                max_khz: None,
+            });
+            match bound {
+                FrequencyBound::Min => policy.min_khz = Some(freq_khz),
+                FrequencyBound::Max => policy.max_khz = Some(freq_khz),
+            }
         }
     }
 
-    // XXX: Use a u64 here as well.
-    let freq_khz = u64::from(freq_mhz) * 1000;
-    let freq_khz_str = freq_khz.to_string();
+    for (&id, policy) in &policies {
+        match (policy.min_khz, policy.max_khz) {
+            (Some(min_khz), Some(max_khz)) if min_khz > max_khz => {
+                return Err(ControlError::InvalidValueError(format!(
+                    "Minimum frequency ({} MHz) cannot be higher than maximum frequency ({} MHz) for core {id}",
+                    min_khz / 1000,
+                    max_khz / 1000
+                )));
+            }
+            (Some(min_khz), None) => validate_min_frequency(id, min_khz)?,
+            (None, Some(max_khz)) => validate_max_frequency(id, max_khz)?,
+            _ => {}
+        }
+    }
 
-    let action = |id: u32| {
-        let path = format!("/sys/devices/system/cpu/cpu{id}/cpufreq/scaling_max_freq");
-        if Path::new(&path).exists() {
-            write_sysfs_value(&path, &freq_khz_str)
+    // The driver may still adjust the value, so report what took effect
+    let mut effective: BTreeMap<(FrequencyBound, u32), Vec<u32>> = BTreeMap::new();
+    for (id, policy) in policies {
+        let current_max = read_sysfs_value_as_u32(&format!(
+            "/sys/devices/system/cpu/cpu{id}/cpufreq/{}",
+            FrequencyBound::Max.file()
+        ));
+        let min_write = policy.min_khz.map(|freq| (FrequencyBound::Min, freq));
+        let max_write = policy.max_khz.map(|freq| (FrequencyBound::Max, freq));
+        let writes = if policy
+            .min_khz
+            .is_some_and(|min_khz| current_max.is_ok_and(|max_khz| min_khz > max_khz))
+        {
+            [max_write, min_write]
         } else {
-            Ok(())
+            [min_write, max_write]
+        };
+
+        for (bound, freq_khz) in writes.into_iter().flatten() {
+            let path = format!("/sys/devices/system/cpu/cpu{id}/cpufreq/{}", bound.file());
+            write_sysfs_value(&path, &freq_khz.to_string())?;
+
+            let applied_khz = read_sysfs_value_as_u32(&path).unwrap_or(freq_khz);
+            if applied_khz != freq_khz {
+                warn!(
+                    "{} frequency of cores {} is {} MHz instead of the requested {} MHz",
+                    bound.label(),
+                    topology::format_cpu_list(&policy.cpus),
+                    applied_khz / 1000,
+                    freq_khz / 1000
+                );
+            }
+            effective
+                .entry((bound, applied_khz))
+                .or_default()
+                .extend(&policy.cpus);
         }
+    }
+
+    for ((bound, freq_khz), mut cores) in effective {
+        cores.sort_unstable();
+        let noun = if cores.len() == 1 { "core" } else { "cores" };
+        info!(
+            "{} frequency is {} MHz on {noun} {}",
+            bound.label(),
+            freq_khz / 1000,
+            topology::format_cpu_list(&cores)
+        );
+    }
+    Ok(())
+}
+
+/// Frequencies in kHz the driver accepts for a core, in ascending order.
+/// Empty when the driver takes any value, e.g. intel_pstate and amd_pstate.
+fn available_frequencies(cpufreq: &str) -> Vec<u32> {
+    let Ok(content) = fs::read_to_string(format!("{cpufreq}/scaling_available_frequencies")) else {
+        return Vec::new();
     };
-    core_id.map_or_else(|| for_each_cpu_core(action), action)
+
+    let mut frequencies: Vec<u32> = content
+        .split_whitespace()
+        .filter_map(|freq| freq.parse().ok())
+        .collect();
+    frequencies.sort_unstable();
+    frequencies
+}
+
+/// Snap a frequency to the driver's frequency table. Drivers with a table,
+/// such as acpi-cpufreq, round or reject other values. A maximum is snapped
+/// down and a minimum up, so the result stays within the requested range.
+fn snap_frequency(freq_khz: u32, available: &[u32], bound: FrequencyBound) -> u32 {
+    let snapped = match bound {
+        FrequencyBound::Max => available.iter().rev().find(|&&freq| freq <= freq_khz),
+        FrequencyBound::Min => available.iter().find(|&&freq| freq >= freq_khz),
+    };
+    // Outside the table, the closest entry is the only option
+    snapped
+        .or_else(|| {
+            available
+                .iter()
+                .min_by_key(|&&freq| freq.abs_diff(freq_khz))
+        })
+        .copied()
+        .unwrap_or(freq_khz)
+}
+
+/// Resolve a frequency limit to kHz for the given core, or every policy, as
+/// the CPU to write through, the CPUs it covers and the frequency. Cores
+/// without cpufreq are skipped. Values outside a core's hardware range are
+/// clamped to it, with a warning, and then snapped to the frequency table.
+fn resolve_frequency_limit(
+    bound: FrequencyBound,
+    limit: FrequencyLimit,
+    core_id: Option<u32>,
+) -> Result<Vec<(u32, Vec<u32>, u32)>> {
+    let mut targets = Vec::new();
+    let mut clamped = Vec::new();
+    for (id, cpus) in cpufreq_targets(core_id)? {
+        let base = format!("/sys/devices/system/cpu/cpu{id}/cpufreq");
+        if !Path::new(&base).exists() {
+            continue;
+        }
+        let available = available_frequencies(&base);
+
+        let hardware = (
+            read_sysfs_value_as_u32(&format!("{base}/cpuinfo_min_freq")),
+            read_sysfs_value_as_u32(&format!("{base}/cpuinfo_max_freq")),
+        );
+        let (Ok(min_khz), Ok(max_khz)) = hardware else {
+            // Without the hardware limits, only absolute values can be used as is
+            let FrequencyLimit::Mhz(mhz) = limit else {
+                return Err(ControlError::NotSupported(format!(
+                    "Core {id} does not expose its hardware frequency limits to resolve {limit}"
+                )));
+            };
+            targets.push((id, cpus, snap_frequency(mhz * 1000, &available, bound)));
+            continue;
+        };
+
+        let freq_khz = match limit {
+            FrequencyLimit::Mhz(mhz) => u64::from(mhz) * 1000,
+            FrequencyLimit::Percent(percent) => {
+                (f64::from(max_khz) * f64::from(percent) / 100.0).round() as u64
+            }
+            FrequencyLimit::Min => u64::from(min_khz),
+            FrequencyLimit::Max => u64::from(max_khz),
+            FrequencyLimit::Base => u64::from(
+                read_sysfs_value_as_u32(&format!("{base}/base_frequency")).map_err(|_| {
+                    ControlError::NotSupported(format!(
+                        "Core {id} does not report a base frequency"
+                    ))
+                })?,
+            ),
+        };
+
+        let resolved = freq_khz.clamp(u64::from(min_khz), u64::from(max_khz));
+        if resolved != freq_khz {
+            clamped.extend(&cpus);
+        }
+        // The clamp keeps the value within u32
+        let resolved = resolved as u32;
+        let snapped = snap_frequency(resolved, &available, bound);
+        if snapped != resolved {
+            debug!(
+                "Snapped the {} frequency of core {id} from {} MHz to {} MHz",
+                bound.label().to_lowercase(),
+                resolved / 1000,
+                snapped / 1000
+            );
+        }
+        targets.push((id, cpus, snapped));
+    }
+
+    if !clamped.is_empty() {
+        clamped.sort_unstable();
+        warn!(
+            "{} frequency {limit} is outside the hardware range of cores {}, clamped to their limits",
+            bound.label(),
+            topology::format_cpu_list(&clamped)
+        );
+    }
+    Ok(targets)
 }
 
 fn read_sysfs_value_as_u32(path: &str) -> Result<u32> {
@@ -435,7 +736,35 @@ fn read_sysfs_value_as_u32(path: &str) -> Result<u32> {
         .map_err(|e| ControlError::ParseError(format!("Failed to parse value from {path}: {e}")))
 }
 
-fn validate_min_frequency(core_id: u32, new_min_freq_mhz: u32) -> Result<()> {
+/// Get the hardware frequency limits (in MHz) across all cores.
+///
+/// Returns the lowest `cpuinfo_min_freq` and the highest `cpuinfo_max_freq`
+/// found, so heterogeneous systems report the full range any core can reach.
+pub fn get_hardware_frequency_limits() -> Result<(u32, u32)> {
+    let mut limits: Option<(u32, u32)> = None;
+
+    for core_id in get_online_cpus()? {
+        let base = format!("/sys/devices/system/cpu/cpu{core_id}/cpufreq");
+        let (Ok(min_khz), Ok(max_khz)) = (
+            read_sysfs_value_as_u32(&format!("{base}/cpuinfo_min_freq")),
+            read_sysfs_value_as_u32(&format!("{base}/cpuinfo_max_freq")),
+        ) else {
+            continue;
+        };
+
+        let (min_mhz, max_mhz) = (min_khz / 1000, max_khz / 1000);
+        limits = Some(match limits {
+            Some((lo, hi)) => (lo.min(min_mhz), hi.max(max_mhz)),
+            None => (min_mhz, max_mhz),
+        });
+    }
+
+    limits.ok_or_else(|| {
+        ControlError::NotSupported("Hardware frequency limits are not exposed".to_string())
+    })
+}
+
+fn validate_min_frequency(core_id: u32, new_min_freq_khz: u32) -> Result<()> {
     let max_freq_path = format!("/sys/devices/system/cpu/cpu{core_id}/cpufreq/scaling_max_freq");
 
     if !Path::new(&max_freq_path).exists() {
@@ -443,12 +772,11 @@ fn validate_min_frequency(core_id: u32, new_min_freq_mhz: u32) -> Result<()> {
     }
 
     let max_freq_khz = read_sysfs_value_as_u32(&max_freq_path)?;
-    let new_min_freq_khz = new_min_freq_mhz * 1000;
 
     if new_min_freq_khz > max_freq_khz {
         return Err(ControlError::InvalidValueError(format!(
             "Minimum frequency ({} MHz) cannot be higher than maximum frequency ({} MHz) for core {}",
-            new_min_freq_mhz,
+            new_min_freq_khz / 1000,
             max_freq_khz / 1000,
             core_id
         )));
@@ -457,7 +785,7 @@ fn validate_min_frequency(core_id: u32, new_min_freq_mhz: u32) -> Result<()> {
     Ok(())
 }
 
-fn validate_max_frequency(core_id: u32, new_max_freq_mhz: u32) -> Result<()> {
+fn validate_max_frequency(core_id: u32, new_max_freq_khz: u32) -> Result<()> {
     let min_freq_path = format!("/sys/devices/system/cpu/cpu{core_id}/cpufreq/scaling_min_freq");
 
     if !Path::new(&min_freq_path).exists() {
@@ -465,12 +793,11 @@ fn validate_max_frequency(core_id: u32, new_max_freq_mhz: u32) -> Result<()> {
     }
 
     let min_freq_khz = read_sysfs_value_as_u32(&min_freq_path)?;
-    let new_max_freq_khz = new_max_freq_mhz * 1000;
 
     if new_max_freq_khz < min_freq_khz {
         return Err(ControlError::InvalidValueError(format!(
             "Maximum frequency ({} MHz) cannot be lower than minimum frequency ({} MHz) for core {}",
-            new_max_freq_mhz,
+            new_max_freq_khz / 1000,
             min_freq_khz / 1000,
             core_id
         )));
@@ -479,6 +806,300 @@ fn validate_max_frequency(core_id: u32, new_max_freq_mhz: u32) -> Result<()> {
     Ok(())
 }
 
+const UNCORE_FREQUENCY_PATH: &str = "/sys/devices/system/cpu/intel_uncore_frequency";
+
+/// Uncore frequency domains, `package_NN_die_NN` on older kernels and
+/// `uncoreNN` on CPUs managed through TPMI
+fn uncore_domains() -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(UNCORE_FREQUENCY_PATH) else {
+        return Vec::new();
+    };
+
+    let mut domains: Vec<PathBuf> = entries
+        .flatten()
+        .filter(|entry| {
+            entry
+                .file_name()
+                .to_str()
+                .is_some_and(|name| name.starts_with("package_") || name.starts_with("uncore"))
+        })
+        .map(|entry| entry.path())
+        .collect();
+    domains.sort();
+    domains
+}
+
+/// Frequencies of an uncore frequency domain in MHz
+#[derive(Debug, Clone)]
+pub struct UncoreFrequency {
+    /// Domain name, e.g. `package_00_die_00`
+    pub domain: String,
+    pub current_mhz: Option<u32>,
+    pub min_mhz: Option<u32>,
+    pub max_mhz: Option<u32>,
+}
+
+/// Current frequency and limits of every uncore frequency domain
+pub fn get_uncore_frequencies() -> Vec<UncoreFrequency> {
+    let read_mhz = |domain: &Path, file: &str| {
+        read_sysfs_value_as_u32(&domain.join(file).to_string_lossy())
+            .ok()
+            .map(|khz| khz / 1000)
+    };
+
+    uncore_domains()
+        .into_iter()
+        .map(|domain| UncoreFrequency {
+            domain: domain
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            current_mhz: read_mhz(&domain, "current_freq_khz"),
+            min_mhz: read_mhz(&domain, "min_freq_khz"),
+            max_mhz: read_mhz(&domain, "max_freq_khz"),
+        })
+        .collect()
+}
+
+/// Limit the uncore (ring bus and last level cache) frequency of every
+/// domain on Intel CPUs. Limits are checked against the range the domain
+/// started with, and written in the order that keeps the minimum below the
+/// maximum. The original limits are restored when the daemon exits.
+pub fn set_uncore_frequency(min_mhz: Option<u32>, max_mhz: Option<u32>) -> Result<()> {
+    let domains = uncore_domains();
+    if domains.is_empty() {
+        return Err(ControlError::NotSupported(format!(
+            "Uncore frequency control not found at {UNCORE_FREQUENCY_PATH}."
+        )));
+    }
+
+    if let (Some(min), Some(max)) = (min_mhz, max_mhz) {
+        if min > max {
+            return Err(ControlError::InvalidValueError(format!(
+                "Minimum uncore frequency ({min} MHz) cannot be higher than maximum uncore frequency ({max} MHz)"
+            )));
+        }
+    }
+
+    for domain in domains {
+        let path = |file: &str| domain.join(file).to_string_lossy().into_owned();
+        let (hw_min, hw_max) = (
+            read_sysfs_value_as_u32(&path("initial_min_freq_khz"))?,
+            read_sysfs_value_as_u32(&path("initial_max_freq_khz"))?,
+        );
+        for freq in [min_mhz, max_mhz].into_iter().flatten() {
+            let freq_khz = u64::from(freq) * 1000;
+            if freq_khz < u64::from(hw_min) || freq_khz > u64::from(hw_max) {
+                return Err(ControlError::InvalidValueError(format!(
+                    "Uncore frequency ({freq} MHz) is outside the range of {}-{} MHz for {}",
+                    hw_min / 1000,
+                    hw_max / 1000,
+                    domain.display()
+                )));
+            }
+        }
+
+        // Raising the minimum above the current maximum needs the maximum first
+        let current_max = read_sysfs_value_as_u32(&path("max_freq_khz"))?;
+        let min_write = min_mhz.map(|freq| (path("min_freq_khz"), freq));
+        let max_write = max_mhz.map(|freq| (path("max_freq_khz"), freq));
+        let writes = if min_mhz.is_some_and(|min| u64::from(min) * 1000 > u64::from(current_max)) {
+            [max_write, min_write]
+        } else {
+            [min_write, max_write]
+        };
+
+        for (file, freq) in writes.into_iter().flatten() {
+            let freq_khz = (u64::from(freq) * 1000).to_string();
+            if fs::read_to_string(&file).is_ok_and(|current| current.trim() == freq_khz) {
+                continue;
+            }
+            restore::record(&file);
+            write_sysfs_value(&file, &freq_khz)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Restore the uncore frequency limits changed by [`set_uncore_frequency`],
+/// only the minimum or maximum if `file` is `min_freq_khz` or `max_freq_khz`.
+/// Returns how many limits were restored.
+pub fn restore_uncore_frequency(file: Option<&str>) -> usize {
+    restore::restore_where(|path| {
+        path.starts_with(UNCORE_FREQUENCY_PATH)
+            && file.is_none_or(|file| path.file_name().is_some_and(|name| name == file))
+    })
+}
+
+/// Order in which cores are parked, lowest first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum ParkClass {
+    /// Second hardware thread of a core whose first thread stays online
+    SmtSibling,
+    EfficiencyCore,
+    PerformanceCore,
+}
+
+/// Park classes learned while the cores were online, as the topology of
+/// offline cores is no longer exposed
+static PARK_CLASSES: Mutex<BTreeMap<u32, ParkClass>> = Mutex::new(BTreeMap::new());
+
+fn park_class(core_id: u32, topology: &CpuTopology) -> ParkClass {
+    let mut classes = PARK_CLASSES
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner);
+
+    let siblings = fs::read_to_string(format!(
+        "/sys/devices/system/cpu/cpu{core_id}/topology/thread_siblings_list"
+    ))
+    .ok()
+    .and_then(|list| topology::parse_cpu_list(&list));
+    let online = read_online(core_id).unwrap_or(true);
+
+    if let (true, Some(siblings)) = (online, siblings) {
+        let class = if siblings.iter().any(|&sibling| sibling < core_id) {
+            ParkClass::SmtSibling
+        } else if topology.core_type(core_id) == Some(CoreType::Efficiency) {
+            ParkClass::EfficiencyCore
+        } else {
+            ParkClass::PerformanceCore
+        };
+        classes.insert(core_id, class);
+    }
+
+    classes
+        .get(&core_id)
+        .copied()
+        .unwrap_or(match topology.core_type(core_id) {
+            Some(CoreType::Efficiency) => ParkClass::EfficiencyCore,
+            _ => ParkClass::PerformanceCore,
+        })
+}
+
+fn online_path(core_id: u32) -> String {
+    format!("/sys/devices/system/cpu/cpu{core_id}/online")
+}
+
+fn read_online(core_id: u32) -> Option<bool> {
+    let value = fs::read_to_string(online_path(core_id)).ok()?;
+    Some(value.trim() == "1")
+}
+
+/// Keep only the given cores online and park the others through
+/// `/sys/devices/system/cpu/cpuN/online`. With a count, SMT siblings are
+/// parked first, then E-cores, then P-cores, highest IDs first. `cpu0` is
+/// never taken offline. The original state is restored when the daemon exits.
+pub fn set_online_cores(target: &OnlineCores, topology: &CpuTopology) -> Result<()> {
+    let present = topology::present_cpus();
+    // cpu0 usually cannot be taken offline, and is always kept online
+    let hotpluggable: Vec<u32> = present
+        .iter()
+        .copied()
+        .filter(|&id| id != 0 && Path::new(&online_path(id)).exists())
+        .collect();
+    if hotpluggable.is_empty() {
+        return Err(ControlError::NotSupported(
+            "CPU hotplug is not available on this system".to_string(),
+        ));
+    }
+    // Cores taken offline by someone else, e.g. SMT siblings disabled through
+    // the SMT control, are left alone
+    let hotpluggable: Vec<u32> = hotpluggable
+        .into_iter()
+        .filter(|&id| read_online(id) == Some(true) || restore::is_recorded(online_path(id)))
+        .collect();
+
+    let parked: BTreeSet<u32> = match target {
+        OnlineCores::Count(count) => {
+            // cpu0 counts towards the cores kept online
+            let to_park = (hotpluggable.len() + 1).saturating_sub(*count as usize);
+            let mut candidates = hotpluggable.clone();
+            candidates.sort_by_key(|&id| (park_class(id, topology), std::cmp::Reverse(id)));
+            candidates.into_iter().take(to_park).collect()
+        }
+        OnlineCores::Cpus(cpus) => {
+            for cpu in cpus.iter().filter(|cpu| !present.contains(cpu)) {
+                warn!("Core {cpu} in online_cores does not exist, ignoring it");
+            }
+            hotpluggable
+                .iter()
+                .copied()
+                .filter(|id| !cpus.contains(id))
+                .collect()
+        }
+    };
+
+    // Bring cores online first, so the system never has fewer cores than requested
+    let (to_park, to_unpark): (Vec<u32>, Vec<u32>) =
+        hotpluggable.iter().partition(|id| parked.contains(id));
+    for (cores, online) in [(to_unpark, true), (to_park, false)] {
+        for core_id in cores {
+            if read_online(core_id) == Some(online) {
+                continue;
+            }
+            let path = online_path(core_id);
+            restore::record(&path);
+            write_sysfs_value(&path, if online { "1" } else { "0" })?;
+            debug!(
+                "{} core {core_id}",
+                if online { "Unparked" } else { "Parked" }
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Bring back every core parked by [`set_online_cores`], returning how many
+/// were brought back online
+pub fn restore_online_cores() -> usize {
+    restore::restore_where(|path| {
+        path.starts_with("/sys/devices/system/cpu") && path.ends_with("online")
+    })
+}
+
+/// Turn simultaneous multithreading (Hyper-Threading) on or off through
+/// `/sys/devices/system/cpu/smt/control`. The kernel takes sibling threads
+/// offline itself. SMT is not restored when the daemon exits, as it may have
+/// been disabled to mitigate CPU vulnerabilities.
+pub fn set_smt(setting: SmtSetting) -> Result<()> {
+    let value = match setting {
+        SmtSetting::On => "on",
+        SmtSetting::Off => "off",
+        SmtSetting::Auto => {
+            debug!("SMT is set to auto, leaving it unchanged");
+            return Ok(());
+        }
+    };
+
+    let path = "/sys/devices/system/cpu/smt/control";
+    if !Path::new(path).exists() {
+        return Err(ControlError::NotSupported(format!(
+            "SMT control not found at {path}."
+        )));
+    }
+
+    let current = fs::read_to_string(path)
+        .map_err(|e| ControlError::ReadError(format!("Failed to read {path}: {e}")))?;
+    match current.trim() {
+        current if current == value => Ok(()),
+        // Disabled on the kernel command line, it cannot be enabled until reboot
+        "forceoff" if setting == SmtSetting::Off => Ok(()),
+        "forceoff" => Err(ControlError::NotSupported(
+            "SMT was disabled at boot (nosmt=force) and cannot be enabled".to_string(),
+        )),
+        "notsupported" | "notimplemented" => Err(ControlError::NotSupported(
+            "SMT is not supported by this CPU".to_string(),
+        )),
+        _ => {
+            write_sysfs_value(path, value)?;
+            debug!("Set SMT to {value}");
+            Ok(())
+        }
+    }
+}
+
 /// Sets the platform profile.
 /// This changes the system performance, temperature, fan, and other hardware replated characteristics.
 ///
@@ -619,3 +1240,51 @@ pub fn get_governor_override() -> Option<String> {
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TABLE: &[u32] = &[800_000, 1_600_000, 2_400_000, 3_200_000];
+
+    #[test]
+    fn listed_frequencies_are_kept() {
+        assert_eq!(
+            snap_frequency(1_600_000, TABLE, FrequencyBound::Max),
+            1_600_000
+        );
+        assert_eq!(
+            snap_frequency(1_600_000, TABLE, FrequencyBound::Min),
+            1_600_000
+        );
+    }
+
+    #[test]
+    fn maximum_snaps_down_and_minimum_snaps_up() {
+        assert_eq!(
+            snap_frequency(2_000_000, TABLE, FrequencyBound::Max),
+            1_600_000
+        );
+        assert_eq!(
+            snap_frequency(2_000_000, TABLE, FrequencyBound::Min),
+            2_400_000
+        );
+    }
+
+    #[test]
+    fn frequencies_outside_the_table_snap_to_the_closest_entry() {
+        assert_eq!(snap_frequency(400_000, TABLE, FrequencyBound::Max), 800_000);
+        assert_eq!(
+            snap_frequency(4_000_000, TABLE, FrequencyBound::Min),
+            3_200_000
+        );
+    }
+
+    #[test]
+    fn frequencies_are_kept_without_a_table() {
+        assert_eq!(
+            snap_frequency(2_000_000, &[], FrequencyBound::Max),
+            2_000_000
+        );
+    }
+}
@@ -0,0 +1,208 @@
+// Control of the cpuidle governor and the idle states (C-states) of the cores
+use crate::cpu;
+use crate::restore;
+use crate::util::error::ControlError;
+use crate::util::sysfs;
+use log::debug;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub type Result<T, E = ControlError> = std::result::Result<T, E>;
+
+const CPUIDLE_PATH: &str = "/sys/devices/system/cpu/cpuidle";
+
+/// An idle state with its statistics summed over the online cores
+#[derive(Debug, Clone)]
+pub struct IdleState {
+    /// Index of the state, as in `cpuN/cpuidle/stateM`
+    pub index: u32,
+    /// Name of the state, e.g. `C6`
+    pub name: String,
+    /// Exit latency in microseconds
+    pub latency_us: Option<u64>,
+    /// Number of times the state was entered
+    pub usage: u64,
+    /// Total time spent in the state in microseconds
+    pub time_us: u64,
+    /// Number of cores on which the state is disabled
+    pub disabled_cores: usize,
+}
+
+/// Current cpuidle governor, e.g. `menu`
+pub fn get_cpuidle_governor() -> Option<String> {
+    // Older kernels only expose the read-only `current_governor_ro`
+    sysfs::read_sysfs_value(Path::new(CPUIDLE_PATH).join("current_governor"))
+        .or_else(|_| sysfs::read_sysfs_value(Path::new(CPUIDLE_PATH).join("current_governor_ro")))
+        .ok()
+}
+
+/// Current cpuidle driver, e.g. `intel_idle` or `acpi_idle`
+pub fn get_cpuidle_driver() -> Option<String> {
+    sysfs::read_sysfs_value(Path::new(CPUIDLE_PATH).join("current_driver")).ok()
+}
+
+/// Get the cpuidle governors the kernel offers
+///
+/// # Errors
+///
+/// Returns [`ControlError::NotSupported`] if the cpuidle governor cannot be
+/// switched at runtime.
+pub fn get_available_cpuidle_governors() -> Result<Vec<String>> {
+    let governors = sysfs::read_sysfs_value(Path::new(CPUIDLE_PATH).join("available_governors"))
+        .map_err(|_| {
+            ControlError::NotSupported(
+                "The cpuidle governor cannot be switched on this system".to_string(),
+            )
+        })?;
+    Ok(governors.split_whitespace().map(str::to_string).collect())
+}
+
+/// Switch the cpuidle governor, e.g. to `teo` or `haltpoll`. The original
+/// governor is restored when the daemon exits.
+pub fn set_cpuidle_governor(governor: &str) -> Result<()> {
+    let available = get_available_cpuidle_governors()?;
+    if !available.iter().any(|g| g == governor) {
+        return Err(ControlError::InvalidValueError(format!(
+            "cpuidle governor '{governor}' is not available. Available governors: {}",
+            available.join(", ")
+        )));
+    }
+
+    let path = Path::new(CPUIDLE_PATH).join("current_governor");
+    if sysfs::read_sysfs_value(&path).is_ok_and(|current| current == governor) {
+        return Ok(());
+    }
+    restore::record(&path);
+    sysfs::write_sysfs_value(&path, governor)?;
+    debug!("Switched the cpuidle governor to {governor}");
+    Ok(())
+}
+
+/// Restore the original cpuidle governor, returning whether it was changed
+pub fn restore_cpuidle_governor() -> bool {
+    restore::restore_where(|path| path == Path::new(CPUIDLE_PATH).join("current_governor")) > 0
+}
+
+/// Directories of the idle states of a core, in index order
+fn state_dirs(core_id: u32) -> Vec<(u32, PathBuf)> {
+    let Ok(entries) = fs::read_dir(format!("/sys/devices/system/cpu/cpu{core_id}/cpuidle")) else {
+        return Vec::new();
+    };
+
+    let mut states: Vec<(u32, PathBuf)> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let index = entry
+                .file_name()
+                .to_str()?
+                .strip_prefix("state")?
+                .parse()
+                .ok()?;
+            Some((index, entry.path()))
+        })
+        .collect();
+    states.sort_unstable_by_key(|(index, _)| *index);
+    states
+}
+
+fn read_u64(path: &Path) -> Option<u64> {
+    sysfs::read_sysfs_value(path).ok()?.parse().ok()
+}
+
+/// The idle states of the online cores, taking names and latencies from the
+/// first core that has them
+pub fn get_idle_states() -> Vec<IdleState> {
+    let mut states: Vec<IdleState> = Vec::new();
+
+    for core_id in cpu::get_online_cpus().unwrap_or_default() {
+        for (index, dir) in state_dirs(core_id) {
+            let state = match states.iter_mut().find(|state| state.index == index) {
+                Some(state) => state,
+                None => {
+                    let Ok(name) = sysfs::read_sysfs_value(dir.join("name")) else {
+                        continue;
+                    };
+                    states.push(IdleState {
+                        index,
+                        name,
+                        latency_us: read_u64(&dir.join("latency")),
+                        usage: 0,
+                        time_us: 0,
+                        disabled_cores: 0,
+                    });
+                    states.last_mut().expect("state was just added")
+                }
+            };
+
+            state.usage += read_u64(&dir.join("usage")).unwrap_or(0);
+            state.time_us += read_u64(&dir.join("time")).unwrap_or(0);
+            if read_u64(&dir.join("disable")).is_some_and(|disabled| disabled != 0) {
+                state.disabled_cores += 1;
+            }
+        }
+    }
+
+    states.sort_unstable_by_key(|state| state.index);
+    states
+}
+
+/// Disable the named idle states on every online core and enable the others
+/// that Watt disabled before. Names are matched case-insensitively. The
+/// original settings are restored when the daemon exits.
+///
+/// # Errors
+///
+/// Returns [`ControlError::NotSupported`] if the cores have no idle states,
+/// and [`ControlError::InvalidValueError`] for unknown state names.
+pub fn set_disabled_idle_states(names: &[String]) -> Result<()> {
+    let available = get_idle_states();
+    if available.is_empty() {
+        return Err(ControlError::NotSupported(
+            "No cpuidle states are exposed on this system".to_string(),
+        ));
+    }
+    if let Some(unknown) = names.iter().find(|name| {
+        !available
+            .iter()
+            .any(|state| state.name.eq_ignore_ascii_case(name))
+    }) {
+        return Err(ControlError::InvalidValueError(format!(
+            "Unknown idle state '{unknown}'. Available states: {}",
+            available
+                .iter()
+                .map(|state| state.name.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        )));
+    }
+
+    for core_id in cpu::get_online_cpus()? {
+        for (_, dir) in state_dirs(core_id) {
+            let path = dir.join("disable");
+            let Ok(name) = sysfs::read_sysfs_value(dir.join("name")) else {
+                continue;
+            };
+
+            if names.iter().any(|n| n.eq_ignore_ascii_case(&name)) {
+                if read_u64(&path) == Some(1) {
+                    continue;
+                }
+                restore::record(&path);
+                sysfs::write_sysfs_value(&path, "1")?;
+                debug!("Disabled idle state {name} on core {core_id}");
+            } else if restore::is_recorded(&path) {
+                restore::restore_where(|recorded| recorded == path);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Restore every idle state Watt disabled, returning the number of states
+/// changed
+pub fn restore_idle_states() -> usize {
+    restore::restore_where(|path| {
+        path.to_str()
+            .is_some_and(|path| path.contains("/cpuidle/state"))
+    })
+}
@@ -1,14 +1,21 @@
 use crate::config::{AppConfig, LogLevel};
+use crate::control::{self, Reply, Request};
 use crate::core::SystemReport;
 use crate::engine;
 use crate::monitor;
-use crate::util::error::{AppError, ControlError};
+use crate::profile::{self, ProfileSelection, ProfileSelector};
+use crate::restore;
+use crate::rules::RuleInputs;
+use crate::schedule;
+use crate::util::error::{AppError, ControlError, EngineError};
+use jiff::Zoned;
 use log::{LevelFilter, debug, error, info, warn};
 use std::collections::VecDeque;
 use std::fs::File;
 use std::io::Write;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
 use std::time::{Duration, Instant};
 
 /// Parameters for computing optimal polling interval
@@ -357,6 +364,15 @@ impl SystemHistory {
         recent_avg < 10.0 && self.get_cpu_volatility() < 5.0
     }
 
+    /// Inputs for profile selection rules, including the state only tracked over time
+    fn rule_inputs(&self, report: &SystemReport) -> RuleInputs {
+        RuleInputs {
+            idle_duration: Some(self.last_user_activity.elapsed()),
+            discharge_rate: self.battery_discharge_rate,
+            ..RuleInputs::from_report(report)
+        }
+    }
+
     /// Calculate optimal polling interval based on system conditions
     fn calculate_optimal_interval(
         &self,
@@ -401,6 +417,91 @@ fn validate_poll_intervals(min_interval: u64, max_interval: u64) -> Result<(), C
     }
 }
 
+/// Delay before retrying a selection that failed to apply, doubled with
+/// every further failure
+const RETRY_DELAY: Duration = Duration::from_secs(30);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30 * 60);
+
+/// What a selection applies, to tell whether a failed selection is chosen again
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct SelectionKey {
+    name: String,
+    schedule: Option<String>,
+    process_trigger: Option<String>,
+    battery_tiers: Vec<u8>,
+}
+
+impl SelectionKey {
+    fn of(selection: &ProfileSelection) -> Self {
+        Self {
+            name: selection.name.clone(),
+            schedule: selection.schedule.clone(),
+            process_trigger: selection.process_trigger.clone(),
+            battery_tiers: selection.battery_tiers.clone(),
+        }
+    }
+}
+
+/// Holds back a selection whose profile failed and was rolled back, so a
+/// persistent failure is not written and reverted again on every cycle. A
+/// different selection is applied right away.
+#[derive(Debug, Default)]
+struct RetryBackoff {
+    failed: Option<(SelectionKey, u32, Instant)>,
+}
+
+impl RetryBackoff {
+    /// How long applying the selection is held back for
+    fn remaining(&self, key: &SelectionKey, now: Instant) -> Option<Duration> {
+        match &self.failed {
+            Some((failed, _, retry_at)) if failed == key && *retry_at > now => {
+                Some(*retry_at - now)
+            }
+            _ => None,
+        }
+    }
+
+    /// Record a failure of the selection, returning the delay until it is retried
+    fn failed(&mut self, key: SelectionKey, now: Instant) -> Duration {
+        let failures = match &self.failed {
+            Some((failed, failures, _)) if *failed == key => failures.saturating_add(1),
+            _ => 1,
+        };
+        let delay = RETRY_DELAY
+            .saturating_mul(2u32.saturating_pow(failures - 1))
+            .min(MAX_RETRY_DELAY);
+        self.failed = Some((key, failures, now + delay));
+        delay
+    }
+
+    fn succeeded(&mut self) {
+        self.failed = None;
+    }
+}
+
+/// Something that interrupts the daemon's sleep between cycles
+enum Event {
+    /// A control socket request, answered through the sender
+    Request(Request, Sender<Reply>),
+    Shutdown,
+}
+
+/// Handle a control request that changes the profile selection. On success,
+/// the reply is sent once the next cycle has applied the new selection.
+fn handle_request(config: &AppConfig, request: &Request) -> Result<(), String> {
+    match request {
+        Request::SetProfile(name) => {
+            profile::set_profile_override(config, name).map_err(|e| e.to_string())?;
+            info!("Profile override set to '{name}' through the control socket");
+        }
+        Request::ResetProfile => {
+            profile::clear_profile_override().map_err(|e| e.to_string())?;
+            info!("Profile override reset through the control socket");
+        }
+    }
+    Ok(())
+}
+
 /// Run the daemon
 pub fn run_daemon(config: AppConfig, verbose: bool) -> Result<(), AppError> {
     // Set effective log level based on config and verbose flag
@@ -431,17 +532,44 @@ pub fn run_daemon(config: AppConfig, verbose: bool) -> Result<(), AppError> {
         return Err(AppError::Control(err));
     }
 
+    let mut cleanup = DaemonCleanup::install();
+
     // Create a flag that will be set to true when a signal is received
     let running = Arc::new(AtomicBool::new(true));
     let r = running.clone();
 
-    // Set up signal handlers
+    // Signals and control requests wake the main loop early
+    let (events_tx, events) = mpsc::channel();
+    let shutdown_tx = events_tx.clone();
+
+    // Set up handlers for SIGINT, SIGTERM and SIGHUP
     ctrlc::set_handler(move || {
         info!("Received shutdown signal, exiting...");
         r.store(false, Ordering::SeqCst);
+        let _ = shutdown_tx.send(Event::Shutdown);
     })
     .map_err(|e| AppError::Generic(format!("Error setting Ctrl-C handler: {e}")))?;
 
+    let serving = control::serve(move |request| {
+        let (reply_tx, reply) = mpsc::channel();
+        if events_tx.send(Event::Request(request, reply_tx)).is_err() {
+            return Reply::error("The daemon is shutting down");
+        }
+        reply
+            .recv()
+            .unwrap_or_else(|_| Reply::error("The daemon is shutting down"))
+    });
+    cleanup.owns_socket = serving.is_ok();
+    match serving {
+        Ok(()) => info!(
+            "Listening for control requests on {}",
+            control::CONTROL_SOCKET_PATH
+        ),
+        Err(e) => {
+            warn!("Control socket unavailable, `watt profile` will apply profiles itself: {e}")
+        }
+    }
+
     info!(
         "Daemon initialized with poll interval: {}s",
         config.daemon.poll_interval_sec
@@ -459,6 +587,10 @@ pub fn run_daemon(config: AppConfig, verbose: bool) -> Result<(), AppError> {
         warn!("Poll interval is set to zero in config, using 1s minimum to prevent a busy loop");
     }
     let mut system_history = SystemHistory::default();
+    let mut profile_selector = ProfileSelector::new();
+    let mut backoff = RetryBackoff::default();
+    // Control requests waiting for the outcome of the next cycle
+    let mut pending_replies: Vec<Sender<Reply>> = Vec::new();
 
     // Main loop
     while running.load(Ordering::SeqCst) {
@@ -481,21 +613,57 @@ pub fn run_daemon(config: AppConfig, verbose: bool) -> Result<(), AppError> {
                     }
                 }
 
-                match engine::determine_and_apply_settings(&report, &config, None) {
-                    Ok(()) => {
-                        debug!("Successfully applied system settings");
-
-                        // If system state changed, log the new state
-                        if system_history.current_state != previous_state {
-                            info!(
-                                "System state changed to: {:?}",
-                                system_history.current_state
+                let rule_inputs = system_history.rule_inputs(&report);
+                let selection = profile_selector.select(&config, &rule_inputs);
+
+                let key = SelectionKey::of(&selection);
+                // Control requests are answered with a fresh attempt
+                let held_back = pending_replies
+                    .is_empty()
+                    .then(|| backoff.remaining(&key, Instant::now()))
+                    .flatten();
+                if let Some(remaining) = held_back {
+                    debug!(
+                        "Not applying '{}' profile, it failed and is retried in {}s",
+                        selection.name,
+                        remaining.as_secs()
+                    );
+                } else {
+                    let outcome = engine::determine_and_apply_settings(&report, &selection);
+                    for reply in pending_replies.drain(..) {
+                        let _ = reply.send(engine::reply_for(&selection, &outcome));
+                    }
+                    match outcome {
+                        Ok(results) => {
+                            backoff.succeeded();
+                            debug!("Successfully applied system settings");
+                            for result in engine::reported(&results) {
+                                debug!("  {result}");
+                            }
+                        }
+                        Err(e) => {
+                            error!("Error applying system settings: {e}");
+                            if let EngineError::RolledBack { results, .. } = &e {
+                                for result in engine::reported(results) {
+                                    warn!("  {result}");
+                                }
+                            }
+                            let delay = backoff.failed(key, Instant::now());
+                            warn!(
+                                "Retrying '{}' profile in {}s unless another profile is selected first",
+                                selection.name,
+                                delay.as_secs()
                             );
                         }
                     }
-                    Err(e) => {
-                        error!("Error applying system settings: {e}");
-                    }
+                }
+
+                // If system state changed, log the new state
+                if system_history.current_state != previous_state {
+                    info!(
+                        "System state changed to: {:?}",
+                        system_history.current_state
+                    );
                 }
 
                 // Check if we're on battery
@@ -566,23 +734,104 @@ pub fn run_daemon(config: AppConfig, verbose: bool) -> Result<(), AppError> {
             }
             Err(e) => {
                 error!("Error collecting system report: {e}");
+                for reply in pending_replies.drain(..) {
+                    let _ =
+                        reply.send(Reply::error(format!("Error collecting system report: {e}")));
+                }
             }
         }
 
-        // Sleep for the remaining time in the poll interval
+        // Sleep for the remaining time in the poll interval, waking early at
+        // schedule boundaries
         let elapsed = start_time.elapsed();
-        let poll_duration = Duration::from_secs(current_poll_interval);
+        let mut poll_duration = Duration::from_secs(current_poll_interval);
+        if let Some(until_boundary) = schedule::until_next_boundary(&config, &Zoned::now()) {
+            let boundary = elapsed + until_boundary;
+            if boundary < poll_duration {
+                debug!("Waking early for a schedule boundary");
+                poll_duration = boundary;
+            }
+        }
+        let wake_at = start_time + poll_duration;
         if elapsed < poll_duration {
-            let sleep_time = poll_duration - elapsed;
-            debug!("Sleeping for {}s until next cycle", sleep_time.as_secs());
-            std::thread::sleep(sleep_time);
+            debug!(
+                "Sleeping for {}s until next cycle",
+                (poll_duration - elapsed).as_secs()
+            );
+        }
+        while running.load(Ordering::SeqCst) {
+            match events.recv_timeout(wake_at.saturating_duration_since(Instant::now())) {
+                Ok(Event::Request(request, reply)) => {
+                    match handle_request(&config, &request) {
+                        // Apply the new selection right away
+                        Ok(()) => {
+                            pending_replies.push(reply);
+                            break;
+                        }
+                        Err(e) => {
+                            let _ = reply.send(Reply::error(e));
+                        }
+                    }
+                }
+                Ok(Event::Shutdown) | Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => {
+                    std::thread::sleep(wake_at.saturating_duration_since(Instant::now()));
+                    break;
+                }
+            }
         }
     }
 
+    for reply in pending_replies.drain(..) {
+        let _ = reply.send(Reply::error("The daemon is shutting down"));
+    }
+    drop(cleanup);
+
     info!("Daemon stopped");
     Ok(())
 }
 
+/// Removes the control socket and restores the original settings when the
+/// daemon exits, including early error returns and panics unwinding the main
+/// loop
+struct DaemonCleanup {
+    /// Whether this daemon created the control socket
+    owns_socket: bool,
+}
+
+impl DaemonCleanup {
+    fn install() -> Self {
+        // Restore before the panic message, in case unwinding does not reach
+        // the guard. Panics of the control thread leave the daemon running.
+        let default_hook = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            if std::thread::current().name() == Some("main") {
+                restore_original_values();
+            }
+            default_hook(info);
+        }));
+        Self { owns_socket: false }
+    }
+}
+
+impl Drop for DaemonCleanup {
+    fn drop(&mut self) {
+        if self.owns_socket {
+            control::remove_socket();
+        }
+        restore_original_values();
+    }
+}
+
+/// Undo changes that only make sense while the daemon manages the system,
+/// such as parked cores
+fn restore_original_values() {
+    let restored = restore::restore_all();
+    if restored > 0 {
+        info!("Restored {restored} settings to their original values");
+    }
+}
+
 /// Write current system stats to a file for --stats to read
 fn write_stats_file(path: &str, report: &SystemReport) -> Result<(), std::io::Error> {
     let mut file = File::create(path)?;
@@ -595,6 +844,22 @@ fn write_stats_file(path: &str, report: &SystemReport) -> Result<(), std::io::Er
     if let Some(temp) = report.cpu_global.average_temperature_celsius {
         writeln!(file, "cpu_temp={temp:.1}")?;
     }
+    for (key, watts) in [
+        ("cpu_power_package", report.cpu_global.package_power_watts),
+        ("cpu_power_core", report.cpu_global.core_power_watts),
+        ("cpu_power_uncore", report.cpu_global.uncore_power_watts),
+        ("cpu_power_dram", report.cpu_global.dram_power_watts),
+    ] {
+        if let Some(watts) = watts {
+            writeln!(file, "{key}={watts:.2}")?;
+        }
+    }
+
+    for gpu in &report.gpus {
+        if let Some(busy) = gpu.busy_percent {
+            writeln!(file, "gpu_busy_{}={busy}", gpu.card)?;
+        }
+    }
 
     // Battery info
     if !report.batteries.is_empty() {
@@ -669,3 +934,57 @@ fn determine_system_state(report: &SystemReport, history: &SystemHistory) -> Sys
     // Default case
     SystemState::Unknown
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(name: &str) -> SelectionKey {
+        SelectionKey {
+            name: name.to_string(),
+            schedule: None,
+            process_trigger: None,
+            battery_tiers: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn failed_selection_is_held_back_until_its_retry() {
+        let mut backoff = RetryBackoff::default();
+        let now = Instant::now();
+
+        assert_eq!(backoff.failed(key("quiet"), now), RETRY_DELAY);
+        assert_eq!(backoff.remaining(&key("quiet"), now), Some(RETRY_DELAY));
+        assert_eq!(backoff.remaining(&key("quiet"), now + RETRY_DELAY), None);
+        // Other selections are not held back
+        assert_eq!(backoff.remaining(&key("performance"), now), None);
+    }
+
+    #[test]
+    fn retry_delay_doubles_up_to_its_maximum() {
+        let mut backoff = RetryBackoff::default();
+        let now = Instant::now();
+
+        assert_eq!(backoff.failed(key("quiet"), now), RETRY_DELAY);
+        assert_eq!(backoff.failed(key("quiet"), now), RETRY_DELAY * 2);
+        assert_eq!(backoff.failed(key("quiet"), now), RETRY_DELAY * 4);
+        for _ in 0..40 {
+            backoff.failed(key("quiet"), now);
+        }
+        assert_eq!(backoff.failed(key("quiet"), now), MAX_RETRY_DELAY);
+
+        // A different failing selection starts over
+        assert_eq!(backoff.failed(key("battery"), now), RETRY_DELAY);
+    }
+
+    #[test]
+    fn success_clears_the_backoff() {
+        let mut backoff = RetryBackoff::default();
+        let now = Instant::now();
+
+        backoff.failed(key("quiet"), now);
+        backoff.succeeded();
+        assert_eq!(backoff.remaining(&key("quiet"), now), None);
+        assert_eq!(backoff.failed(key("quiet"), now), RETRY_DELAY);
+    }
+}
@@ -1,9 +1,19 @@
 use crate::battery;
-use crate::config::{AppConfig, ProfileConfig, TurboAutoSettings};
-use crate::core::{OperationalMode, SystemReport, TurboSetting};
+use crate::config::{FrequencyLimit, ProfileConfig, TurboAutoSettings};
+use crate::control::Reply;
+use crate::core::{AmdPstateStatus, SystemReport, TurboSetting};
 use crate::cpu::{self};
+use crate::cpuidle;
+use crate::governor;
+use crate::gpu;
+use crate::profile::{self, ProfileSelection};
+use crate::pstate;
+use crate::rapl::{self, PowerLimit};
+use crate::restore;
+use crate::topology::{CoreType, CpuTopology};
 use crate::util::error::{ControlError, EngineError};
 use log::{debug, info, warn};
+use std::fmt;
 use std::sync::OnceLock;
 use std::sync::atomic::{AtomicBool, Ordering};
 
@@ -23,6 +33,12 @@ impl TurboHysteresisStates {
         }
     }
 
+    /// Forget the previous states, e.g. after the turbo setting was rolled back
+    fn reset(&self) {
+        self.charger.initialized.store(false, Ordering::Release);
+        self.battery.initialized.store(false, Ordering::Release);
+    }
+
     const fn get_for_power_state(&self, is_on_ac: bool) -> &TurboHysteresis {
         if is_on_ac {
             &self.charger
@@ -115,160 +131,758 @@ impl TurboHysteresis {
     }
 }
 
-/// Try applying a CPU feature and handle common error cases. Centralizes the where we
-/// previously did:
-/// 1. Try to apply a feature setting
-/// 2. If not supported, log a warning and continue
-/// 3. If other error, propagate the error
-fn try_apply_feature<F, T>(
-    feature_name: &str,
-    value_description: &str,
-    apply_fn: F,
-) -> Result<(), EngineError>
-where
-    F: FnOnce() -> Result<T, ControlError>,
-{
-    info!("Setting {feature_name} to '{value_description}'");
-
-    match apply_fn() {
-        Ok(_) => Ok(()),
+/// What happened to one setting when a profile was applied
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KnobOutcome {
+    /// The setting was written
+    Applied,
+    /// Nothing had to be written, e.g. there was nothing to restore
+    Unchanged,
+    /// The system does not support the setting, so it was skipped
+    Unsupported,
+    /// Writing the setting failed, which rolled back the whole profile
+    Failed(String),
+    /// The setting was written, then reverted because a later one failed
+    RolledBack,
+    /// The setting was not attempted because an earlier one failed
+    NotAttempted,
+}
+
+impl fmt::Display for KnobOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Applied => write!(f, "applied"),
+            Self::Unchanged => write!(f, "unchanged"),
+            Self::Unsupported => write!(f, "not supported"),
+            Self::Failed(error) => write!(f, "failed: {error}"),
+            Self::RolledBack => write!(f, "rolled back"),
+            Self::NotAttempted => write!(f, "not attempted"),
+        }
+    }
+}
+
+/// The outcome of one setting of a profile
+#[derive(Debug, Clone)]
+pub struct KnobResult {
+    pub knob: String,
+    pub value: String,
+    pub outcome: KnobOutcome,
+}
+
+impl fmt::Display for KnobResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} '{}': {}", self.knob, self.value, self.outcome)
+    }
+}
+
+/// The results worth reporting, leaving out restores that had nothing to do
+pub fn reported(results: &[KnobResult]) -> impl Iterator<Item = &KnobResult> {
+    results
+        .iter()
+        .filter(|result| result.outcome != KnobOutcome::Unchanged)
+}
+
+/// Reply to a control request with the outcome of applying a profile
+pub fn reply_for(
+    selection: &ProfileSelection,
+    outcome: &Result<Vec<KnobResult>, EngineError>,
+) -> Reply {
+    match outcome {
+        Ok(results) => Reply {
+            ok: true,
+            lines: std::iter::once(format!("Applied profile '{}':", selection.name))
+                .chain(reported(results).map(|result| format!("  {result}")))
+                .collect(),
+        },
         Err(e) => {
-            if matches!(e, ControlError::NotSupported(_)) {
-                warn!(
-                    "{feature_name} setting is not supported on this system. Skipping {feature_name} configuration."
-                );
-                Ok(())
-            } else {
-                // Propagate all other errors, including InvalidValueError
-                Err(EngineError::ControlError(e))
+            let mut lines = vec![e.to_string()];
+            if let EngineError::RolledBack { results, .. } = e {
+                lines.extend(reported(results).map(|result| format!("  {result}")));
             }
+            Reply { ok: false, lines }
         }
     }
 }
 
-/// Determines the appropriate CPU profile based on power status or forced mode,
-/// and applies the settings (via helpers defined in the `cpu` module)
+/// Writes a setting, returning whether anything was changed
+type ApplyFn<'a> = Box<dyn FnOnce() -> Result<bool, EngineError> + 'a>;
+
+struct Change<'a> {
+    knob: String,
+    value: String,
+    /// Whether to log the setting before it is written
+    announce: bool,
+    /// Whether a failure is only recorded, instead of failing the transaction
+    soft: bool,
+    apply: ApplyFn<'a>,
+}
+
+/// The settings a profile changes, in the order they are written. The set is
+/// applied as a transaction: the prior value of every file is journaled before
+/// it is written, and a hard failure reverts everything written before it.
+#[derive(Default)]
+struct ChangeSet<'a> {
+    changes: Vec<Change<'a>>,
+}
+
+impl<'a> ChangeSet<'a> {
+    /// Add a setting. If the system does not support it, it is skipped with a
+    /// warning; any other error fails the transaction.
+    fn add<T>(
+        &mut self,
+        knob: impl Into<String>,
+        value: impl Into<String>,
+        apply: impl FnOnce() -> Result<T, ControlError> + 'a,
+    ) {
+        self.push(knob, value, move || {
+            apply()?;
+            Ok(true)
+        });
+    }
+
+    /// Add a setting whose failure is recorded with a warning but does not
+    /// fail the transaction
+    fn add_soft<T>(
+        &mut self,
+        knob: impl Into<String>,
+        value: impl Into<String>,
+        apply: impl FnOnce() -> Result<T, ControlError> + 'a,
+    ) {
+        self.changes.push(Change {
+            knob: knob.into(),
+            value: value.into(),
+            announce: true,
+            soft: true,
+            apply: Box::new(move || {
+                apply()?;
+                Ok(true)
+            }),
+        });
+    }
+
+    /// Add a setting that reports whether it changed anything
+    fn push(
+        &mut self,
+        knob: impl Into<String>,
+        value: impl Into<String>,
+        apply: impl FnOnce() -> Result<bool, EngineError> + 'a,
+    ) {
+        self.changes.push(Change {
+            knob: knob.into(),
+            value: value.into(),
+            announce: true,
+            soft: false,
+            apply: Box::new(apply),
+        });
+    }
+
+    /// Add the restoring of a setting the profile does not set, which logs
+    /// itself only if it changed anything
+    fn add_restore(&mut self, knob: impl Into<String>, restore: impl FnOnce() -> bool + 'a) {
+        self.changes.push(Change {
+            knob: knob.into(),
+            value: "original".to_string(),
+            announce: false,
+            soft: false,
+            apply: Box::new(move || Ok(restore())),
+        });
+    }
+
+    /// Apply every change in order. On a hard failure, the files written so
+    /// far are rolled back to their prior values and the error carries the
+    /// outcome of every setting.
+    fn apply(self) -> Result<Vec<KnobResult>, EngineError> {
+        let transaction = restore::Transaction::begin();
+        let mut results: Vec<KnobResult> = Vec::with_capacity(self.changes.len());
+        let mut changes = self.changes.into_iter();
+
+        while let Some(change) = changes.next() {
+            if change.announce {
+                info!("Setting {} to '{}'", change.knob, change.value);
+            }
+
+            let outcome = match (change.apply)() {
+                Ok(true) => KnobOutcome::Applied,
+                Ok(false) => KnobOutcome::Unchanged,
+                Err(EngineError::ControlError(ControlError::NotSupported(_))) => {
+                    warn!(
+                        "{knob} setting is not supported on this system. Skipping {knob} configuration.",
+                        knob = change.knob
+                    );
+                    KnobOutcome::Unsupported
+                }
+                Err(error) if change.soft => {
+                    warn!("Failed to set {}: {error}", change.knob);
+                    KnobOutcome::Failed(error.to_string())
+                }
+                Err(error) => {
+                    let reverted = transaction.rollback();
+                    // Auto turbo may have recorded a state that was just reverted
+                    get_turbo_states().reset();
+                    warn!(
+                        "Failed to set {}, rolled back the profile ({reverted} files reverted)",
+                        change.knob
+                    );
+
+                    for result in &mut results {
+                        if result.outcome == KnobOutcome::Applied {
+                            result.outcome = KnobOutcome::RolledBack;
+                        }
+                    }
+                    results.push(KnobResult {
+                        knob: change.knob.clone(),
+                        value: change.value,
+                        outcome: KnobOutcome::Failed(error.to_string()),
+                    });
+                    results.extend(changes.map(|change| KnobResult {
+                        knob: change.knob,
+                        value: change.value,
+                        outcome: KnobOutcome::NotAttempted,
+                    }));
+
+                    return Err(EngineError::RolledBack {
+                        knob: change.knob,
+                        source: Box::new(error),
+                        results,
+                    });
+                }
+            };
+
+            results.push(KnobResult {
+                knob: change.knob,
+                value: change.value,
+                outcome,
+            });
+        }
+
+        transaction.commit();
+        Ok(results)
+    }
+}
+
+/// Which CPUs a setting that differs between core types is written through
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum WriteScope {
+    /// cpufreq settings, shared by every CPU of a policy
+    Policy,
+    /// Settings of each CPU, such as EPB
+    Cpu,
+}
+
+/// Add a setting that may differ between performance and efficiency cores.
+/// `values` holds the profile-wide value and those for P-cores and E-cores.
+/// Without core type values, the setting is written to every core at once.
+fn apply_per_core_type<'a, T: Copy + 'a>(
+    changes: &mut ChangeSet<'a>,
+    feature_name: &str,
+    topology: &'a CpuTopology,
+    scope: WriteScope,
+    [value, p_cores, e_cores]: [Option<T>; 3],
+    describe: impl Fn(T) -> String,
+    apply: impl Fn(T, Option<u32>) -> Result<(), ControlError> + Copy + 'a,
+) {
+    if p_cores.is_none() && e_cores.is_none() {
+        if let Some(value) = value {
+            changes.add(feature_name, describe(value), move || apply(value, None));
+        }
+        return;
+    }
+
+    if !topology.is_hybrid() && e_cores.is_some() {
+        debug!("No efficiency cores detected, ignoring the E-core {feature_name} setting");
+    }
+
+    for (core_type, type_value) in [
+        (CoreType::Performance, p_cores),
+        (CoreType::Efficiency, e_cores),
+    ] {
+        let Some(value) = type_value.or(value) else {
+            continue;
+        };
+        if topology.cores_of(core_type).next().is_none() {
+            continue;
+        }
+
+        changes.push(
+            format!("{feature_name} for {core_type}s"),
+            describe(value),
+            move || {
+                // Chosen when written, as SMT and core parking earlier in the
+                // set change which cores are online
+                let cores: Vec<u32> = topology.cores_of(core_type).collect();
+                let cores = match scope {
+                    // Cores sharing a cpufreq policy share the setting, so one write per policy suffices
+                    WriteScope::Policy => cpu::one_cpu_per_policy(&cores),
+                    WriteScope::Cpu => cpu::online_cpus_of(&cores),
+                };
+                if cores.is_empty() {
+                    return Ok(false);
+                }
+                cores
+                    .iter()
+                    .try_for_each(|&core_id| apply(value, Some(core_id)))?;
+                Ok(true)
+            },
+        );
+    }
+}
+
+/// Add a package power limit, or the restoring of the original one when the
+/// profile does not set it
+fn apply_power_limit(
+    changes: &mut ChangeSet<'_>,
+    limit: PowerLimit,
+    watts: Option<f32>,
+    window_sec: Option<f32>,
+) {
+    let feature_name = match limit {
+        PowerLimit::LongTerm => "package power limit (PL1)",
+        PowerLimit::ShortTerm => "short-term package power limit (PL2)",
+    };
+
+    let Some(watts) = watts else {
+        changes.add_restore(feature_name, move || {
+            let restored = rapl::restore_power_limit(limit) > 0;
+            if restored {
+                info!("Restored the original {feature_name}");
+            }
+            restored
+        });
+        return;
+    };
+
+    let description = match window_sec {
+        Some(window) => format!("{watts} W over {window} s"),
+        None => format!("{watts} W"),
+    };
+    changes.add(feature_name, description, move || {
+        rapl::set_power_limit(limit, watts, window_sec)
+    });
+}
+
+/// Applies the settings of the selected profile (via helpers defined in the `cpu` module)
+/// as one transaction, returning the outcome of every setting. If a setting fails, the
+/// ones written before it are rolled back and [`EngineError::RolledBack`] is returned.
 pub fn determine_and_apply_settings(
     report: &SystemReport,
-    config: &AppConfig,
-    force_mode: Option<OperationalMode>,
-) -> Result<(), EngineError> {
+    selection: &ProfileSelection,
+) -> Result<Vec<KnobResult>, EngineError> {
+    let mut changes = ChangeSet::default();
+
     // First, check if there's a governor override set
     if let Some(override_governor) = cpu::get_governor_override() {
-        info!(
-            "Governor override is active: '{}'. Setting governor.",
-            override_governor.trim()
-        );
+        let override_governor = override_governor.trim().to_string();
+        info!("Governor override is active: '{override_governor}'. Setting governor.");
 
         // Apply the override governor setting
-        try_apply_feature("override governor", override_governor.trim(), || {
-            cpu::set_governor(override_governor.trim(), None)
-        })?;
+        changes.add("override governor", override_governor.clone(), move || {
+            cpu::set_governor(&override_governor, None)
+        });
     }
 
     // Determine AC/Battery status once, early in the function
-    // For desktops (no batteries), we should always use the AC power profile
-    // For laptops, we check if all batteries report connected to AC
-    let on_ac_power = if report.batteries.is_empty() {
-        // No batteries means desktop/server, always on AC
-        true
-    } else {
-        // Check if all batteries report AC connected
-        report.batteries.iter().all(|b| b.ac_connected)
-    };
+    let on_ac_power = profile::is_on_ac_power(report);
+
+    info!(
+        "Selecting '{}' profile ({}).",
+        selection.name, selection.reason
+    );
+    if let Some(trigger) = &selection.process_trigger {
+        info!("Applying settings of process trigger '{trigger}'.");
+    }
+    if !selection.battery_tiers.is_empty() {
+        info!(
+            "Applying battery tier settings for capacity below {}%.",
+            selection
+                .battery_tiers
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join("%, ")
+        );
+    }
+    let selected_profile_config: &ProfileConfig = &selection.profile;
 
-    let selected_profile_config: &ProfileConfig;
+    let topology = &report.topology;
+    let (p_cores, e_cores) = (
+        &selected_profile_config.p_cores,
+        &selected_profile_config.e_cores,
+    );
 
-    if let Some(mode) = force_mode {
-        match mode {
-            OperationalMode::Powersave => {
-                info!("Forced Powersave mode selected. Applying 'battery' profile.");
-                selected_profile_config = &config.battery;
-            }
-            OperationalMode::Performance => {
-                info!("Forced Performance mode selected. Applying 'charger' profile.");
-                selected_profile_config = &config.charger;
-            }
+    // SMT and core parking come first, so that the settings below reach every online core
+    if let Some(smt) = selected_profile_config.smt {
+        changes.add("SMT", format!("{smt:?}"), move || cpu::set_smt(smt));
+    }
+
+    match &selected_profile_config.online_cores {
+        Some(online_cores) => {
+            changes.add("online cores", online_cores.to_string(), move || {
+                cpu::set_online_cores(online_cores, topology)
+            });
         }
-    } else {
-        // Use the previously computed on_ac_power value
-        if on_ac_power {
-            info!("On AC power, selecting Charger profile.");
-            selected_profile_config = &config.charger;
-        } else {
-            info!("On Battery power, selecting Battery profile.");
-            selected_profile_config = &config.battery;
+        None => {
+            changes.add_restore("online cores", || {
+                let unparked = cpu::restore_online_cores();
+                if unparked > 0 {
+                    info!("Brought {unparked} parked cores back online");
+                }
+                unparked > 0
+            });
         }
     }
 
+    // Switching the intel_pstate mode resets the governor, so it comes first
+    let intel_pstate = &selected_profile_config.intel_pstate;
+    if let Some(status) = intel_pstate.status {
+        changes.add("intel_pstate status", status.as_str(), move || {
+            pstate::set_intel_pstate_status(status)
+        });
+    }
+
+    if let Some(status) = selected_profile_config.amd_pstate.status {
+        changes.add("amd_pstate status", status.as_str(), move || {
+            pstate::set_amd_pstate_status(status)
+        });
+    }
+
+    // amd_pstate only uses EPP in active mode, where it only offers the
+    // performance and powersave governors. Settings the mode ignores are
+    // skipped instead of failing on every cycle. The profile may switch the
+    // mode above, so the settings are matched against the mode it selects.
+    let amd_pstate_mode = pstate::get_amd_pstate_status()
+        .map(|current| selected_profile_config.amd_pstate.status.unwrap_or(current));
+    let governor_applies = |governor: &&str| match amd_pstate_mode {
+        Some(AmdPstateStatus::Active)
+            if !pstate::AMD_PSTATE_ACTIVE_GOVERNORS.contains(governor) =>
+        {
+            debug!("Skipping governor '{governor}', amd_pstate in active mode is guided by EPP");
+            false
+        }
+        _ => true,
+    };
+    let epp_applies = |epp: &&str| match amd_pstate_mode {
+        Some(mode @ (AmdPstateStatus::Guided | AmdPstateStatus::Passive)) => {
+            debug!("Skipping EPP '{epp}', amd_pstate in {mode} mode is driven by the governor");
+            false
+        }
+        _ => true,
+    };
+
     // Apply settings from selected_profile_config
-    if let Some(governor) = &selected_profile_config.governor {
-        info!("Setting governor to '{governor}'");
-        // Let set_governor handle the validation
-        if let Err(e) = cpu::set_governor(governor, None) {
+    apply_per_core_type(
+        &mut changes,
+        "governor",
+        topology,
+        WriteScope::Policy,
+        [
+            selected_profile_config
+                .governor
+                .as_deref()
+                .filter(governor_applies),
+            p_cores.governor.as_deref().filter(governor_applies),
+            e_cores.governor.as_deref().filter(governor_applies),
+        ],
+        ToString::to_string,
+        |governor, core_id| match cpu::set_governor(governor, core_id) {
             // If the governor is not available, log a warning
-            if matches!(e, ControlError::InvalidGovernor(_))
-                || matches!(e, ControlError::NotSupported(_))
-            {
+            Err(ControlError::InvalidGovernor(_)) => {
                 warn!(
                     "Configured governor '{governor}' is not available on this system. Skipping."
                 );
-            } else {
-                return Err(e.into());
+                Ok(())
             }
+            result => result,
+        },
+    );
+
+    // Tunables only exist while their governor is in use, so they follow it
+    for (governor, tunables) in [
+        ("schedutil", selected_profile_config.schedutil.tunables()),
+        ("ondemand", selected_profile_config.ondemand.tunables()),
+        (
+            "conservative",
+            selected_profile_config.conservative.tunables(),
+        ),
+    ] {
+        for (tunable, value) in tunables {
+            changes.push(format!("{governor} {tunable}"), value.clone(), move || {
+                if !governor::governor_in_use(governor) {
+                    debug!("Skipping the {governor} {tunable} tunable, the governor is not in use");
+                    return Ok(false);
+                }
+                governor::set_governor_tunable(governor, tunable, &value)?;
+                Ok(true)
+            });
         }
     }
 
     if let Some(turbo_setting) = selected_profile_config.turbo {
-        info!("Setting turbo to '{turbo_setting:?}'");
         match turbo_setting {
             TurboSetting::Auto => {
                 if selected_profile_config.enable_auto_turbo {
-                    debug!("Managing turbo in auto mode based on system conditions");
-                    manage_auto_turbo(report, selected_profile_config, on_ac_power)?;
+                    changes.push("Turbo boost", "Auto (managed by Watt)", move || {
+                        debug!("Managing turbo in auto mode based on system conditions");
+                        manage_auto_turbo(report, selected_profile_config, on_ac_power)?;
+                        Ok(true)
+                    });
                 } else {
                     debug!(
                         "Watt's dynamic turbo management is disabled by configuration. Ensuring system uses its default behavior for automatic turbo control."
                     );
                     // Make sure the system is set to its default automatic turbo mode.
                     // This is important if turbo was previously forced off.
-                    try_apply_feature("Turbo boost", "system default (Auto)", || {
+                    changes.add("Turbo boost", "system default (Auto)", || {
                         cpu::set_turbo(TurboSetting::Auto)
-                    })?;
+                    });
                 }
             }
             _ => {
-                try_apply_feature("Turbo boost", &format!("{turbo_setting:?}"), || {
+                changes.add("Turbo boost", format!("{turbo_setting:?}"), move || {
                     cpu::set_turbo(turbo_setting)
-                })?;
+                });
+            }
+        }
+    }
+
+    apply_per_core_type(
+        &mut changes,
+        "EPP",
+        topology,
+        WriteScope::Policy,
+        [
+            selected_profile_config.epp.as_deref().filter(epp_applies),
+            p_cores.epp.as_deref().filter(epp_applies),
+            e_cores.epp.as_deref().filter(epp_applies),
+        ],
+        ToString::to_string,
+        cpu::set_epp,
+    );
+
+    apply_per_core_type(
+        &mut changes,
+        "EPB",
+        topology,
+        WriteScope::Cpu,
+        [
+            selected_profile_config.epb.as_deref(),
+            p_cores.epb.as_deref(),
+            e_cores.epb.as_deref(),
+        ],
+        ToString::to_string,
+        cpu::set_epb,
+    );
+
+    // Both limits are written together, so the order of the writes can keep
+    // the minimum below the maximum
+    let frequency_limits = |min: Option<FrequencyLimit>, max: Option<FrequencyLimit>| {
+        (min.is_some() || max.is_some()).then_some((min, max))
+    };
+    let (min_freq, max_freq) = (
+        selected_profile_config.min_freq,
+        selected_profile_config.max_freq,
+    );
+    apply_per_core_type(
+        &mut changes,
+        "frequency limits",
+        topology,
+        WriteScope::Policy,
+        [
+            frequency_limits(min_freq, max_freq),
+            frequency_limits(p_cores.min_freq, p_cores.max_freq)
+                .map(|(min, max)| (min.or(min_freq), max.or(max_freq))),
+            frequency_limits(e_cores.min_freq, e_cores.max_freq)
+                .map(|(min, max)| (min.or(min_freq), max.or(max_freq))),
+        ],
+        |(min, max)| {
+            [("min", min), ("max", max)]
+                .into_iter()
+                .filter_map(|(limit, freq)| Some(format!("{limit} {}", freq?)))
+                .collect::<Vec<_>>()
+                .join(", ")
+        },
+        |(min, max), core_id| cpu::set_frequency_limits(min, max, core_id),
+    );
+
+    if intel_pstate.min_perf_pct.is_some() || intel_pstate.max_perf_pct.is_some() {
+        let limits: Vec<String> = [
+            ("min", intel_pstate.min_perf_pct),
+            ("max", intel_pstate.max_perf_pct),
+        ]
+        .into_iter()
+        .filter_map(|(limit, percent)| Some(format!("{limit} {}%", percent?)))
+        .collect();
+        changes.add("intel_pstate performance limits", limits.join(", "), || {
+            pstate::set_perf_pct(intel_pstate.min_perf_pct, intel_pstate.max_perf_pct)
+        });
+    }
+    if let Some(enabled) = intel_pstate.hwp_dynamic_boost {
+        changes.add("HWP dynamic boost", enabled.to_string(), move || {
+            pstate::set_hwp_dynamic_boost(enabled)
+        });
+    }
+    if let Some(enabled) = intel_pstate.energy_efficiency {
+        changes.add(
+            "intel_pstate energy efficiency",
+            enabled.to_string(),
+            move || pstate::set_energy_efficiency(enabled),
+        );
+    }
+
+    let (uncore_min, uncore_max) = (
+        selected_profile_config.uncore_min_freq_mhz,
+        selected_profile_config.uncore_max_freq_mhz,
+    );
+    if uncore_min.is_some() || uncore_max.is_some() {
+        let limits: Vec<String> = [("min", uncore_min), ("max", uncore_max)]
+            .into_iter()
+            .filter_map(|(limit, freq)| Some(format!("{limit} {} MHz", freq?)))
+            .collect();
+        changes.add("uncore frequency", limits.join(", "), move || {
+            cpu::set_uncore_frequency(uncore_min, uncore_max)
+        });
+    }
+    // Limits the profile does not set go back to their original values
+    let restore_file = match (uncore_min, uncore_max) {
+        (None, None) => Some(None),
+        (Some(_), None) => Some(Some("max_freq_khz")),
+        (None, Some(_)) => Some(Some("min_freq_khz")),
+        (Some(_), Some(_)) => None,
+    };
+    if let Some(file) = restore_file {
+        changes.add_restore("uncore frequency", move || {
+            let restored = cpu::restore_uncore_frequency(file) > 0;
+            if restored {
+                info!("Restored the original uncore frequency limits");
             }
+            restored
+        });
+    }
+
+    match &selected_profile_config.cpuidle_governor {
+        Some(governor) => {
+            changes.add("cpuidle governor", governor, || {
+                cpuidle::set_cpuidle_governor(governor)
+            });
+        }
+        None => {
+            changes.add_restore("cpuidle governor", || {
+                let restored = cpuidle::restore_cpuidle_governor();
+                if restored {
+                    info!("Restored the original cpuidle governor");
+                }
+                restored
+            });
         }
     }
 
-    if let Some(epp) = &selected_profile_config.epp {
-        try_apply_feature("EPP", epp, || cpu::set_epp(epp, None))?;
+    match &selected_profile_config.disabled_idle_states {
+        Some(states) => {
+            let description = if states.is_empty() {
+                "none".to_string()
+            } else {
+                states.join(", ")
+            };
+            changes.add("disabled idle states", description, || {
+                cpuidle::set_disabled_idle_states(states)
+            });
+        }
+        None => {
+            changes.add_restore("disabled idle states", || {
+                let enabled = cpuidle::restore_idle_states();
+                if enabled > 0 {
+                    info!("Restored {enabled} idle state settings changed by Watt");
+                }
+                enabled > 0
+            });
+        }
     }
 
-    if let Some(epb) = &selected_profile_config.epb {
-        try_apply_feature("EPB", epb, || cpu::set_epb(epb, None))?;
+    let (gpu_min, gpu_max, gpu_boost) = (
+        selected_profile_config.gpu_min_freq_mhz,
+        selected_profile_config.gpu_max_freq_mhz,
+        selected_profile_config.gpu_boost_freq_mhz,
+    );
+    if gpu_min.is_some() || gpu_max.is_some() || gpu_boost.is_some() {
+        let limits: Vec<String> = [("min", gpu_min), ("max", gpu_max), ("boost", gpu_boost)]
+            .into_iter()
+            .filter_map(|(limit, freq)| Some(format!("{limit} {} MHz", freq?)))
+            .collect();
+        changes.add("GPU frequency", limits.join(", "), move || {
+            gpu::set_gpu_frequency(gpu_min, gpu_max, gpu_boost)
+        });
+    }
+    // Limits the profile does not set go back to their original values
+    if gpu_min.is_none() || gpu_max.is_none() || gpu_boost.is_none() {
+        changes.add_restore("GPU frequency", move || {
+            let restored = gpu::restore_gpu_frequency(
+                gpu_min.is_none(),
+                gpu_max.is_none(),
+                gpu_boost.is_none(),
+            ) > 0;
+            if restored {
+                info!("Restored the original GPU frequency limits");
+            }
+            restored
+        });
+    }
+
+    match &selected_profile_config.gpu_dpm_level {
+        Some(level) => {
+            changes.add("GPU DPM level", level, || gpu::set_amd_gpu_dpm_level(level));
+        }
+        None => {
+            changes.add_restore("GPU DPM level", || {
+                let restored = gpu::restore_amd_gpu_dpm_level() > 0;
+                if restored {
+                    info!("Restored the original GPU DPM level");
+                }
+                restored
+            });
+        }
     }
 
-    if let Some(min_freq) = selected_profile_config.min_freq_mhz {
-        try_apply_feature("min frequency", &format!("{min_freq} MHz"), || {
-            cpu::set_min_frequency(min_freq, None)
-        })?;
+    match &selected_profile_config.gpu_power_profile {
+        Some(profile) => {
+            changes.add("GPU power profile", profile, || {
+                gpu::set_amd_gpu_power_profile(profile)
+            });
+        }
+        None => {
+            changes.add_restore("GPU power profile", || {
+                let restored = gpu::restore_amd_gpu_power_profile() > 0;
+                if restored {
+                    info!("Restored the original GPU power profile");
+                }
+                restored
+            });
+        }
     }
 
-    if let Some(max_freq) = selected_profile_config.max_freq_mhz {
-        try_apply_feature("max frequency", &format!("{max_freq} MHz"), || {
-            cpu::set_max_frequency(max_freq, None)
-        })?;
+    apply_power_limit(
+        &mut changes,
+        PowerLimit::LongTerm,
+        selected_profile_config.package_power_limit_w,
+        selected_profile_config.package_power_window_sec,
+    );
+    apply_power_limit(
+        &mut changes,
+        PowerLimit::ShortTerm,
+        selected_profile_config.package_power_limit_short_w,
+        selected_profile_config.package_power_window_short_sec,
+    );
+    if selected_profile_config.package_power_limit_w.is_none()
+        && selected_profile_config
+            .package_power_limit_short_w
+            .is_none()
+    {
+        // Re-disable zones that only had limits while the profile set them
+        changes.add_restore("package power limits", || rapl::restore_power_limits() > 0);
     }
 
     if let Some(profile) = &selected_profile_config.platform_profile {
-        try_apply_feature("platform profile", profile, || {
+        changes.add("platform profile", profile, || {
             cpu::set_platform_profile(profile)
-        })?;
+        });
     }
 
     // Set battery charge thresholds if configured
@@ -277,11 +891,11 @@ pub fn determine_and_apply_settings(
         let stop_threshold = thresholds.stop;
 
         if start_threshold < stop_threshold && stop_threshold <= 100 {
-            info!("Setting battery charge thresholds: {start_threshold}-{stop_threshold}%");
-            match battery::set_battery_charge_thresholds(start_threshold, stop_threshold) {
-                Ok(()) => debug!("Battery charge thresholds set successfully"),
-                Err(e) => warn!("Failed to set battery charge thresholds: {e}"),
-            }
+            changes.add_soft(
+                "battery charge thresholds",
+                format!("{start_threshold}-{stop_threshold}%"),
+                move || battery::set_battery_charge_thresholds(start_threshold, stop_threshold),
+            );
         } else {
             warn!(
                 "Invalid battery threshold values: start={start_threshold}, stop={stop_threshold}"
@@ -289,9 +903,10 @@ pub fn determine_and_apply_settings(
         }
     }
 
+    let results = changes.apply()?;
     debug!("Profile settings applied successfully.");
 
-    Ok(())
+    Ok(results)
 }
 
 fn manage_auto_turbo(
@@ -0,0 +1,125 @@
+// Tunables of the cpufreq governors
+use crate::cpu;
+use crate::util::error::ControlError;
+use crate::util::sysfs;
+use log::debug;
+use std::path::{Path, PathBuf};
+
+pub type Result<T, E = ControlError> = std::result::Result<T, E>;
+
+const CPUFREQ_PATH: &str = "/sys/devices/system/cpu/cpufreq";
+
+/// Tunables that Watt manages, by governor
+pub const GOVERNOR_TUNABLES: &[(&str, &[&str])] = &[
+    ("schedutil", &["rate_limit_us"]),
+    (
+        "ondemand",
+        &[
+            "up_threshold",
+            "sampling_rate",
+            "powersave_bias",
+            "io_is_busy",
+        ],
+    ),
+    ("conservative", &["freq_step", "down_threshold"]),
+];
+
+/// Tunables of a governor directory with their current values, `None` for
+/// tunables the directory does not have
+pub struct GovernorTunables {
+    pub governor: &'static str,
+    /// The policy the tunables belong to, `None` if they are shared by all
+    pub policy: Option<u32>,
+    pub values: Vec<(&'static str, Option<String>)>,
+}
+
+/// IDs of the cpufreq policies currently running a governor
+fn policies_running(governor: &str) -> Vec<u32> {
+    cpu::get_policy_ids()
+        .into_iter()
+        .filter(|id| {
+            sysfs::read_sysfs_value(format!("{CPUFREQ_PATH}/policy{id}/scaling_governor"))
+                .is_ok_and(|current| current == governor)
+        })
+        .collect()
+}
+
+/// Whether any cpufreq policy runs the governor
+pub fn governor_in_use(governor: &str) -> bool {
+    !policies_running(governor).is_empty()
+}
+
+/// Directories holding the tunables of a governor, with the policy they belong
+/// to. Depending on the driver, a governor keeps its tunables per policy in
+/// `policyN/<governor>`, or shares one `cpufreq/<governor>` directory between
+/// all policies. Either only exists while the governor is in use.
+fn tunable_dirs(governor: &str) -> Vec<(Option<u32>, PathBuf)> {
+    let running = policies_running(governor);
+
+    let mut dirs: Vec<(Option<u32>, PathBuf)> = running
+        .iter()
+        .map(|&id| {
+            let dir = Path::new(CPUFREQ_PATH)
+                .join(format!("policy{id}"))
+                .join(governor);
+            (Some(id), dir)
+        })
+        .filter(|(_, dir)| dir.is_dir())
+        .collect();
+
+    let shared = Path::new(CPUFREQ_PATH).join(governor);
+    if !running.is_empty() && shared.is_dir() {
+        dirs.push((None, shared));
+    }
+    dirs
+}
+
+/// Set a tunable of a governor for every policy running it
+///
+/// # Errors
+///
+/// Returns [`ControlError::NotSupported`] if the governor is not in use or
+/// does not have the tunable.
+pub fn set_governor_tunable(governor: &str, tunable: &str, value: &str) -> Result<()> {
+    let dirs = tunable_dirs(governor);
+    if dirs.is_empty() {
+        return Err(ControlError::NotSupported(format!(
+            "The {governor} governor is not in use"
+        )));
+    }
+
+    for (_, dir) in dirs {
+        let path = dir.join(tunable);
+        if !path.exists() {
+            return Err(ControlError::NotSupported(format!(
+                "The {governor} governor has no {tunable} tunable in {}",
+                dir.display()
+            )));
+        }
+        if sysfs::read_sysfs_value(&path).is_ok_and(|current| current == value) {
+            continue;
+        }
+        sysfs::write_sysfs_value(&path, value)?;
+        debug!("Set {} to {value}", path.display());
+    }
+    Ok(())
+}
+
+/// The tunables of every governor in use, per directory
+pub fn get_governor_tunables() -> Vec<GovernorTunables> {
+    GOVERNOR_TUNABLES
+        .iter()
+        .flat_map(|&(governor, tunables)| {
+            tunable_dirs(governor)
+                .into_iter()
+                .map(move |(policy, dir)| GovernorTunables {
+                    governor,
+                    policy,
+                    values: tunables
+                        .iter()
+                        .map(|&tunable| (tunable, sysfs::read_sysfs_value(dir.join(tunable)).ok()))
+                        .collect(),
+                })
+        })
+        .collect()
+}
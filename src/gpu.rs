@@ -0,0 +1,548 @@
+// Control of integrated and discrete GPUs through DRM sysfs
+use crate::core::GpuInfo;
+use crate::restore;
+use crate::util::error::ControlError;
+use crate::util::sysfs;
+use log::debug;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub type Result<T, E = ControlError> = std::result::Result<T, E>;
+
+const DRM_PATH: &str = "/sys/class/drm";
+
+/// Values accepted by `power_dpm_force_performance_level` of amdgpu
+pub const AMDGPU_DPM_LEVELS: &[&str] = &[
+    "auto",
+    "low",
+    "high",
+    "manual",
+    "profile_standard",
+    "profile_min_sclk",
+    "profile_min_mclk",
+    "profile_peak",
+    "perf_determinism",
+];
+
+/// Kernel driver of a GPU
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GpuDriver {
+    I915,
+    Xe,
+    Amdgpu,
+    Other,
+}
+
+impl fmt::Display for GpuDriver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::I915 => write!(f, "i915"),
+            Self::Xe => write!(f, "xe"),
+            Self::Amdgpu => write!(f, "amdgpu"),
+            Self::Other => write!(f, "other"),
+        }
+    }
+}
+
+/// A GPU, e.g. `/sys/class/drm/card1`
+#[derive(Debug, Clone)]
+pub struct GpuCard {
+    /// Card name, e.g. `card1`
+    pub name: String,
+    path: PathBuf,
+    pub driver: GpuDriver,
+}
+
+/// Files controlling the frequency of an Intel GPU. i915 exposes one set per
+/// card, xe one per GT (e.g. render and media).
+#[derive(Debug, Clone)]
+struct FrequencyFiles {
+    min: PathBuf,
+    max: PathBuf,
+    /// Only i915 has a separate boost frequency
+    boost: Option<PathBuf>,
+    actual: PathBuf,
+    requested: PathBuf,
+    /// Lowest and highest frequency the hardware supports (RPn and RP0)
+    hardware_min: PathBuf,
+    hardware_max: PathBuf,
+}
+
+impl FrequencyFiles {
+    fn i915(card: &Path) -> Self {
+        let file = |name: &str| card.join(format!("gt_{name}_freq_mhz"));
+        Self {
+            min: file("min"),
+            max: file("max"),
+            boost: Some(file("boost")),
+            actual: file("act"),
+            requested: file("cur"),
+            hardware_min: file("RPn"),
+            hardware_max: file("RP0"),
+        }
+    }
+
+    fn xe(freq_dir: &Path) -> Self {
+        let file = |name: &str| freq_dir.join(format!("{name}_freq"));
+        Self {
+            min: file("min"),
+            max: file("max"),
+            boost: None,
+            actual: file("act"),
+            requested: file("cur"),
+            hardware_min: file("rpn"),
+            hardware_max: file("rp0"),
+        }
+    }
+}
+
+fn read_mhz(path: &Path) -> Option<u32> {
+    sysfs::read_sysfs_value(path).ok()?.parse().ok()
+}
+
+/// Every GPU in `/sys/class/drm`, skipping connectors such as `card1-eDP-1`
+pub fn gpu_cards() -> Vec<GpuCard> {
+    let Ok(entries) = fs::read_dir(DRM_PATH) else {
+        return Vec::new();
+    };
+
+    let mut cards: Vec<GpuCard> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_str()?.to_string();
+            name.strip_prefix("card")?.parse::<u32>().ok()?;
+
+            let path = entry.path();
+            let driver = fs::read_link(path.join("device/driver"))
+                .ok()
+                .and_then(|driver| {
+                    driver
+                        .file_name()
+                        .map(|name| name.to_string_lossy().into_owned())
+                });
+            let driver = match driver.as_deref() {
+                Some("i915") => GpuDriver::I915,
+                Some("xe") => GpuDriver::Xe,
+                Some("amdgpu") => GpuDriver::Amdgpu,
+                _ => GpuDriver::Other,
+            };
+            Some(GpuCard { name, path, driver })
+        })
+        .collect();
+    cards.sort_by_key(|card| card.name[4..].parse::<u32>().unwrap_or(u32::MAX));
+    cards
+}
+
+/// Frequency control files of an Intel GPU, empty for other GPUs
+fn frequency_files(card: &GpuCard) -> Vec<FrequencyFiles> {
+    match card.driver {
+        GpuDriver::I915 => {
+            let files = FrequencyFiles::i915(&card.path);
+            if files.min.exists() {
+                vec![files]
+            } else {
+                Vec::new()
+            }
+        }
+        GpuDriver::Xe => xe_frequency_dirs(&card.path.join("device"))
+            .iter()
+            .map(|dir| FrequencyFiles::xe(dir))
+            .collect(),
+        GpuDriver::Amdgpu | GpuDriver::Other => Vec::new(),
+    }
+}
+
+/// The `tile*/gt*/freq0` directories of an xe device
+fn xe_frequency_dirs(device: &Path) -> Vec<PathBuf> {
+    let subdirs = |dir: &Path, prefix: &str| -> Vec<PathBuf> {
+        let Ok(entries) = fs::read_dir(dir) else {
+            return Vec::new();
+        };
+        let mut dirs: Vec<PathBuf> = entries
+            .flatten()
+            .filter(|entry| {
+                entry
+                    .file_name()
+                    .to_str()
+                    .is_some_and(|name| name.starts_with(prefix))
+            })
+            .map(|entry| entry.path())
+            .collect();
+        dirs.sort();
+        dirs
+    };
+
+    subdirs(device, "tile")
+        .iter()
+        .flat_map(|tile| subdirs(tile, "gt"))
+        .map(|gt| gt.join("freq0"))
+        .filter(|freq| freq.exists())
+        .collect()
+}
+
+/// Get the Intel GPUs whose frequency can be controlled
+///
+/// # Errors
+///
+/// Returns [`ControlError::NotSupported`] if there is no i915 or xe GPU with
+/// frequency controls.
+pub fn get_intel_gpus() -> Result<Vec<GpuCard>> {
+    let cards: Vec<GpuCard> = gpu_cards()
+        .into_iter()
+        .filter(|card| !frequency_files(card).is_empty())
+        .collect();
+
+    if cards.is_empty() {
+        return Err(ControlError::NotSupported(format!(
+            "No Intel GPU frequency controls found in {DRM_PATH}."
+        )));
+    }
+    Ok(cards)
+}
+
+/// Frequencies of every GPU for the system report
+pub fn get_gpu_info() -> Vec<GpuInfo> {
+    gpu_cards()
+        .into_iter()
+        .map(|card| {
+            // The first GT of xe GPUs is the render engine
+            let files = frequency_files(&card);
+            let files = files.first();
+            let read = |path: Option<&PathBuf>| path.and_then(|path| read_mhz(path));
+
+            let device = card.path.join("device");
+            let amdgpu = card.driver == GpuDriver::Amdgpu;
+
+            GpuInfo {
+                card: card.name.clone(),
+                driver: card.driver.to_string(),
+                busy_percent: sysfs::read_sysfs_value(device.join("gpu_busy_percent"))
+                    .ok()
+                    .and_then(|busy| busy.parse().ok()),
+                dpm_level: amdgpu
+                    .then(|| {
+                        sysfs::read_sysfs_value(device.join("power_dpm_force_performance_level"))
+                            .ok()
+                    })
+                    .flatten(),
+                power_profile: amdgpu
+                    .then(|| {
+                        read_power_profiles(&device)
+                            .into_iter()
+                            .find(|profile| profile.active)
+                            .map(|profile| profile.name)
+                    })
+                    .flatten(),
+                current_freq_mhz: read(files.map(|f| &f.actual)),
+                requested_freq_mhz: read(files.map(|f| &f.requested)),
+                min_freq_mhz: read(files.map(|f| &f.min)),
+                max_freq_mhz: read(files.map(|f| &f.max)),
+                boost_freq_mhz: read(files.and_then(|f| f.boost.as_ref())),
+            }
+        })
+        .collect()
+}
+
+fn write_mhz(path: &Path, freq: u32) -> Result<()> {
+    if read_mhz(path) == Some(freq) {
+        return Ok(());
+    }
+    restore::record(path);
+    sysfs::write_sysfs_value(path, &freq.to_string())?;
+    debug!("Set {} to {freq} MHz", path.display());
+    Ok(())
+}
+
+/// Set the frequency limits of every Intel GPU. The limits must lie within
+/// the hardware range and are written in the order that keeps the minimum
+/// below the maximum. The boost frequency only exists on i915.
+pub fn set_gpu_frequency(
+    min_mhz: Option<u32>,
+    max_mhz: Option<u32>,
+    boost_mhz: Option<u32>,
+) -> Result<()> {
+    if let (Some(min), Some(max)) = (min_mhz, max_mhz) {
+        if min > max {
+            return Err(ControlError::InvalidValueError(format!(
+                "Minimum GPU frequency ({min} MHz) cannot be higher than maximum GPU frequency ({max} MHz)"
+            )));
+        }
+    }
+
+    let cards = get_intel_gpus()?;
+    if boost_mhz.is_some() && !cards.iter().any(|card| card.driver == GpuDriver::I915) {
+        return Err(ControlError::NotSupported(
+            "GPU boost frequency is only available with the i915 driver".to_string(),
+        ));
+    }
+
+    for card in &cards {
+        for files in frequency_files(card) {
+            let hardware = read_mhz(&files.hardware_min).zip(read_mhz(&files.hardware_max));
+            if let Some((hw_min, hw_max)) = hardware {
+                for freq in [min_mhz, max_mhz, boost_mhz].into_iter().flatten() {
+                    if freq < hw_min || freq > hw_max {
+                        return Err(ControlError::InvalidValueError(format!(
+                            "GPU frequency ({freq} MHz) is outside the range of {hw_min}-{hw_max} MHz for {}",
+                            card.name
+                        )));
+                    }
+                }
+            }
+
+            // Raising the minimum above the current maximum needs the maximum first
+            let raise_max_first =
+                min_mhz.is_some_and(|min| read_mhz(&files.max).is_some_and(|max| min > max));
+            let min_write = min_mhz.map(|freq| (&files.min, freq));
+            let max_write = max_mhz.map(|freq| (&files.max, freq));
+            let writes = if raise_max_first {
+                [max_write, min_write]
+            } else {
+                [min_write, max_write]
+            };
+            for (path, freq) in writes.into_iter().flatten() {
+                write_mhz(path, freq)?;
+            }
+
+            if let (Some(boost), Some(path)) = (boost_mhz, &files.boost) {
+                write_mhz(path, boost)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Restore the Intel GPU frequency limits changed by [`set_gpu_frequency`],
+/// only those selected. Returns how many limits were restored.
+pub fn restore_gpu_frequency(min: bool, max: bool, boost: bool) -> usize {
+    let mut paths = Vec::new();
+    for card in get_intel_gpus().unwrap_or_default() {
+        for files in frequency_files(&card) {
+            paths.extend(min.then_some(files.min));
+            paths.extend(max.then_some(files.max));
+            paths.extend(files.boost.filter(|_| boost));
+        }
+    }
+    restore::restore_where(|path| paths.iter().any(|p| p == path))
+}
+
+/// An entry of amdgpu's `pp_power_profile_mode`
+#[derive(Debug, Clone)]
+pub struct PowerProfile {
+    pub index: u32,
+    /// Mode name, e.g. `POWER_SAVING`
+    pub name: String,
+    pub active: bool,
+}
+
+/// Read and parse `pp_power_profile_mode`. Depending on the GPU generation the modes
+/// are followed by their heuristics, but every mode line starts with its
+/// index and name, and the active mode is marked with `*`.
+fn read_power_profiles(device: &Path) -> Vec<PowerProfile> {
+    fs::read_to_string(device.join("pp_power_profile_mode"))
+        .map(|content| parse_power_profiles(&content))
+        .unwrap_or_default()
+}
+
+fn parse_power_profiles(content: &str) -> Vec<PowerProfile> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let index = fields.next()?.parse().ok()?;
+            let name = fields.next()?;
+            let active = name.contains('*') || fields.next().is_some_and(|f| f.starts_with('*'));
+            let name = name.trim_end_matches([':', '*']).to_string();
+            (!name.is_empty()).then_some(PowerProfile {
+                index,
+                name,
+                active,
+            })
+        })
+        .collect()
+}
+
+/// Get the amdgpu GPUs with power management controls
+///
+/// # Errors
+///
+/// Returns [`ControlError::NotSupported`] if there is no amdgpu GPU with
+/// `power_dpm_force_performance_level`.
+pub fn get_amd_gpus() -> Result<Vec<GpuCard>> {
+    let cards: Vec<GpuCard> = gpu_cards()
+        .into_iter()
+        .filter(|card| {
+            card.driver == GpuDriver::Amdgpu
+                && card
+                    .path
+                    .join("device/power_dpm_force_performance_level")
+                    .exists()
+        })
+        .collect();
+
+    if cards.is_empty() {
+        return Err(ControlError::NotSupported(format!(
+            "No amdgpu power management controls found in {DRM_PATH}."
+        )));
+    }
+    Ok(cards)
+}
+
+/// Get the power profile modes offered by the amdgpu GPUs
+pub fn get_amd_gpu_power_profiles() -> Result<Vec<String>> {
+    let mut names: Vec<String> = Vec::new();
+    for card in get_amd_gpus()? {
+        for profile in read_power_profiles(&card.path.join("device")) {
+            if !names.contains(&profile.name) {
+                names.push(profile.name);
+            }
+        }
+    }
+    Ok(names)
+}
+
+/// Force the DPM performance level of every amdgpu GPU, e.g. `low` to pin
+/// an APU's GPU to its lowest clocks on battery
+pub fn set_amd_gpu_dpm_level(level: &str) -> Result<()> {
+    if !AMDGPU_DPM_LEVELS.contains(&level) {
+        return Err(ControlError::InvalidValueError(format!(
+            "Invalid GPU DPM level '{level}'. Valid levels: {}",
+            AMDGPU_DPM_LEVELS.join(", ")
+        )));
+    }
+
+    for card in get_amd_gpus()? {
+        let path = card.path.join("device/power_dpm_force_performance_level");
+        if sysfs::read_sysfs_value(&path).is_ok_and(|current| current == level) {
+            continue;
+        }
+        restore::record(&path);
+        sysfs::write_sysfs_value(&path, level)?;
+        debug!("Set DPM level of {} to {level}", card.name);
+    }
+    Ok(())
+}
+
+/// Restore the DPM performance levels changed by [`set_amd_gpu_dpm_level`],
+/// returning how many were restored
+pub fn restore_amd_gpu_dpm_level() -> usize {
+    restore_amd_gpu_file("power_dpm_force_performance_level")
+}
+
+/// Restore the power profile modes changed by [`set_amd_gpu_power_profile`],
+/// returning how many were restored
+pub fn restore_amd_gpu_power_profile() -> usize {
+    restore_amd_gpu_file("pp_power_profile_mode")
+}
+
+fn restore_amd_gpu_file(file: &str) -> usize {
+    restore::restore_where(|path| {
+        path.starts_with(DRM_PATH) && path.file_name().is_some_and(|name| name == file)
+    })
+}
+
+/// Select a power profile mode, e.g. `POWER_SAVING`, on every amdgpu GPU.
+/// Names are matched case-insensitively, and a mode index is accepted too.
+pub fn set_amd_gpu_power_profile(profile: &str) -> Result<()> {
+    for card in get_amd_gpus()? {
+        let device = card.path.join("device");
+        let profiles = read_power_profiles(&device);
+        if profiles.is_empty() {
+            return Err(ControlError::NotSupported(format!(
+                "{} has no power profile modes",
+                card.name
+            )));
+        }
+
+        let Some(selected) = profiles
+            .iter()
+            .find(|p| p.name.eq_ignore_ascii_case(profile) || profile.parse() == Ok(p.index))
+        else {
+            return Err(ControlError::InvalidValueError(format!(
+                "GPU power profile '{profile}' is not available on {}. Available profiles: {}",
+                card.name,
+                profiles
+                    .iter()
+                    .map(|p| p.name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )));
+        };
+        if selected.active {
+            continue;
+        }
+
+        // The file reads back as a table, so restore the active mode's index
+        let path = device.join("pp_power_profile_mode");
+        if let Some(active) = profiles.iter().find(|p| p.active) {
+            let index = active.index.to_string();
+            restore::record_value(&path, &index);
+            restore::journal_value(&path, &index);
+        }
+        sysfs::write_sysfs_value(&path, &selected.index.to_string())?;
+        debug!("Set power profile of {} to {}", card.name, selected.name);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summary(profiles: &[PowerProfile]) -> Vec<(u32, &str, bool)> {
+        profiles
+            .iter()
+            .map(|p| (p.index, p.name.as_str(), p.active))
+            .collect()
+    }
+
+    #[test]
+    fn apu_power_profiles_are_listed_one_per_line() {
+        let content = "0 BOOTUP_DEFAULT\n1 3D_FULL_SCREEN\n2 POWER_SAVING*\n3 VIDEO\n4 VR\n5 COMPUTE\n6 CUSTOM\n";
+        let profiles = parse_power_profiles(content);
+        assert_eq!(profiles.len(), 7);
+        assert_eq!(summary(&profiles)[2], (2, "POWER_SAVING", true));
+        assert_eq!(profiles.iter().filter(|p| p.active).count(), 1);
+    }
+
+    #[test]
+    fn table_headers_and_heuristics_are_skipped() {
+        let content = "\
+NUM        MODE_NAME     SCLK_UP_HYST   SCLK_DOWN_HYST SCLK_ACTIVE_LEVEL
+  0   BOOTUP_DEFAULT:        -                -                -
+  1 3D_FULL_SCREEN *:        0              100               30
+  2     POWER_SAVING:       10                0               30
+";
+        assert_eq!(
+            summary(&parse_power_profiles(content)),
+            [
+                (0, "BOOTUP_DEFAULT", false),
+                (1, "3D_FULL_SCREEN", true),
+                (2, "POWER_SAVING", false),
+            ]
+        );
+    }
+
+    #[test]
+    fn per_clock_heuristics_of_newer_gpus_are_skipped() {
+        let content = "\
+PROFILE_INDEX(NAME) CLOCK_TYPE(NAME) FPS MinFreqType MinActiveFreqType
+ 0 BOOTUP_DEFAULT*:
+                     0(       GFXCLK)       0       5       1
+                     1(       SOCCLK)       0       5       1
+ 1 3D_FULL_SCREEN :
+                     0(       GFXCLK)       1       5       1
+";
+        assert_eq!(
+            summary(&parse_power_profiles(content)),
+            [(0, "BOOTUP_DEFAULT", true), (1, "3D_FULL_SCREEN", false)]
+        );
+    }
+
+    #[test]
+    fn empty_files_have_no_profiles() {
+        assert!(parse_power_profiles("").is_empty());
+    }
+}
@@ -1,25 +1,50 @@
 mod battery;
 mod cli;
 mod config;
+mod control;
 mod core;
 mod cpu;
+mod cpuidle;
 mod daemon;
 mod engine;
+mod governor;
+mod gpu;
 mod monitor;
+mod process;
+mod profile;
+mod pstate;
+mod rapl;
+mod restore;
+mod rules;
+mod schedule;
+mod topology;
 mod util;
 
-use crate::config::AppConfig;
-use crate::core::{GovernorOverrideMode, TurboSetting};
+use crate::config::{AppConfig, FrequencyLimit};
+use crate::core::{
+    AmdPstateStatus, CpuCoreInfo, GovernorOverrideMode, IntelPstateStatus, SmtSetting, TurboSetting,
+};
+use crate::topology::CoreType;
 use crate::util::error::{AppError, ControlError};
-use clap::{Parser, value_parser};
+use clap::builder::BoolishValueParser;
+use clap::{ArgAction, Parser, value_parser};
 use env_logger::Builder;
 use log::{debug, error, info};
 use std::error::Error;
+use std::path::PathBuf;
 use std::sync::Once;
 
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
 struct Cli {
+    /// Load configuration from this file (and its drop-in directory) instead of the default locations
+    #[clap(long, global = true, value_name = "PATH", conflicts_with = "no_config")]
+    config: Option<PathBuf>,
+
+    /// Ignore all configuration files and use the built-in defaults
+    #[clap(long, global = true)]
+    no_config: bool,
+
     #[clap(subcommand)]
     command: Option<Commands>,
 }
@@ -50,6 +75,11 @@ enum Commands {
         #[clap(value_enum)]
         setting: TurboSetting,
     },
+    /// Turn simultaneous multithreading (Hyper-Threading) on or off
+    SetSmt {
+        #[clap(value_enum)]
+        setting: SmtSetting,
+    },
     /// Display comprehensive debug information
     Debug,
     /// Set Energy Performance Preference (EPP)
@@ -66,18 +96,50 @@ enum Commands {
     },
     /// Set minimum CPU frequency
     SetMinFreq {
-        freq_mhz: u32,
+        /// Frequency in MHz, or e.g. 2.4GHz, 70% (of the core's maximum), base or max
+        freq: FrequencyLimit,
         #[clap(long)]
         core_id: Option<u32>,
     },
     /// Set maximum CPU frequency
     SetMaxFreq {
-        freq_mhz: u32,
+        /// Frequency in MHz, or e.g. 2.4GHz, 70% (of the core's maximum), base or max
+        freq: FrequencyLimit,
         #[clap(long)]
         core_id: Option<u32>,
     },
     /// Set ACPI platform profile
     SetPlatformProfile { profile: String },
+    /// Switch the intel_pstate driver between active, passive and off
+    SetPstateStatus {
+        #[clap(value_enum)]
+        status: IntelPstateStatus,
+    },
+    /// Switch the amd_pstate driver between active, guided and passive
+    SetAmdPstateStatus {
+        #[clap(value_enum)]
+        status: AmdPstateStatus,
+    },
+    /// Set the lowest intel_pstate P-state in percent of the maximum performance
+    SetMinPerfPct {
+        #[clap(value_parser = value_parser!(u8).range(0..=100))]
+        percent: u8,
+    },
+    /// Set the highest intel_pstate P-state in percent of the maximum performance
+    SetMaxPerfPct {
+        #[clap(value_parser = value_parser!(u8).range(0..=100))]
+        percent: u8,
+    },
+    /// Enable or disable intel_pstate HWP dynamic boost
+    SetHwpDynamicBoost {
+        #[clap(action = ArgAction::Set, value_parser = BoolishValueParser::new())]
+        enabled: bool,
+    },
+    /// Enable or disable the intel_pstate energy efficiency optimization
+    SetEnergyEfficiency {
+        #[clap(action = ArgAction::Set, value_parser = BoolishValueParser::new())]
+        enabled: bool,
+    },
     /// Set battery charge thresholds to extend battery lifespan
     SetBatteryThresholds {
         /// Percentage at which charging starts (when below this value)
@@ -87,6 +149,72 @@ enum Commands {
         #[clap(value_parser = value_parser!(u8).range(1..=100))]
         stop_threshold: u8,
     },
+    /// Show which profile is selected right now and why
+    Explain,
+    /// List, select or reset named profiles
+    Profile {
+        #[clap(subcommand)]
+        command: ProfileCommands,
+    },
+    /// Inspect and validate the configuration
+    Config {
+        #[clap(subcommand)]
+        command: ConfigCommands,
+    },
+}
+
+#[derive(Parser, Debug)]
+enum ProfileCommands {
+    /// List all profiles and show which one is active
+    List,
+    /// Apply a profile and keep it selected regardless of the power source
+    Set {
+        /// Name of the profile, e.g. `battery` or a `[profiles.<name>]` entry
+        name: String,
+    },
+    /// Return to selecting profiles automatically
+    Reset,
+}
+
+#[derive(Parser, Debug)]
+enum ConfigCommands {
+    /// Check a configuration file for errors and unsupported settings
+    Check {
+        /// Path to the configuration file (defaults to the file Watt would load)
+        path: Option<PathBuf>,
+    },
+    /// Print the effective configuration and where each value came from
+    Dump {
+        /// Output format
+        #[clap(long, value_enum, default_value_t = cli::config::DumpFormat::Toml)]
+        format: cli::config::DumpFormat,
+        /// Print a fully commented reference configuration instead
+        #[clap(long, conflicts_with = "format")]
+        defaults: bool,
+    },
+}
+
+/// Load the configuration selected on the command line
+fn load_app_config(cli: &Cli) -> Result<AppConfig, AppError> {
+    if cli.no_config {
+        info!("Ignoring configuration files, using default values.");
+        Ok(AppConfig::default())
+    } else if let Some(path) = &cli.config {
+        // An explicitly selected configuration must load, never silently fall back
+        config::load_config_from_path(Some(path)).map_err(|e| {
+            error!("Error loading configuration from {}: {e}", path.display());
+            AppError::Config(e)
+        })
+    } else {
+        match config::load_config() {
+            Ok(cfg) => Ok(cfg),
+            Err(e) => {
+                error!("Error loading configuration: {e}. Using default values.");
+                // Proceed with default config if loading fails
+                Ok(AppConfig::default())
+            }
+        }
+    }
 }
 
 fn main() -> Result<(), AppError> {
@@ -96,14 +224,13 @@ fn main() -> Result<(), AppError> {
     let cli = Cli::parse();
 
     // Load configuration first, as it might be needed by the monitor module
-    // E.g., for ignored power supplies
-    let config = match config::load_config() {
-        Ok(cfg) => cfg,
-        Err(e) => {
-            error!("Error loading configuration: {e}. Using default values.");
-            // Proceed with default config if loading fails
-            AppConfig::default()
-        }
+    // E.g., for ignored power supplies. `config check` reads the file itself,
+    // so that a broken file is reported with its diagnostics.
+    let config = match &cli.command {
+        Some(Commands::Config {
+            command: ConfigCommands::Check { .. },
+        }) => AppConfig::default(),
+        _ => load_app_config(&cli)?,
     };
 
     let command_result: Result<(), AppError> = match cli.command {
@@ -173,6 +300,158 @@ fn main() -> Result<(), AppError> {
                         .as_deref()
                         .unwrap_or("N/A")
                 );
+                let scaling_driver = pstate::get_intel_pstate_status()
+                    .map(|status| format!("intel_pstate ({status})"))
+                    .or_else(|| {
+                        pstate::get_amd_pstate_status()
+                            .map(|status| format!("amd_pstate ({status})"))
+                    });
+                if let Some(scaling_driver) = scaling_driver {
+                    println!("P-State Driver:      {scaling_driver}");
+                }
+                // Policies are only listed when they are not all set up alike
+                let policies = &report.cpu_global.policies;
+                if policies
+                    .iter()
+                    .any(|policy| !policy.same_settings(&policies[0]))
+                {
+                    for policy in policies {
+                        let mhz =
+                            |freq: Option<u32>| freq.map_or("N/A".to_string(), |f| f.to_string());
+                        println!(
+                            "{:<21}CPUs {}, {}, EPP {}, {} MHz - {} MHz",
+                            format!("Policy {}:", policy.id),
+                            topology::format_cpu_list(&policy.cpus),
+                            policy.governor.as_deref().unwrap_or("N/A"),
+                            policy.epp.as_deref().unwrap_or("N/A"),
+                            mhz(policy.min_freq_mhz),
+                            mhz(policy.max_freq_mhz)
+                        );
+                    }
+                }
+                println!(
+                    "SMT:                 {}",
+                    match (&report.cpu_global.smt_control, report.cpu_global.smt_active) {
+                        (Some(control), Some(active)) =>
+                            format!("{control} ({})", if active { "active" } else { "inactive" }),
+                        (Some(control), None) => control.clone(),
+                        (None, _) => "N/A".to_string(),
+                    }
+                );
+                for uncore in cpu::get_uncore_frequencies() {
+                    let mhz = |freq: Option<u32>| freq.map_or("N/A".to_string(), |f| f.to_string());
+                    println!(
+                        "Uncore Frequency:    {} MHz ({} MHz - {} MHz, {})",
+                        mhz(uncore.current_mhz),
+                        mhz(uncore.min_mhz),
+                        mhz(uncore.max_mhz),
+                        uncore.domain
+                    );
+                }
+                for gpu in report.gpus.iter().filter(|gpu| gpu.max_freq_mhz.is_some()) {
+                    let mhz = |freq: Option<u32>| freq.map_or("N/A".to_string(), |f| f.to_string());
+                    let boost = gpu
+                        .boost_freq_mhz
+                        .map_or(String::new(), |boost| format!(", boost {boost} MHz"));
+                    println!(
+                        "GPU Frequency:       {} MHz, requested {} MHz ({} MHz - {} MHz{boost}, {} {})",
+                        mhz(gpu.current_freq_mhz),
+                        mhz(gpu.requested_freq_mhz),
+                        mhz(gpu.min_freq_mhz),
+                        mhz(gpu.max_freq_mhz),
+                        gpu.card,
+                        gpu.driver
+                    );
+                }
+                for gpu in &report.gpus {
+                    let details: Vec<String> = [
+                        gpu.busy_percent.map(|busy| format!("{busy}% busy")),
+                        gpu.dpm_level
+                            .as_ref()
+                            .map(|level| format!("DPM level {level}")),
+                        gpu.power_profile
+                            .as_ref()
+                            .map(|profile| format!("power profile {profile}")),
+                    ]
+                    .into_iter()
+                    .flatten()
+                    .collect();
+                    if !details.is_empty() {
+                        println!(
+                            "GPU:                 {} ({} {})",
+                            details.join(", "),
+                            gpu.card,
+                            gpu.driver
+                        );
+                    }
+                }
+                if let Some(package) = report.cpu_global.package_power_watts {
+                    let domains: Vec<String> = [
+                        ("core", report.cpu_global.core_power_watts),
+                        ("uncore", report.cpu_global.uncore_power_watts),
+                        ("DRAM", report.cpu_global.dram_power_watts),
+                    ]
+                    .into_iter()
+                    .filter_map(|(domain, watts)| Some(format!("{domain} {:.2} W", watts?)))
+                    .collect();
+                    if domains.is_empty() {
+                        println!("CPU Power:           {package:.2} W");
+                    } else {
+                        println!(
+                            "CPU Power:           {package:.2} W ({})",
+                            domains.join(", ")
+                        );
+                    }
+                }
+                for package in rapl::get_package_power_limits() {
+                    let constraints: Vec<String> = package
+                        .constraints
+                        .iter()
+                        .filter_map(|constraint| {
+                            let limit = constraint.power_limit_w?;
+                            Some(match constraint.time_window_sec {
+                                Some(window) => {
+                                    format!("{} {limit:.1} W ({window:.3} s)", constraint.name)
+                                }
+                                None => format!("{} {limit:.1} W", constraint.name),
+                            })
+                        })
+                        .collect();
+                    println!(
+                        "Power Limits:        {}: {}{}",
+                        package.name,
+                        constraints.join(", "),
+                        if package.enabled { "" } else { " (disabled)" }
+                    );
+                }
+                if let Ok(online) = cpu::get_online_cpus() {
+                    println!(
+                        "Online Cores:        {} ({} of {})",
+                        topology::format_cpu_list(&online),
+                        online.len(),
+                        topology::present_cpus().len().max(online.len())
+                    );
+                }
+                println!(
+                    "Core Topology:       {}",
+                    report.topology.source.map_or_else(
+                        || "Uniform".to_string(),
+                        |source| {
+                            let count = |core_type| {
+                                report
+                                    .cpu_cores
+                                    .iter()
+                                    .filter(|core| core.core_type == Some(core_type))
+                                    .count()
+                            };
+                            format!(
+                                "Hybrid, {} P-cores and {} E-cores (from {source})",
+                                count(CoreType::Performance),
+                                count(CoreType::Efficiency)
+                            )
+                        }
+                    )
+                );
                 println!(
                     "CPU Temperature:     {}",
                     report.cpu_global.average_temperature_celsius.map_or_else(
@@ -181,6 +460,44 @@ fn main() -> Result<(), AppError> {
                     )
                 );
 
+                let idle_states = cpuidle::get_idle_states();
+                if !idle_states.is_empty() {
+                    format_section("CPU Idle States");
+                    println!(
+                        "Idle Governor:       {} (driver {})",
+                        cpuidle::get_cpuidle_governor().as_deref().unwrap_or("N/A"),
+                        cpuidle::get_cpuidle_driver().as_deref().unwrap_or("N/A")
+                    );
+
+                    let max_name_len = idle_states
+                        .iter()
+                        .map(|state| state.name.len())
+                        .max()
+                        .unwrap_or(0);
+                    // Residency is the share of the time spent in any idle state
+                    let total_time_us: u64 = idle_states.iter().map(|state| state.time_us).sum();
+                    for state in &idle_states {
+                        let residency = if total_time_us > 0 {
+                            state.time_us as f64 * 100.0 / total_time_us as f64
+                        } else {
+                            0.0
+                        };
+                        let disabled = match state.disabled_cores {
+                            0 => String::new(),
+                            1 => ", disabled on 1 core".to_string(),
+                            cores => format!(", disabled on {cores} cores"),
+                        };
+                        println!(
+                            "{:<max_name_len$}  {:>6} latency, {residency:>5.1}% residency, {} entries{disabled}",
+                            state.name,
+                            state
+                                .latency_us
+                                .map_or_else(|| "N/A".to_string(), |us| format!("{us} us")),
+                            state.usage
+                        );
+                    }
+                }
+
                 format_section("CPU Core Info");
 
                 // Get max core ID length for padding
@@ -211,7 +528,7 @@ fn main() -> Result<(), AppError> {
                     width = max_core_id_len + 4
                 );
 
-                for core_info in &report.cpu_cores {
+                let print_core = |core_info: &CpuCoreInfo| {
                     // Format frequencies: if current > max, show in a special way
                     let current_freq = match core_info.current_frequency_mhz {
                         Some(freq) => {
@@ -257,6 +574,29 @@ fn main() -> Result<(), AppError> {
                         ),
                         width = max_core_id_len
                     );
+                };
+
+                if report.topology.is_hybrid() {
+                    // Group the cores by type, as their frequency ranges differ
+                    for core_type in [CoreType::Performance, CoreType::Efficiency] {
+                        let cores: Vec<&CpuCoreInfo> = report
+                            .cpu_cores
+                            .iter()
+                            .filter(|core| core.core_type == Some(core_type))
+                            .collect();
+                        if cores.is_empty() {
+                            continue;
+                        }
+
+                        println!("  {core_type}s ({}):", cores.len());
+                        for core_info in cores {
+                            print_core(core_info);
+                        }
+                    }
+                } else {
+                    for core_info in &report.cpu_cores {
+                        print_core(core_info);
+                    }
                 }
 
                 // Only display battery info for systems that have real batteries
@@ -348,21 +688,36 @@ fn main() -> Result<(), AppError> {
             cpu::force_governor(mode).map_err(AppError::Control)
         }
         Some(Commands::SetTurbo { setting }) => cpu::set_turbo(setting).map_err(AppError::Control),
+        Some(Commands::SetSmt { setting }) => cpu::set_smt(setting).map_err(AppError::Control),
+        Some(Commands::SetPstateStatus { status }) => {
+            pstate::set_intel_pstate_status(status).map_err(AppError::Control)
+        }
+        Some(Commands::SetAmdPstateStatus { status }) => {
+            pstate::set_amd_pstate_status(status).map_err(AppError::Control)
+        }
+        Some(Commands::SetMinPerfPct { percent }) => {
+            pstate::set_min_perf_pct(percent).map_err(AppError::Control)
+        }
+        Some(Commands::SetMaxPerfPct { percent }) => {
+            pstate::set_max_perf_pct(percent).map_err(AppError::Control)
+        }
+        Some(Commands::SetHwpDynamicBoost { enabled }) => {
+            pstate::set_hwp_dynamic_boost(enabled).map_err(AppError::Control)
+        }
+        Some(Commands::SetEnergyEfficiency { enabled }) => {
+            pstate::set_energy_efficiency(enabled).map_err(AppError::Control)
+        }
         Some(Commands::SetEpp { epp, core_id }) => {
             cpu::set_epp(&epp, core_id).map_err(AppError::Control)
         }
         Some(Commands::SetEpb { epb, core_id }) => {
             cpu::set_epb(&epb, core_id).map_err(AppError::Control)
         }
-        Some(Commands::SetMinFreq { freq_mhz, core_id }) => {
-            // Basic validation for reasonable CPU frequency values
-            validate_freq(freq_mhz, "Minimum")?;
-            cpu::set_min_frequency(freq_mhz, core_id).map_err(AppError::Control)
+        Some(Commands::SetMinFreq { freq, core_id }) => {
+            cpu::set_min_frequency(freq, core_id).map_err(AppError::Control)
         }
-        Some(Commands::SetMaxFreq { freq_mhz, core_id }) => {
-            // Basic validation for reasonable CPU frequency values
-            validate_freq(freq_mhz, "Maximum")?;
-            cpu::set_max_frequency(freq_mhz, core_id).map_err(AppError::Control)
+        Some(Commands::SetMaxFreq { freq, core_id }) => {
+            cpu::set_max_frequency(freq, core_id).map_err(AppError::Control)
         }
         Some(Commands::SetPlatformProfile { profile }) => {
             // Get available platform profiles and validate early if possible
@@ -412,6 +767,20 @@ fn main() -> Result<(), AppError> {
         }
         Some(Commands::Daemon { verbose }) => daemon::run_daemon(config, verbose),
         Some(Commands::Debug) => cli::debug::run_debug(&config),
+        Some(Commands::Explain) => cli::explain::run_explain(&config),
+        Some(Commands::Profile { command }) => match command {
+            ProfileCommands::List => cli::profile::run_list(&config),
+            ProfileCommands::Set { name } => cli::profile::run_set(&config, &name),
+            ProfileCommands::Reset => cli::profile::run_reset(&config),
+        },
+        Some(Commands::Config { command }) => match command {
+            ConfigCommands::Check { path } => {
+                cli::config::run_check(path.as_deref(), cli.config.as_deref(), cli.no_config)
+            }
+            ConfigCommands::Dump { format, defaults } => {
+                cli::config::run_dump(&config, format, defaults)
+            }
+        },
         None => {
             info!("Welcome to Watt! Use --help for commands.");
             debug!("Current effective configuration: {config:?}");
@@ -456,21 +825,3 @@ fn init_logger() {
         debug!("Logger initialized with RUST_LOG={env_log}");
     });
 }
-
-/// Validate CPU frequency input values
-fn validate_freq(freq_mhz: u32, label: &str) -> Result<(), AppError> {
-    if freq_mhz == 0 {
-        error!("{label} frequency cannot be zero");
-        Err(AppError::Generic(format!(
-            "{label} frequency cannot be zero"
-        )))
-    } else if freq_mhz > 10000 {
-        // Extremely high value unlikely to be valid
-        error!("{label} frequency ({freq_mhz} MHz) is unreasonably high");
-        Err(AppError::Generic(format!(
-            "{label} frequency ({freq_mhz} MHz) is unreasonably high"
-        )))
-    } else {
-        Ok(())
-    }
-}
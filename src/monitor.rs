@@ -1,6 +1,12 @@
 use crate::config::AppConfig;
-use crate::core::{BatteryInfo, CpuCoreInfo, CpuGlobalInfo, SystemInfo, SystemLoad, SystemReport};
-use crate::cpu::get_logical_core_count;
+use crate::core::{
+    BatteryInfo, CpuCoreInfo, CpuGlobalInfo, CpufreqPolicyInfo, SystemInfo, SystemLoad,
+    SystemReport,
+};
+use crate::cpu::{self, get_online_cpus};
+use crate::gpu;
+use crate::rapl::{self, CpuPower};
+use crate::topology::{self, CpuTopology};
 use crate::util::error::SysMonitorError;
 use log::debug;
 use std::{
@@ -155,6 +161,7 @@ fn read_all_cpu_times() -> Result<HashMap<u32, CpuTimes>> {
 
 pub fn get_cpu_core_info(
     core_id: u32,
+    topology: &CpuTopology,
     prev_times: &CpuTimes,
     current_times: &CpuTimes,
 ) -> Result<CpuCoreInfo> {
@@ -170,6 +177,13 @@ pub fn get_cpu_core_info(
         .map(|khz| khz / 1000)
         .ok();
 
+    // Sensors are labelled with the physical core, which differs from the
+    // logical CPU ID with SMT and when cores are offline
+    let physical_core_id = read_sysfs_value::<u32>(format!(
+        "/sys/devices/system/cpu/cpu{core_id}/topology/core_id"
+    ))
+    .unwrap_or(core_id);
+
     // Temperature detection.
     // Should be generic enough to be able to support for multiple hardware sensors
     // with the possibility of extending later down the road.
@@ -184,7 +198,8 @@ pub fn get_cpu_core_info(
             if let Ok(name) = read_sysfs_file_trimmed(hw_path.join("name")) {
                 // Intel CPU temperature driver
                 if name == "coretemp" {
-                    if let Some(temp) = get_temperature_for_core(&hw_path, core_id, "Core") {
+                    if let Some(temp) = get_temperature_for_core(&hw_path, physical_core_id, "Core")
+                    {
                         temperature_celsius = Some(temp);
                         break;
                     }
@@ -194,7 +209,8 @@ pub fn get_cpu_core_info(
                 else if name == "k10temp" || name == "zenpower" || name == "amdgpu" {
                     // AMD's k10temp doesn't always label cores individually
                     // First try to find core-specific temps
-                    if let Some(temp) = get_temperature_for_core(&hw_path, core_id, "Tdie") {
+                    if let Some(temp) = get_temperature_for_core(&hw_path, physical_core_id, "Tdie")
+                    {
                         temperature_celsius = Some(temp);
                         break;
                     }
@@ -220,7 +236,8 @@ pub fn get_cpu_core_info(
                 // Other CPU temperature drivers
                 else if name.contains("cpu") || name.contains("temp") {
                     // Try to find a label that matches this core
-                    if let Some(temp) = get_temperature_for_core(&hw_path, core_id, "Core") {
+                    if let Some(temp) = get_temperature_for_core(&hw_path, physical_core_id, "Core")
+                    {
                         temperature_celsius = Some(temp);
                         break;
                     }
@@ -281,6 +298,10 @@ pub fn get_cpu_core_info(
 
     Ok(CpuCoreInfo {
         core_id,
+        core_type: topology
+            .is_hybrid()
+            .then(|| topology.core_type(core_id))
+            .flatten(),
         current_frequency_mhz,
         min_frequency_mhz,
         max_frequency_mhz,
@@ -359,22 +380,23 @@ fn get_fallback_temperature(hw_path: &Path) -> Option<f32> {
     None
 }
 
-pub fn get_all_cpu_core_info() -> Result<Vec<CpuCoreInfo>> {
+pub fn get_all_cpu_core_info(topology: &CpuTopology) -> Result<Vec<CpuCoreInfo>> {
     let initial_cpu_times = read_all_cpu_times()?;
     thread::sleep(Duration::from_millis(250)); // interval for CPU usage calculation
     let final_cpu_times = read_all_cpu_times()?;
 
-    let num_cores = get_logical_core_count()
-        .map_err(|_| SysMonitorError::ReadError("Could not get the number of cores".to_string()))?;
+    let online_cpus = get_online_cpus()
+        .map_err(|_| SysMonitorError::ReadError("Could not get the online cores".to_string()))?;
 
-    let mut core_infos = Vec::with_capacity(num_cores as usize);
+    let mut core_infos = Vec::with_capacity(online_cpus.len());
 
-    for core_id in 0..num_cores {
+    // Cores may be parked or unparked between the two samples
+    for core_id in online_cpus {
         if let (Some(prev), Some(curr)) = (
             initial_cpu_times.get(&core_id),
             final_cpu_times.get(&core_id),
         ) {
-            match get_cpu_core_info(core_id, prev, curr) {
+            match get_cpu_core_info(core_id, topology, prev, curr) {
                 Ok(info) => core_infos.push(info),
                 Err(e) => {
                     // Log or handle error for a single core, maybe push a partial info or skip
@@ -382,25 +404,35 @@ pub fn get_all_cpu_core_info() -> Result<Vec<CpuCoreInfo>> {
                 }
             }
         } else {
-            // Log or handle missing times for a core
-            eprintln!("Missing CPU time data for core {core_id}");
+            // The core was parked or unparked while sampling
+            debug!("Missing CPU time data for core {core_id}");
         }
     }
     Ok(core_infos)
 }
 
-pub fn get_cpu_global_info(cpu_cores: &[CpuCoreInfo]) -> CpuGlobalInfo {
-    // Find a valid CPU to read global settings from
-    // Try cpu0 first, then fall back to any available CPU with cpufreq
-    let mut cpufreq_base_path_buf = PathBuf::from("/sys/devices/system/cpu/cpu0/cpufreq/");
+pub fn get_cpu_global_info(cpu_cores: &[CpuCoreInfo], cpu_power: CpuPower) -> CpuGlobalInfo {
+    let policies = get_policy_info();
+
+    // Read global settings from the first policy with online CPUs. Without
+    // policies, try cpu0 first, then fall back to any available CPU with cpufreq
+    let mut cpufreq_base_path_buf = policies.first().map_or_else(
+        || PathBuf::from("/sys/devices/system/cpu/cpu0/cpufreq/"),
+        |policy| {
+            PathBuf::from(format!(
+                "/sys/devices/system/cpu/cpufreq/policy{}/",
+                policy.id
+            ))
+        },
+    );
 
     if !cpufreq_base_path_buf.exists() {
-        let core_count = get_logical_core_count().unwrap_or_else(|e| {
+        let online_cpus = get_online_cpus().unwrap_or_else(|e| {
             eprintln!("Warning: {e}");
-            0
+            Vec::new()
         });
 
-        for i in 0..core_count {
+        for i in online_cpus {
             let test_path = PathBuf::from(format!("/sys/devices/system/cpu/cpu{i}/cpufreq/"));
             if test_path.exists() {
                 cpufreq_base_path_buf = test_path;
@@ -447,12 +479,19 @@ pub fn get_cpu_global_info(cpu_cores: &[CpuCoreInfo]) -> CpuGlobalInfo {
     let energy_perf_pref =
         read_sysfs_file_trimmed(cpufreq_base_path_buf.join("energy_performance_preference")).ok();
 
-    // EPB (Energy Performance Bias)
-    let energy_perf_bias =
-        read_sysfs_file_trimmed(cpufreq_base_path_buf.join("energy_performance_bias")).ok();
+    // EPB (Energy Performance Bias), a per-CPU setting outside cpufreq
+    let energy_perf_bias = get_online_cpus()
+        .ok()
+        .and_then(|cpus| cpus.first().copied())
+        .and_then(|id| read_sysfs_file_trimmed(cpu::epb_path(id)).ok());
 
     let platform_profile = read_sysfs_file_trimmed("/sys/firmware/acpi/platform_profile").ok();
 
+    let smt_control = read_sysfs_file_trimmed("/sys/devices/system/cpu/smt/control").ok();
+    let smt_active = read_sysfs_value::<u8>("/sys/devices/system/cpu/smt/active")
+        .map(|active| active == 1)
+        .ok();
+
     // Calculate average CPU temperature from the core temperatures
     let average_temperature_celsius = if cpu_cores.is_empty() {
         None
@@ -483,10 +522,46 @@ pub fn get_cpu_global_info(cpu_cores: &[CpuCoreInfo]) -> CpuGlobalInfo {
         epp: energy_perf_pref,
         epb: energy_perf_bias,
         platform_profile,
+        smt_control,
+        smt_active,
+        package_power_watts: cpu_power.package_watts,
+        core_power_watts: cpu_power.core_watts,
+        uncore_power_watts: cpu_power.uncore_watts,
+        dram_power_watts: cpu_power.dram_watts,
         average_temperature_celsius,
+        policies,
     }
 }
 
+/// The state of every cpufreq policy with online CPUs
+fn get_policy_info() -> Vec<CpufreqPolicyInfo> {
+    cpu::get_policies()
+        .into_iter()
+        .filter(|policy| !policy.affected_cpus.is_empty())
+        .map(|policy| {
+            let path = PathBuf::from(format!(
+                "/sys/devices/system/cpu/cpufreq/policy{}",
+                policy.id
+            ));
+            let khz_to_mhz = |file: &str| {
+                read_sysfs_value::<u32>(path.join(file))
+                    .ok()
+                    .map(|khz| khz / 1000)
+            };
+
+            CpufreqPolicyInfo {
+                id: policy.id,
+                cpus: policy.affected_cpus,
+                driver: read_sysfs_file_trimmed(path.join("scaling_driver")).ok(),
+                governor: read_sysfs_file_trimmed(path.join("scaling_governor")).ok(),
+                epp: read_sysfs_file_trimmed(path.join("energy_performance_preference")).ok(),
+                min_freq_mhz: khz_to_mhz("scaling_min_freq"),
+                max_freq_mhz: khz_to_mhz("scaling_max_freq"),
+            }
+        })
+        .collect()
+}
+
 pub fn get_battery_info(config: &AppConfig) -> Result<Vec<BatteryInfo>> {
     let mut batteries = Vec::new();
     let power_supply_path = Path::new("/sys/class/power_supply");
@@ -710,8 +785,12 @@ pub fn get_system_load() -> Result<SystemLoad> {
 
 pub fn collect_system_report(config: &AppConfig) -> Result<SystemReport> {
     let system_info = get_system_info();
-    let cpu_cores = get_all_cpu_core_info()?;
-    let cpu_global = get_cpu_global_info(&cpu_cores);
+    let topology = topology::detect();
+    // The energy counters are read around the CPU usage sampling interval
+    let energy_before = rapl::sample_energy();
+    let cpu_cores = get_all_cpu_core_info(&topology)?;
+    let cpu_power = rapl::sample_energy().power_since(&energy_before);
+    let cpu_global = get_cpu_global_info(&cpu_cores, cpu_power);
     let batteries = get_battery_info(config)?;
     let system_load = get_system_load()?;
 
@@ -719,6 +798,8 @@ pub fn collect_system_report(config: &AppConfig) -> Result<SystemReport> {
         system_info,
         cpu_cores,
         cpu_global,
+        topology,
+        gpus: gpu::get_gpu_info(),
         batteries,
         system_load,
         timestamp: SystemTime::now(),
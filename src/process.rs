@@ -0,0 +1,366 @@
+// Profile triggers based on running processes
+use crate::config::ProcessTriggerConfig;
+use log::info;
+use std::collections::HashMap;
+use std::fs;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Time between the two scans of a one-off evaluation, so that the CPU usage
+/// of matching processes is known
+const BASELINE_INTERVAL: Duration = Duration::from_millis(250);
+
+/// A running process matched by a trigger
+#[derive(Debug, Clone)]
+pub struct MatchedProcess {
+    pub pid: u32,
+    /// The command or executable name that matched
+    pub name: String,
+    /// CPU usage since the previous scan, where 100 is one fully busy CPU
+    pub cpu_percent: Option<f32>,
+}
+
+/// State of a process trigger after a scan
+#[derive(Debug, Clone, Default)]
+pub struct TriggerStatus {
+    pub processes: Vec<MatchedProcess>,
+    /// Combined CPU usage of the matching processes, where 100 is one fully
+    /// busy CPU. Unknown on the first scan.
+    pub cpu_percent: Option<f32>,
+    /// Whether the trigger matches right now
+    pub matches: bool,
+    /// Whether the trigger applies, which includes its hold time
+    pub active: bool,
+}
+
+impl ProcessTriggerConfig {
+    /// Whether any pattern matches one of the names of a process
+    pub fn matches_name(&self, name: &str) -> bool {
+        self.processes
+            .iter()
+            .any(|pattern| wildcard_match(pattern, name))
+    }
+}
+
+/// Match `text` against a pattern in which `*` matches any run of characters
+/// and `?` matches a single character
+fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // Position of the last `*` and the text position it was tried at
+    let mut star: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                // Let the last `*` swallow one more character
+                Some((star_p, star_t)) => {
+                    p = star_p + 1;
+                    t = star_t + 1;
+                    star = Some((star_p, star_t + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// CPU accounting of a process at a scan
+#[derive(Debug, Clone, Copy)]
+struct ProcessTicks {
+    /// Start time, to tell a reused PID apart from the process seen before
+    start_time: u64,
+    /// User and system time in clock ticks
+    cpu_ticks: u64,
+}
+
+/// Scans `/proc` for processes matching the configured triggers. Only the
+/// names of every process are read; `stat` is read for matching ones alone.
+pub struct ProcessWatcher {
+    /// CPU accounting of matching processes at the previous scan
+    previous: HashMap<u32, ProcessTicks>,
+    /// Total CPU time across all CPUs at the previous scan
+    previous_total: Option<u64>,
+    /// When each trigger last matched, for its hold time
+    last_matched: Vec<Option<Instant>>,
+    /// Whether each trigger was active after the previous scan
+    active: Vec<bool>,
+    daemon: bool,
+}
+
+impl ProcessWatcher {
+    /// A watcher for the daemon, which measures CPU usage between cycles and
+    /// honours hold times
+    pub fn new() -> Self {
+        Self {
+            previous: HashMap::new(),
+            previous_total: None,
+            last_matched: Vec::new(),
+            active: Vec::new(),
+            daemon: true,
+        }
+    }
+
+    /// A watcher for a one-off evaluation, which takes a short baseline sample
+    /// when a trigger depends on CPU usage
+    pub fn immediate() -> Self {
+        Self {
+            daemon: false,
+            ..Self::new()
+        }
+    }
+
+    /// Scan the running processes and return the status of every trigger
+    pub fn scan(&mut self, triggers: &[ProcessTriggerConfig]) -> Vec<TriggerStatus> {
+        if triggers.is_empty() {
+            return Vec::new();
+        }
+
+        let needs_baseline = triggers.iter().any(|trigger| trigger.cpu_above.is_some());
+        if !self.daemon && self.previous_total.is_none() && needs_baseline {
+            self.scan_processes(triggers);
+            thread::sleep(BASELINE_INTERVAL);
+        }
+
+        let mut statuses = self.scan_processes(triggers);
+        self.update_active(triggers, &mut statuses, Instant::now());
+        statuses
+    }
+
+    fn scan_processes(&mut self, triggers: &[ProcessTriggerConfig]) -> Vec<TriggerStatus> {
+        let mut statuses = vec![TriggerStatus::default(); triggers.len()];
+        let (total, cpus) = read_total_ticks().unzip();
+        let cpus = cpus.unwrap_or(1);
+        // CPU time that passed on a single CPU, so that 100% is one busy CPU
+        let elapsed = total
+            .zip(self.previous_total)
+            .map(|(total, previous)| total.saturating_sub(previous))
+            .filter(|&elapsed| elapsed > 0)
+            .map(|elapsed| elapsed as f32 / cpus as f32);
+        let mut current = HashMap::new();
+
+        let Ok(entries) = fs::read_dir("/proc") else {
+            return statuses;
+        };
+        for entry in entries.flatten() {
+            let Some(pid) = entry.file_name().to_str().and_then(|s| s.parse().ok()) else {
+                continue;
+            };
+            // Processes may exit at any point during the scan
+            let Some(matches) = match_process(pid, triggers) else {
+                continue;
+            };
+            let Some(ticks) = read_process_ticks(pid) else {
+                continue;
+            };
+
+            let cpu_percent = elapsed.map(|elapsed| {
+                let used = match self.previous.get(&pid) {
+                    Some(previous) if previous.start_time == ticks.start_time => {
+                        ticks.cpu_ticks.saturating_sub(previous.cpu_ticks)
+                    }
+                    // Started since the previous scan
+                    _ => ticks.cpu_ticks,
+                };
+                (used as f32 / elapsed * 100.0).min(100.0 * cpus as f32)
+            });
+            current.insert(pid, ticks);
+
+            for (index, name) in matches {
+                statuses[index].processes.push(MatchedProcess {
+                    pid,
+                    name,
+                    cpu_percent,
+                });
+            }
+        }
+
+        for (trigger, status) in triggers.iter().zip(&mut statuses) {
+            status.cpu_percent = elapsed.map(|_| {
+                status
+                    .processes
+                    .iter()
+                    .filter_map(|process| process.cpu_percent)
+                    .sum()
+            });
+            status.matches = !status.processes.is_empty()
+                && trigger.cpu_above.is_none_or(|threshold| {
+                    status.cpu_percent.is_some_and(|usage| usage > threshold)
+                });
+        }
+
+        self.previous = current;
+        self.previous_total = total;
+        statuses
+    }
+
+    /// Keep triggers active for their hold time after they stop matching
+    fn update_active(
+        &mut self,
+        triggers: &[ProcessTriggerConfig],
+        statuses: &mut [TriggerStatus],
+        now: Instant,
+    ) {
+        self.last_matched.resize(triggers.len(), None);
+        self.active.resize(triggers.len(), false);
+
+        for (index, (trigger, status)) in triggers.iter().zip(statuses).enumerate() {
+            if status.matches {
+                self.last_matched[index] = Some(now);
+            }
+            status.active = status.matches
+                || (self.daemon
+                    && self.last_matched[index].is_some_and(|last| {
+                        now.duration_since(last) < Duration::from_secs(trigger.hold_sec)
+                    }));
+
+            if self.daemon && status.active != self.active[index] {
+                let name = trigger.name.as_deref().unwrap_or_default();
+                if status.active {
+                    let processes: Vec<String> = status
+                        .processes
+                        .iter()
+                        .map(|process| format!("{} ({})", process.name, process.pid))
+                        .collect();
+                    info!("Process trigger '{name}' matched: {}", processes.join(", "));
+                } else {
+                    info!("Process trigger '{name}' no longer matches");
+                }
+            }
+            self.active[index] = status.active;
+        }
+    }
+}
+
+/// Longest command name the kernel keeps in `comm`, longer names are truncated
+const COMM_MAX_LEN: usize = 15;
+
+/// Indices of the triggers matching a process, with the name that matched.
+/// The command name is checked first. The executable name from the command
+/// line is only read when the command name may have been truncated, so most
+/// processes cost a single read.
+fn match_process(pid: u32, triggers: &[ProcessTriggerConfig]) -> Option<Vec<(usize, String)>> {
+    let comm = fs::read_to_string(format!("/proc/{pid}/comm")).ok()?;
+    let comm = comm.trim_end_matches('\n');
+    let truncated = comm.len() >= COMM_MAX_LEN;
+    let mut executable: Option<Option<String>> = None;
+    let mut matches = Vec::new();
+
+    for (index, trigger) in triggers.iter().enumerate() {
+        if trigger.matches_name(comm) {
+            matches.push((index, comm.to_string()));
+            continue;
+        }
+        if !truncated {
+            continue;
+        }
+
+        let executable = executable.get_or_insert_with(|| read_executable_name(pid));
+        if let Some(executable) = executable {
+            if trigger.matches_name(executable) {
+                matches.push((index, executable.clone()));
+            }
+        }
+    }
+
+    (!matches.is_empty()).then_some(matches)
+}
+
+/// File name of the first command line argument. Both separators are
+/// handled so Windows paths of Wine and Proton processes are matched too.
+fn read_executable_name(pid: u32) -> Option<String> {
+    let cmdline = fs::read(format!("/proc/{pid}/cmdline")).ok()?;
+    let argv0 = cmdline.split(|&b| b == 0).next()?;
+    let argv0 = String::from_utf8_lossy(argv0);
+    let name = argv0.rsplit(['/', '\\']).next()?;
+    (!name.is_empty()).then(|| name.to_string())
+}
+
+fn read_process_ticks(pid: u32) -> Option<ProcessTicks> {
+    let stat = fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+    // The command name may contain spaces and parentheses, so split after the last `)`
+    let fields: Vec<&str> = stat[stat.rfind(')')? + 1..].split_whitespace().collect();
+    // Fields are counted from `state`, the third field of the file
+    let utime: u64 = fields.get(11)?.parse().ok()?;
+    let stime: u64 = fields.get(12)?.parse().ok()?;
+    let start_time = fields.get(19)?.parse().ok()?;
+
+    Some(ProcessTicks {
+        start_time,
+        cpu_ticks: utime + stime,
+    })
+}
+
+/// Total CPU time across all CPUs from the aggregate line of `/proc/stat`,
+/// with the number of online CPUs it covers
+fn read_total_ticks() -> Option<(u64, usize)> {
+    let stat = fs::read_to_string("/proc/stat").ok()?;
+    let line = stat.lines().find(|line| line.starts_with("cpu "))?;
+    // user through steal, guest time is already included in user time
+    let total = line
+        .split_whitespace()
+        .skip(1)
+        .take(8)
+        .map(|value| value.parse::<u64>().ok())
+        .sum::<Option<u64>>()?;
+    let cpus = stat
+        .lines()
+        .filter(|line| {
+            line.strip_prefix("cpu")
+                .is_some_and(|rest| rest.starts_with(|c: char| c.is_ascii_digit()))
+        })
+        .count();
+
+    Some((total, cpus.max(1)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn literal_patterns_match_the_whole_name() {
+        assert!(wildcard_match("cargo", "cargo"));
+        assert!(!wildcard_match("cargo", "cargo-clippy"));
+        assert!(!wildcard_match("cargo", "carg"));
+        assert!(!wildcard_match("cargo", ""));
+    }
+
+    #[test]
+    fn star_matches_any_run_of_characters() {
+        assert!(wildcard_match("*.exe", "Game.exe"));
+        assert!(wildcard_match("*.exe", ".exe"));
+        assert!(!wildcard_match("*.exe", "Game.exe.bak"));
+        assert!(wildcard_match("steam*", "steamwebhelper"));
+        assert!(wildcard_match("*", ""));
+        assert!(wildcard_match("**", "anything"));
+        // Needs backtracking past the first `b`
+        assert!(wildcard_match("a*b*c", "abxbyc"));
+        assert!(!wildcard_match("a*b*c", "abxbyd"));
+    }
+
+    #[test]
+    fn question_mark_matches_one_character() {
+        assert!(wildcard_match("ld.?", "ld.s"));
+        assert!(wildcard_match("python3.??", "python3.12"));
+        assert!(!wildcard_match("python3.??", "python3.9"));
+        assert!(!wildcard_match("?", ""));
+    }
+
+    #[test]
+    fn non_ascii_names_are_matched_by_character() {
+        assert!(wildcard_match("caf?", "café"));
+        assert!(wildcard_match("*é", "café"));
+    }
+}
@@ -0,0 +1,392 @@
+// Profile selection and the persistent profile override
+use crate::config::{
+    AppConfig, BatteryTierConfig, ProcessTriggerConfig, ProfileConfig, ScheduleConfig,
+};
+use crate::core::SystemReport;
+use crate::process::{ProcessWatcher, TriggerStatus};
+use crate::rules::{RuleEngine, RuleInputs};
+use crate::schedule;
+use crate::util::error::ControlError;
+use jiff::Zoned;
+use log::{info, warn};
+use std::fmt;
+use std::time::Instant;
+use std::{fs, io, path::Path};
+
+pub type Result<T, E = ControlError> = std::result::Result<T, E>;
+
+/// Path for storing the profile override state, which persists across reboots.
+/// The daemon reads it every cycle.
+const PROFILE_OVERRIDE_PATH: &str = "/var/lib/watt/profile_override";
+
+/// Why a profile was selected
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SelectionReason {
+    /// Selected with `watt profile set`
+    Override,
+    /// Selected by a `[[rules]]` entry
+    Rule { name: String },
+    /// Selected by a `[[process_triggers]]` entry
+    Process { name: String },
+    /// Selected by a `[[schedules]]` entry
+    Schedule { name: String },
+    /// Default mapping of the power source to `charger` or `battery`
+    PowerSource { on_ac: bool },
+}
+
+impl fmt::Display for SelectionReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Override => write!(f, "profile override"),
+            Self::Rule { name } => write!(f, "rule '{name}'"),
+            Self::Process { name } => write!(f, "process trigger '{name}'"),
+            Self::Schedule { name } => write!(f, "schedule '{name}'"),
+            Self::PowerSource { on_ac: true } => write!(f, "on AC power"),
+            Self::PowerSource { on_ac: false } => write!(f, "on battery power"),
+        }
+    }
+}
+
+/// The profile chosen for the current system state
+pub struct ProfileSelection {
+    pub name: String,
+    /// Settings to apply, including those of active battery tiers
+    pub profile: ProfileConfig,
+    pub reason: SelectionReason,
+    /// Name of the active schedule, whose settings are applied on top of the profile
+    pub schedule: Option<String>,
+    /// Name of the active process trigger, whose settings are applied on top
+    /// of the profile and the schedule
+    pub process_trigger: Option<String>,
+    /// Status of every configured process trigger, in configuration order
+    pub processes: Vec<TriggerStatus>,
+    /// Thresholds of the active battery tiers, highest first
+    pub battery_tiers: Vec<u8>,
+}
+
+/// Whether the system is running on AC power.
+/// Desktops and servers without batteries are always considered to be on AC.
+pub fn is_on_ac_power(report: &SystemReport) -> bool {
+    report.batteries.is_empty() || report.batteries.iter().all(|b| b.ac_connected)
+}
+
+/// Average capacity across all batteries that report one
+pub fn battery_capacity(report: &SystemReport) -> Option<u8> {
+    let capacities: Vec<u32> = report
+        .batteries
+        .iter()
+        .filter_map(|b| b.capacity_percent.map(u32::from))
+        .collect();
+
+    if capacities.is_empty() {
+        return None;
+    }
+    let average = capacities.iter().sum::<u32>() / capacities.len() as u32;
+    u8::try_from(average).ok()
+}
+
+/// Chooses the profile to apply, in order of precedence: a profile override,
+/// the first matching rule, the first active process trigger, the active
+/// schedule, and finally `charger` on AC power and `battery` on battery power.
+/// Unless a profile override is set, the settings of the active schedule, the
+/// active process trigger and then active battery tiers (on battery power) are
+/// layered on top of the chosen profile.
+pub struct ProfileSelector {
+    rules: RuleEngine,
+    processes: ProcessWatcher,
+    tiers: BatteryTiers,
+}
+
+impl ProfileSelector {
+    /// A selector for the daemon, which keeps rule debounce, process and
+    /// battery tier state between cycles
+    pub fn new() -> Self {
+        Self {
+            rules: RuleEngine::new(),
+            processes: ProcessWatcher::new(),
+            tiers: BatteryTiers::new(true),
+        }
+    }
+
+    /// A selector for a one-off selection, in which rules apply without debounce
+    pub fn immediate() -> Self {
+        Self {
+            rules: RuleEngine::immediate(),
+            processes: ProcessWatcher::immediate(),
+            tiers: BatteryTiers::new(false),
+        }
+    }
+
+    pub fn select(&mut self, config: &AppConfig, inputs: &RuleInputs) -> ProfileSelection {
+        let schedule = schedule::active_schedule(config, &Zoned::now());
+        let processes = self.processes.scan(&config.process_triggers);
+        let trigger = config
+            .process_triggers
+            .iter()
+            .zip(&processes)
+            .find(|(_, status)| status.active)
+            .map(|(trigger, _)| trigger);
+
+        let (name, profile, reason) = self.select_base(config, inputs, trigger, schedule);
+        let mut selection = ProfileSelection {
+            name,
+            profile: profile.clone(),
+            reason,
+            schedule: None,
+            process_trigger: None,
+            processes,
+            battery_tiers: Vec::new(),
+        };
+        let overridden = selection.reason == SelectionReason::Override;
+
+        if let Some(schedule) = schedule.filter(|_| !overridden) {
+            selection.profile.apply_overrides(&schedule.settings);
+            selection.schedule = schedule.name.clone();
+        }
+
+        if let Some(trigger) = trigger.filter(|_| !overridden) {
+            selection.profile.apply_overrides(&trigger.settings);
+            selection.process_trigger = trigger.name.clone();
+        }
+
+        let capacity = if inputs.on_ac || overridden {
+            None
+        } else {
+            inputs.battery_percent
+        };
+        for tier in self.tiers.update(&config.battery_tiers, capacity) {
+            selection.profile.apply_overrides(&tier.settings);
+            selection.battery_tiers.push(tier.below);
+        }
+
+        selection
+    }
+
+    fn select_base<'a>(
+        &mut self,
+        config: &'a AppConfig,
+        inputs: &RuleInputs,
+        trigger: Option<&ProcessTriggerConfig>,
+        schedule: Option<&ScheduleConfig>,
+    ) -> (String, &'a ProfileConfig, SelectionReason) {
+        // Keep evaluating rules under an override so their debounce state stays current
+        let rule = self.rules.evaluate(&config.rules, inputs, Instant::now());
+
+        if let Some(name) = get_profile_override() {
+            match config.profile(&name) {
+                Some(profile) => return (name, profile, SelectionReason::Override),
+                None => warn!(
+                    "Profile override '{name}' does not exist in the configuration. Ignoring it."
+                ),
+            }
+        }
+
+        if let Some(rule) = rule {
+            // Rules are checked against the configured profiles when loading
+            if let Some(profile) = config.profile(&rule.profile) {
+                let reason = SelectionReason::Rule {
+                    name: rule.name.clone().unwrap_or_default(),
+                };
+                return (rule.profile.clone(), profile, reason);
+            }
+        }
+
+        if let Some(trigger) = trigger {
+            if let Some(name) = &trigger.profile {
+                // Process triggers are checked against the configured profiles when loading
+                if let Some(profile) = config.profile(name) {
+                    let reason = SelectionReason::Process {
+                        name: trigger.name.clone().unwrap_or_default(),
+                    };
+                    return (name.clone(), profile, reason);
+                }
+            }
+        }
+
+        if let Some(schedule) = schedule {
+            if let Some(name) = &schedule.profile {
+                // Schedules are checked against the configured profiles when loading
+                if let Some(profile) = config.profile(name) {
+                    let reason = SelectionReason::Schedule {
+                        name: schedule.name.clone().unwrap_or_default(),
+                    };
+                    return (name.clone(), profile, reason);
+                }
+            }
+        }
+
+        let on_ac = inputs.on_ac;
+        let (name, profile) = if on_ac {
+            ("charger", &config.charger)
+        } else {
+            ("battery", &config.battery)
+        };
+        (
+            name.to_string(),
+            profile,
+            SelectionReason::PowerSource { on_ac },
+        )
+    }
+}
+
+/// Tracks which battery tiers are active. A tier becomes active when the
+/// capacity drops below its threshold, and is only left once the capacity
+/// has risen to the threshold plus its hysteresis.
+struct BatteryTiers {
+    active: Vec<bool>,
+    log_changes: bool,
+}
+
+impl BatteryTiers {
+    const fn new(log_changes: bool) -> Self {
+        Self {
+            active: Vec::new(),
+            log_changes,
+        }
+    }
+
+    /// Update the tiers for the current capacity, `None` leaving every tier,
+    /// and return the active ones
+    fn update<'a>(
+        &mut self,
+        tiers: &'a [BatteryTierConfig],
+        capacity: Option<u8>,
+    ) -> impl Iterator<Item = &'a BatteryTierConfig> {
+        self.active.resize(tiers.len(), false);
+
+        for (tier, active) in tiers.iter().zip(&mut self.active) {
+            let now_active = capacity.is_some_and(|capacity| {
+                if *active {
+                    u16::from(capacity) < u16::from(tier.below) + u16::from(tier.hysteresis)
+                } else {
+                    capacity < tier.below
+                }
+            });
+
+            if self.log_changes && now_active != *active {
+                if now_active {
+                    info!("Battery below {}%, entering battery tier", tier.below);
+                } else {
+                    info!("Leaving battery tier below {}%", tier.below);
+                }
+            }
+            *active = now_active;
+        }
+
+        tiers
+            .iter()
+            .zip(self.active.clone())
+            .filter_map(|(tier, active)| active.then_some(tier))
+    }
+}
+
+/// Choose the profile to apply for a single system report, without debounce
+pub fn select_profile(report: &SystemReport, config: &AppConfig) -> ProfileSelection {
+    ProfileSelector::immediate().select(config, &RuleInputs::from_report(report))
+}
+
+/// Persistently select a profile, overriding the automatic selection
+pub fn set_profile_override(config: &AppConfig, name: &str) -> Result<()> {
+    if config.profile(name).is_none() {
+        return Err(ControlError::InvalidValueError(format!(
+            "Unknown profile '{name}'. Available profiles: {}",
+            config.profile_names().collect::<Vec<_>>().join(", ")
+        )));
+    }
+
+    if let Some(dir_path) = Path::new(PROFILE_OVERRIDE_PATH).parent() {
+        fs::create_dir_all(dir_path)
+            .map_err(|e| override_io_error(e, "creating directory", PROFILE_OVERRIDE_PATH))?;
+    }
+    fs::write(PROFILE_OVERRIDE_PATH, name)
+        .map_err(|e| override_io_error(e, "writing", PROFILE_OVERRIDE_PATH))?;
+    Ok(())
+}
+
+/// Remove the profile override, returning whether one was set
+pub fn clear_profile_override() -> Result<bool> {
+    let was_set = get_profile_override().is_some();
+    if Path::new(PROFILE_OVERRIDE_PATH).exists() {
+        fs::remove_file(PROFILE_OVERRIDE_PATH)
+            .map_err(|e| override_io_error(e, "removing", PROFILE_OVERRIDE_PATH))?;
+    }
+    Ok(was_set)
+}
+
+/// Get the current profile override if set
+pub fn get_profile_override() -> Option<String> {
+    let name = fs::read_to_string(PROFILE_OVERRIDE_PATH).ok()?;
+    let name = name.trim();
+    (!name.is_empty()).then(|| name.to_string())
+}
+
+fn override_io_error(e: io::Error, action: &str, path: &str) -> ControlError {
+    if e.kind() == io::ErrorKind::PermissionDenied {
+        ControlError::PermissionDenied(format!("Permission denied {action} override file: {path}"))
+    } else {
+        ControlError::Io(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ProfileOverrides;
+
+    fn tier(below: u8, hysteresis: u8) -> BatteryTierConfig {
+        BatteryTierConfig {
+            below,
+            hysteresis,
+            settings: ProfileOverrides::default(),
+        }
+    }
+
+    fn active(tiers: &mut BatteryTiers, config: &[BatteryTierConfig], capacity: u8) -> Vec<u8> {
+        tiers
+            .update(config, Some(capacity))
+            .map(|tier| tier.below)
+            .collect()
+    }
+
+    #[test]
+    fn tier_is_entered_below_its_threshold() {
+        let config = [tier(30, 5), tier(15, 5)];
+        let mut tiers = BatteryTiers::new(false);
+
+        assert!(active(&mut tiers, &config, 30).is_empty());
+        assert_eq!(active(&mut tiers, &config, 29), [30]);
+        assert_eq!(active(&mut tiers, &config, 14), [30, 15]);
+    }
+
+    #[test]
+    fn tier_is_left_only_after_the_hysteresis() {
+        let config = [tier(30, 5)];
+        let mut tiers = BatteryTiers::new(false);
+
+        assert_eq!(active(&mut tiers, &config, 29), [30]);
+        // Charging back above the threshold keeps the tier within the hysteresis
+        assert_eq!(active(&mut tiers, &config, 32), [30]);
+        assert_eq!(active(&mut tiers, &config, 34), [30]);
+        assert!(active(&mut tiers, &config, 35).is_empty());
+        // Once left, the tier needs the capacity to drop below the threshold again
+        assert!(active(&mut tiers, &config, 32).is_empty());
+    }
+
+    #[test]
+    fn unknown_capacity_leaves_every_tier() {
+        let config = [tier(30, 5)];
+        let mut tiers = BatteryTiers::new(false);
+
+        assert_eq!(active(&mut tiers, &config, 10), [30]);
+        assert_eq!(tiers.update(&config, None).count(), 0);
+    }
+
+    #[test]
+    fn hysteresis_does_not_overflow_near_full_capacity() {
+        let config = [tier(100, 255)];
+        let mut tiers = BatteryTiers::new(false);
+
+        assert_eq!(active(&mut tiers, &config, 99), [100]);
+        assert_eq!(active(&mut tiers, &config, 100), [100]);
+    }
+}
@@ -0,0 +1,288 @@
+// Control of the intel_pstate and amd_pstate scaling drivers
+use crate::core::{AmdPstateStatus, IntelPstateStatus};
+use crate::cpu;
+use crate::util::error::ControlError;
+use crate::util::sysfs;
+use log::debug;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub type Result<T, E = ControlError> = std::result::Result<T, E>;
+
+/// Attributes with their current values, `None` for missing attributes
+pub type Knobs = Vec<(&'static str, Option<String>)>;
+
+const INTEL_PSTATE_PATH: &str = "/sys/devices/system/cpu/intel_pstate";
+const AMD_PSTATE_PATH: &str = "/sys/devices/system/cpu/amd_pstate";
+
+/// Attributes of `/sys/devices/system/cpu/intel_pstate` that Watt reports
+const INTEL_PSTATE_KNOBS: &[&str] = &[
+    "status",
+    "min_perf_pct",
+    "max_perf_pct",
+    "no_turbo",
+    "hwp_dynamic_boost",
+    "energy_efficiency",
+    "num_pstates",
+    "turbo_pct",
+];
+
+/// Per-policy attributes of amd_pstate that Watt reports
+const AMD_PSTATE_POLICY_KNOBS: &[&str] = &[
+    "amd_pstate_highest_perf",
+    "amd_pstate_prefcore_ranking",
+    "boost",
+];
+
+/// Governors offered by amd_pstate in active mode, where EPP guides the
+/// firmware's frequency selection
+pub const AMD_PSTATE_ACTIVE_GOVERNORS: &[&str] = &["performance", "powersave"];
+
+/// Whether the intel_pstate driver is loaded, in any mode
+pub fn intel_pstate_available() -> bool {
+    Path::new(INTEL_PSTATE_PATH).exists()
+}
+
+/// Current operation mode of intel_pstate, `None` if the driver is not loaded
+pub fn get_intel_pstate_status() -> Option<IntelPstateStatus> {
+    let status = sysfs::read_sysfs_value(Path::new(INTEL_PSTATE_PATH).join("status")).ok()?;
+    match status.as_str() {
+        "active" => Some(IntelPstateStatus::Active),
+        "passive" => Some(IntelPstateStatus::Passive),
+        "off" => Some(IntelPstateStatus::Off),
+        _ => None,
+    }
+}
+
+/// Every intel_pstate attribute Watt knows about with its current value,
+/// `None` for attributes that do not exist in the current mode
+pub fn get_intel_pstate_knobs() -> Knobs {
+    INTEL_PSTATE_KNOBS
+        .iter()
+        .map(|&knob| {
+            let value = sysfs::read_sysfs_value(Path::new(INTEL_PSTATE_PATH).join(knob)).ok();
+            (knob, value)
+        })
+        .collect()
+}
+
+/// Whether hardware-managed P-states (HWP) are in use. The CPU must support
+/// HWP and it must not have been disabled with `intel_pstate=no_hwp`.
+fn hwp_enabled() -> bool {
+    let supported = fs::read_to_string("/proc/cpuinfo").is_ok_and(|cpuinfo| {
+        cpuinfo
+            .lines()
+            .find(|line| line.starts_with("flags"))
+            .is_some_and(|flags| flags.split_whitespace().any(|flag| flag == "hwp"))
+    });
+    let disabled = fs::read_to_string("/proc/cmdline").is_ok_and(|cmdline| {
+        cmdline
+            .split_whitespace()
+            .filter_map(|arg| arg.strip_prefix("intel_pstate="))
+            .any(|options| options.split(',').any(|option| option == "no_hwp"))
+    });
+    supported && !disabled
+}
+
+/// Path of an intel_pstate attribute, checking that it exists in the current
+/// mode of the driver
+fn knob_path(knob: &str, modes: &[IntelPstateStatus]) -> Result<PathBuf> {
+    let Some(status) = get_intel_pstate_status() else {
+        return Err(ControlError::NotSupported(
+            "intel_pstate driver is not in use on this system".to_string(),
+        ));
+    };
+    if !modes.contains(&status) {
+        return Err(ControlError::NotSupported(format!(
+            "intel_pstate {knob} is not available in {status} mode"
+        )));
+    }
+
+    let path = Path::new(INTEL_PSTATE_PATH).join(knob);
+    if !path.exists() {
+        return Err(ControlError::NotSupported(format!(
+            "intel_pstate {knob} is not supported by this CPU"
+        )));
+    }
+    Ok(path)
+}
+
+fn write_knob(path: &Path, value: &str) -> Result<()> {
+    if sysfs::read_sysfs_value(path).is_ok_and(|current| current == value) {
+        return Ok(());
+    }
+    sysfs::write_sysfs_value(path, value)?;
+    debug!("Set {} to {value}", path.display());
+    Ok(())
+}
+
+/// Switch intel_pstate between active mode, where it selects P-states
+/// itself, passive mode, where it acts as a cpufreq driver for the generic
+/// governors, and off. The driver cannot be turned off while HWP is in use.
+pub fn set_intel_pstate_status(status: IntelPstateStatus) -> Result<()> {
+    let Some(current) = get_intel_pstate_status() else {
+        return Err(ControlError::NotSupported(
+            "intel_pstate driver is not in use on this system".to_string(),
+        ));
+    };
+    if current == status {
+        return Ok(());
+    }
+    if status == IntelPstateStatus::Off && hwp_enabled() {
+        return Err(ControlError::InvalidValueError(
+            "intel_pstate cannot be turned off while hardware P-states (HWP) are enabled"
+                .to_string(),
+        ));
+    }
+
+    sysfs::write_sysfs_value(Path::new(INTEL_PSTATE_PATH).join("status"), status.as_str())?;
+    debug!("Switched intel_pstate from {current} to {status} mode");
+    Ok(())
+}
+
+fn validate_perf_pct(knob: &str, percent: u8) -> Result<()> {
+    if percent > 100 {
+        return Err(ControlError::InvalidValueError(format!(
+            "{knob} ({percent}) must be between 0 and 100"
+        )));
+    }
+    Ok(())
+}
+
+fn read_perf_pct(knob: &str) -> Option<u8> {
+    sysfs::read_sysfs_value(Path::new(INTEL_PSTATE_PATH).join(knob))
+        .ok()?
+        .parse()
+        .ok()
+}
+
+/// Set the lowest P-state intel_pstate may select, in percent of the maximum
+/// performance
+pub fn set_min_perf_pct(percent: u8) -> Result<()> {
+    set_perf_pct(Some(percent), None)
+}
+
+/// Set the highest P-state intel_pstate may select, in percent of the maximum
+/// performance
+pub fn set_max_perf_pct(percent: u8) -> Result<()> {
+    set_perf_pct(None, Some(percent))
+}
+
+/// Set the P-state limits of intel_pstate. The driver clamps each limit to
+/// the other, so they are written in the order that keeps both values.
+pub fn set_perf_pct(min: Option<u8>, max: Option<u8>) -> Result<()> {
+    const MODES: &[IntelPstateStatus] = &[IntelPstateStatus::Active, IntelPstateStatus::Passive];
+
+    if let Some(min) = min {
+        validate_perf_pct("min_perf_pct", min)?;
+    }
+    if let Some(max) = max {
+        validate_perf_pct("max_perf_pct", max)?;
+    }
+
+    let min_path = min.map(|_| knob_path("min_perf_pct", MODES)).transpose()?;
+    let max_path = max.map(|_| knob_path("max_perf_pct", MODES)).transpose()?;
+
+    let new_min = min.or_else(|| read_perf_pct("min_perf_pct"));
+    let new_max = max.or_else(|| read_perf_pct("max_perf_pct"));
+    if let (Some(new_min), Some(new_max)) = (new_min, new_max) {
+        if new_min > new_max {
+            return Err(ControlError::InvalidValueError(format!(
+                "min_perf_pct ({new_min}) cannot be higher than max_perf_pct ({new_max})"
+            )));
+        }
+    }
+
+    // Raising the minimum above the current maximum needs the maximum first
+    let raise_max_first = min.is_some_and(|min| {
+        read_perf_pct("max_perf_pct").is_some_and(|current_max| min > current_max)
+    });
+    let min_write = min_path.zip(min);
+    let max_write = max_path.zip(max);
+    let writes = if raise_max_first {
+        [max_write, min_write]
+    } else {
+        [min_write, max_write]
+    };
+    for (path, percent) in writes.into_iter().flatten() {
+        write_knob(&path, &percent.to_string())?;
+    }
+    Ok(())
+}
+
+/// Let intel_pstate raise the P-state of cores waking up from I/O waits.
+/// Only available in active mode with HWP.
+pub fn set_hwp_dynamic_boost(enabled: bool) -> Result<()> {
+    let path = knob_path("hwp_dynamic_boost", &[IntelPstateStatus::Active])?;
+    write_knob(&path, if enabled { "1" } else { "0" })
+}
+
+/// Prefer energy efficiency over performance in the P-state selection of
+/// some CPU generations
+pub fn set_energy_efficiency(enabled: bool) -> Result<()> {
+    let path = knob_path(
+        "energy_efficiency",
+        &[IntelPstateStatus::Active, IntelPstateStatus::Passive],
+    )?;
+    write_knob(&path, if enabled { "1" } else { "0" })
+}
+
+/// Whether the amd_pstate driver is present, in any mode
+pub fn amd_pstate_available() -> bool {
+    Path::new(AMD_PSTATE_PATH).join("status").exists()
+}
+
+/// Current operation mode of amd_pstate, `None` if the driver is not in use
+pub fn get_amd_pstate_status() -> Option<AmdPstateStatus> {
+    let status = sysfs::read_sysfs_value(Path::new(AMD_PSTATE_PATH).join("status")).ok()?;
+    match status.as_str() {
+        "active" => Some(AmdPstateStatus::Active),
+        "guided" => Some(AmdPstateStatus::Guided),
+        "passive" => Some(AmdPstateStatus::Passive),
+        _ => None,
+    }
+}
+
+/// Switch amd_pstate between active mode, where EPP guides the firmware,
+/// guided mode, where the governor sets a range for the firmware, and
+/// passive mode, where the governor requests frequencies directly
+pub fn set_amd_pstate_status(status: AmdPstateStatus) -> Result<()> {
+    let path = Path::new(AMD_PSTATE_PATH).join("status");
+    if !path.exists() {
+        return Err(ControlError::NotSupported(
+            "amd_pstate driver is not available on this system".to_string(),
+        ));
+    }
+
+    let current = sysfs::read_sysfs_value(&path)?;
+    if current == status.as_str() {
+        return Ok(());
+    }
+    sysfs::write_sysfs_value(&path, status.as_str())?;
+    debug!("Switched amd_pstate from {current} to {status} mode");
+    Ok(())
+}
+
+/// Whether amd_pstate ranks cores for preferred scheduling, `None` if unknown
+pub fn get_amd_pstate_prefcore() -> Option<String> {
+    sysfs::read_sysfs_value(Path::new(AMD_PSTATE_PATH).join("prefcore")).ok()
+}
+
+/// The CPPC attributes of every cpufreq policy, `None` for attributes the
+/// policy does not have
+pub fn get_amd_pstate_policy_knobs() -> Vec<(u32, Knobs)> {
+    cpu::get_policy_ids()
+        .into_iter()
+        .map(|id| {
+            let policy = format!("/sys/devices/system/cpu/cpufreq/policy{id}");
+            let knobs = AMD_PSTATE_POLICY_KNOBS
+                .iter()
+                .map(|&knob| {
+                    let value = sysfs::read_sysfs_value(Path::new(&policy).join(knob)).ok();
+                    (knob, value)
+                })
+                .collect();
+            (id, knobs)
+        })
+        .collect()
+}
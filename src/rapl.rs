@@ -0,0 +1,509 @@
+// Package power limits and energy counters of the RAPL powercap interface
+use crate::restore;
+use crate::util::error::ControlError;
+use crate::util::sysfs;
+use log::debug;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+pub type Result<T, E = ControlError> = std::result::Result<T, E>;
+
+const POWERCAP_PATH: &str = "/sys/class/powercap";
+
+/// A power limit of a RAPL package zone
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PowerLimit {
+    /// PL1, the sustained power limit
+    LongTerm,
+    /// PL2, the limit for short bursts
+    ShortTerm,
+}
+
+impl PowerLimit {
+    /// Name of the constraint in `constraint_N_name`
+    const fn constraint_name(self) -> &'static str {
+        match self {
+            Self::LongTerm => "long_term",
+            Self::ShortTerm => "short_term",
+        }
+    }
+}
+
+/// A constraint of a RAPL zone with its current values
+#[derive(Debug, Clone)]
+pub struct Constraint {
+    pub name: String,
+    pub power_limit_w: Option<f32>,
+    pub time_window_sec: Option<f32>,
+}
+
+/// The constraints of a RAPL package zone
+#[derive(Debug, Clone)]
+pub struct PackageLimits {
+    /// Zone name, e.g. `package-0`
+    pub name: String,
+    pub enabled: bool,
+    pub constraints: Vec<Constraint>,
+}
+
+/// Package zones, e.g. `/sys/class/powercap/intel-rapl:0`. AMD CPUs expose
+/// their RAPL interface under the same name.
+fn package_zones() -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(POWERCAP_PATH) else {
+        return Vec::new();
+    };
+
+    let mut zones: Vec<PathBuf> = entries
+        .flatten()
+        .filter(|entry| {
+            let name = entry.file_name();
+            // Subzones such as `intel-rapl:0:0` are core, uncore and DRAM
+            name.to_str()
+                .and_then(|name| name.strip_prefix("intel-rapl:"))
+                .is_some_and(|index| !index.contains(':'))
+        })
+        .map(|entry| entry.path())
+        .filter(|zone| read_trimmed(&zone.join("name")).is_some_and(|n| n.starts_with("package")))
+        .collect();
+    zones.sort();
+    zones
+}
+
+fn read_trimmed(path: &Path) -> Option<String> {
+    sysfs::read_sysfs_value(path).ok()
+}
+
+fn read_u64(path: &Path) -> Option<u64> {
+    read_trimmed(path)?.parse().ok()
+}
+
+/// Indices of the constraints of a zone, from its `constraint_N_name` files
+fn constraint_indices(zone: &Path) -> Vec<(usize, String)> {
+    (0..)
+        .map_while(|index| {
+            let name = read_trimmed(&zone.join(format!("constraint_{index}_name")))?;
+            Some((index, name))
+        })
+        .collect()
+}
+
+/// Whether the system has RAPL package zones
+pub fn rapl_available() -> bool {
+    !package_zones().is_empty()
+}
+
+/// Current power limits of every package zone
+pub fn get_package_power_limits() -> Vec<PackageLimits> {
+    package_zones()
+        .into_iter()
+        .map(|zone| {
+            let constraints = constraint_indices(&zone)
+                .into_iter()
+                .map(|(index, name)| Constraint {
+                    name,
+                    power_limit_w: read_u64(
+                        &zone.join(format!("constraint_{index}_power_limit_uw")),
+                    )
+                    .map(|uw| uw as f32 / 1_000_000.0),
+                    time_window_sec: read_u64(
+                        &zone.join(format!("constraint_{index}_time_window_us")),
+                    )
+                    .map(|us| us as f32 / 1_000_000.0),
+                })
+                .collect();
+
+            PackageLimits {
+                name: read_trimmed(&zone.join("name")).unwrap_or_default(),
+                enabled: read_trimmed(&zone.join("enabled")).as_deref() == Some("1"),
+                constraints,
+            }
+        })
+        .collect()
+}
+
+/// Write a value, recording the original one so it can be restored
+fn write_recorded(path: &Path, value: &str) -> Result<()> {
+    if read_trimmed(path).as_deref() == Some(value) {
+        return Ok(());
+    }
+    restore::record(path);
+    sysfs::write_sysfs_value(path, value)?;
+    debug!("Set {} to {value}", path.display());
+    Ok(())
+}
+
+/// Set a power limit and optionally its time window on every package zone.
+/// The original values are restored by [`restore_power_limits`] and when the
+/// daemon exits.
+pub fn set_power_limit(limit: PowerLimit, watts: f32, window_sec: Option<f32>) -> Result<()> {
+    if !watts.is_finite() || watts <= 0.0 {
+        return Err(ControlError::InvalidValueError(format!(
+            "Power limit ({watts} W) must be greater than 0"
+        )));
+    }
+    if let Some(window) = window_sec {
+        if !window.is_finite() || window <= 0.0 {
+            return Err(ControlError::InvalidValueError(format!(
+                "Power limit time window ({window} s) must be greater than 0"
+            )));
+        }
+    }
+
+    let zones = package_zones();
+    if zones.is_empty() {
+        return Err(ControlError::NotSupported(format!(
+            "No RAPL package zones found in {POWERCAP_PATH}"
+        )));
+    }
+
+    let power_uw = (f64::from(watts) * 1_000_000.0).round() as u64;
+    for zone in zones {
+        let Some((index, _)) = constraint_indices(&zone)
+            .into_iter()
+            .find(|(_, name)| name == limit.constraint_name())
+        else {
+            return Err(ControlError::NotSupported(format!(
+                "{} has no {} power limit",
+                zone.display(),
+                limit.constraint_name()
+            )));
+        };
+
+        let max_uw =
+            read_u64(&zone.join(format!("constraint_{index}_max_power_uw"))).filter(|&max| max > 0);
+        if let Some(max_uw) = max_uw {
+            if power_uw > max_uw {
+                return Err(ControlError::InvalidValueError(format!(
+                    "Power limit ({watts} W) exceeds the maximum of {} W for {}",
+                    max_uw as f32 / 1_000_000.0,
+                    zone.display()
+                )));
+            }
+        }
+
+        write_recorded(
+            &zone.join(format!("constraint_{index}_power_limit_uw")),
+            &power_uw.to_string(),
+        )?;
+        if let Some(window) = window_sec {
+            let window_us = (f64::from(window) * 1_000_000.0).round() as u64;
+            write_recorded(
+                &zone.join(format!("constraint_{index}_time_window_us")),
+                &window_us.to_string(),
+            )?;
+        }
+        // Limits of a disabled zone are not enforced
+        write_recorded(&zone.join("enabled"), "1")?;
+    }
+
+    Ok(())
+}
+
+/// Restore one power limit and its time window on every package zone,
+/// returning how many values were restored
+pub fn restore_power_limit(limit: PowerLimit) -> usize {
+    restore::restore_where(|path| {
+        let (Some(zone), Some(file)) = (path.parent(), path.file_name().and_then(|f| f.to_str()))
+        else {
+            return false;
+        };
+        path.starts_with(POWERCAP_PATH)
+            && constraint_indices(zone).into_iter().any(|(index, name)| {
+                name == limit.constraint_name() && file.starts_with(&format!("constraint_{index}_"))
+            })
+    })
+}
+
+/// Restore every power limit changed by [`set_power_limit`], returning how
+/// many values were restored
+pub fn restore_power_limits() -> usize {
+    restore::restore_where(|path| path.starts_with(POWERCAP_PATH))
+}
+
+/// Power domain of an energy counter
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EnergyDomain {
+    Package,
+    Core,
+    Uncore,
+    Dram,
+}
+
+impl EnergyDomain {
+    /// Domain of a RAPL zone from its name, e.g. `package-0` or `core`
+    fn from_zone_name(name: &str) -> Option<Self> {
+        match name {
+            _ if name.starts_with("package") => Some(Self::Package),
+            "core" => Some(Self::Core),
+            "uncore" => Some(Self::Uncore),
+            "dram" => Some(Self::Dram),
+            _ => None,
+        }
+    }
+
+    /// Domain of an `amd_energy` hwmon channel from its label, e.g. `Esocket0`
+    fn from_amd_energy_label(label: &str) -> Option<Self> {
+        if label.starts_with("Esocket") {
+            Some(Self::Package)
+        } else if label.starts_with("Ecore") {
+            Some(Self::Core)
+        } else {
+            None
+        }
+    }
+}
+
+/// A reading of an energy counter in microjoules
+#[derive(Debug, Clone)]
+struct EnergyCounter {
+    domain: EnergyDomain,
+    path: PathBuf,
+    energy_uj: u64,
+    /// Value at which the counter wraps around to 0, if known
+    max_energy_uj: Option<u64>,
+}
+
+/// Readings of every CPU energy counter at one point in time
+#[derive(Debug, Clone)]
+pub struct EnergySample {
+    counters: Vec<EnergyCounter>,
+    taken: Instant,
+}
+
+/// Average power of the CPU power domains between two energy samples
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CpuPower {
+    pub package_watts: Option<f32>,
+    pub core_watts: Option<f32>,
+    pub uncore_watts: Option<f32>,
+    pub dram_watts: Option<f32>,
+}
+
+/// Read the energy counters of the RAPL zones, or of the `amd_energy` hwmon
+/// driver on AMD systems without RAPL zones. Counters are usually only
+/// readable by root.
+pub fn sample_energy() -> EnergySample {
+    let mut counters = rapl_energy_counters();
+    if counters.is_empty() {
+        counters = amd_energy_counters();
+    }
+
+    EnergySample {
+        counters,
+        taken: Instant::now(),
+    }
+}
+
+/// Energy counters of every RAPL zone and subzone
+fn rapl_energy_counters() -> Vec<EnergyCounter> {
+    let Ok(entries) = fs::read_dir(POWERCAP_PATH) else {
+        return Vec::new();
+    };
+
+    entries
+        .flatten()
+        .filter(|entry| {
+            // `intel-rapl-mmio` zones duplicate the package zones
+            entry
+                .file_name()
+                .to_str()
+                .is_some_and(|name| name.starts_with("intel-rapl:"))
+        })
+        .filter_map(|entry| {
+            let zone = entry.path();
+            let domain = EnergyDomain::from_zone_name(&read_trimmed(&zone.join("name"))?)?;
+            let path = zone.join("energy_uj");
+            Some(EnergyCounter {
+                domain,
+                energy_uj: read_u64(&path)?,
+                max_energy_uj: read_u64(&zone.join("max_energy_range_uj")),
+                path,
+            })
+        })
+        .collect()
+}
+
+/// Energy counters of the `amd_energy` hwmon driver
+fn amd_energy_counters() -> Vec<EnergyCounter> {
+    let Ok(entries) = fs::read_dir("/sys/class/hwmon") else {
+        return Vec::new();
+    };
+    let Some(hwmon) = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .find(|hwmon| read_trimmed(&hwmon.join("name")).as_deref() == Some("amd_energy"))
+    else {
+        return Vec::new();
+    };
+
+    (1..)
+        .map_while(|channel| {
+            let label = read_trimmed(&hwmon.join(format!("energy{channel}_label")))?;
+            let path = hwmon.join(format!("energy{channel}_input"));
+            Some((label, path))
+        })
+        .filter_map(|(label, path)| {
+            Some(EnergyCounter {
+                domain: EnergyDomain::from_amd_energy_label(&label)?,
+                energy_uj: read_u64(&path)?,
+                // The driver accumulates the counters into 64 bits
+                max_energy_uj: None,
+                path,
+            })
+        })
+        .collect()
+}
+
+impl EnergySample {
+    /// Average power of each domain since an earlier sample, summed across
+    /// packages. Domains without counters are `None`.
+    pub fn power_since(&self, earlier: &Self) -> CpuPower {
+        let seconds = self.taken.duration_since(earlier.taken).as_secs_f64();
+        if seconds <= 0.0 {
+            return CpuPower::default();
+        }
+
+        let watts = |domain: EnergyDomain| -> Option<f32> {
+            let mut total_uj: Option<u64> = None;
+            for counter in self.counters.iter().filter(|c| c.domain == domain) {
+                let Some(previous) = earlier.counters.iter().find(|c| c.path == counter.path)
+                else {
+                    continue;
+                };
+                let consumed = if counter.energy_uj >= previous.energy_uj {
+                    counter.energy_uj - previous.energy_uj
+                } else if let Some(max) = counter.max_energy_uj {
+                    // The counter wrapped around since the earlier sample
+                    max.saturating_sub(previous.energy_uj) + counter.energy_uj
+                } else {
+                    continue;
+                };
+                *total_uj.get_or_insert(0) += consumed;
+            }
+            total_uj.map(|uj| (uj as f64 / 1_000_000.0 / seconds) as f32)
+        };
+
+        CpuPower {
+            package_watts: watts(EnergyDomain::Package),
+            core_watts: watts(EnergyDomain::Core),
+            uncore_watts: watts(EnergyDomain::Uncore),
+            dram_watts: watts(EnergyDomain::Dram),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn counter(
+        domain: EnergyDomain,
+        zone: &str,
+        energy_uj: u64,
+        max: Option<u64>,
+    ) -> EnergyCounter {
+        EnergyCounter {
+            domain,
+            path: Path::new(POWERCAP_PATH).join(zone).join("energy_uj"),
+            energy_uj,
+            max_energy_uj: max,
+        }
+    }
+
+    /// Two samples taken two seconds apart
+    fn samples(
+        earlier: Vec<EnergyCounter>,
+        later: Vec<EnergyCounter>,
+    ) -> (EnergySample, EnergySample) {
+        let taken = Instant::now();
+        (
+            EnergySample {
+                counters: earlier,
+                taken,
+            },
+            EnergySample {
+                counters: later,
+                taken: taken + Duration::from_secs(2),
+            },
+        )
+    }
+
+    #[test]
+    fn power_is_energy_over_time() {
+        let (earlier, later) = samples(
+            vec![counter(
+                EnergyDomain::Package,
+                "intel-rapl:0",
+                1_000_000,
+                None,
+            )],
+            vec![counter(
+                EnergyDomain::Package,
+                "intel-rapl:0",
+                21_000_000,
+                None,
+            )],
+        );
+        let power = later.power_since(&earlier);
+        assert_eq!(power.package_watts, Some(10.0));
+        assert_eq!(power.core_watts, None);
+    }
+
+    #[test]
+    fn wrapped_counters_count_the_energy_up_to_their_maximum() {
+        let max = Some(100_000_000);
+        let (earlier, later) = samples(
+            vec![counter(
+                EnergyDomain::Package,
+                "intel-rapl:0",
+                96_000_000,
+                max,
+            )],
+            vec![counter(
+                EnergyDomain::Package,
+                "intel-rapl:0",
+                4_000_000,
+                max,
+            )],
+        );
+        assert_eq!(later.power_since(&earlier).package_watts, Some(4.0));
+    }
+
+    #[test]
+    fn wrapped_counters_without_a_maximum_are_skipped() {
+        let (earlier, later) = samples(
+            vec![counter(
+                EnergyDomain::Package,
+                "intel-rapl:0",
+                96_000_000,
+                None,
+            )],
+            vec![counter(
+                EnergyDomain::Package,
+                "intel-rapl:0",
+                4_000_000,
+                None,
+            )],
+        );
+        assert_eq!(later.power_since(&earlier).package_watts, None);
+    }
+
+    #[test]
+    fn counters_of_a_domain_are_summed_across_packages() {
+        let (earlier, later) = samples(
+            vec![
+                counter(EnergyDomain::Package, "intel-rapl:0", 0, None),
+                counter(EnergyDomain::Package, "intel-rapl:1", 0, None),
+            ],
+            vec![
+                counter(EnergyDomain::Package, "intel-rapl:0", 6_000_000, None),
+                counter(EnergyDomain::Package, "intel-rapl:1", 2_000_000, None),
+                // Not in the earlier sample
+                counter(EnergyDomain::Package, "intel-rapl:2", 9_000_000, None),
+            ],
+        );
+        assert_eq!(later.power_since(&earlier).package_watts, Some(4.0));
+    }
+}
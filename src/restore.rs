@@ -0,0 +1,238 @@
+// Restoring hardware settings changed by Watt when the daemon exits
+use crate::util::sysfs;
+use log::{debug, warn};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex, MutexGuard};
+
+/// File the original values are kept in, so that they outlive the process
+/// that recorded them. A daemon started after `watt profile set` parked cores
+/// still restores them when it exits. `/run` is cleared on reboot, together
+/// with the changed settings.
+pub const ORIGINAL_VALUES_PATH: &str = "/run/watt/original_values";
+
+/// Values sysfs files had before Watt first changed them
+static ORIGINAL_VALUES: LazyLock<Mutex<BTreeMap<PathBuf, String>>> =
+    LazyLock::new(|| Mutex::new(load_original_values()));
+
+/// Values sysfs files had before the running transaction first wrote them, in
+/// write order. `None` while no transaction is running.
+static JOURNAL: Mutex<Option<Vec<(PathBuf, String)>>> = Mutex::new(None);
+
+fn original_values() -> MutexGuard<'static, BTreeMap<PathBuf, String>> {
+    // The map stays consistent even if a thread panicked while holding the lock
+    ORIGINAL_VALUES
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
+fn load_original_values() -> BTreeMap<PathBuf, String> {
+    let contents = match fs::read_to_string(ORIGINAL_VALUES_PATH) {
+        Ok(contents) => contents,
+        Err(e) => {
+            if e.kind() != io::ErrorKind::NotFound {
+                warn!("Failed to read {ORIGINAL_VALUES_PATH}: {e}");
+            }
+            return BTreeMap::new();
+        }
+    };
+
+    match toml::from_str::<BTreeMap<PathBuf, String>>(&contents) {
+        Ok(values) => {
+            debug!(
+                "Loaded {} original values from {ORIGINAL_VALUES_PATH}",
+                values.len()
+            );
+            values
+        }
+        Err(e) => {
+            warn!("Ignoring malformed {ORIGINAL_VALUES_PATH}: {e}");
+            BTreeMap::new()
+        }
+    }
+}
+
+/// Write the original values back to [`ORIGINAL_VALUES_PATH`], removing the
+/// file once nothing is left to restore
+fn save_original_values(values: &BTreeMap<PathBuf, String>) {
+    let path = Path::new(ORIGINAL_VALUES_PATH);
+    let result = if values.is_empty() {
+        fs::remove_file(path).or_else(|e| match e.kind() {
+            io::ErrorKind::NotFound => Ok(()),
+            _ => Err(e),
+        })
+    } else {
+        let contents = toml::to_string(values).map_err(io::Error::other);
+        path.parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|()| fs::write(path, contents?))
+    };
+
+    if let Err(e) = result {
+        warn!("Failed to save original values to {ORIGINAL_VALUES_PATH}: {e}");
+    }
+}
+
+fn journal() -> MutexGuard<'static, Option<Vec<(PathBuf, String)>>> {
+    JOURNAL
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
+/// Remember the current value of a file before it is changed. Only the first
+/// recorded value is kept, so restoring returns to the state before Watt ran,
+/// even across processes.
+pub fn record(path: impl AsRef<Path>) {
+    let path = path.as_ref();
+    if is_recorded(path) {
+        return;
+    }
+
+    match sysfs::read_sysfs_value(path) {
+        Ok(value) => record_value(path, &value),
+        Err(e) => debug!("Not recording original value of {}: {e}", path.display()),
+    }
+}
+
+/// Remember the value to restore a file to, for files whose contents cannot
+/// be written back as read. Only the first recorded value is kept.
+pub fn record_value(path: impl AsRef<Path>, value: &str) {
+    let path = path.as_ref();
+    let mut values = original_values();
+    if !values.contains_key(path) {
+        values.insert(path.to_path_buf(), value.to_string());
+        save_original_values(&values);
+    }
+}
+
+/// Whether the original value of a file was recorded, i.e. Watt changed it
+pub fn is_recorded(path: impl AsRef<Path>) -> bool {
+    original_values().contains_key(path.as_ref())
+}
+
+/// Restore every recorded file matching `filter` and forget it, returning the
+/// number of files restored
+pub fn restore_where(filter: impl Fn(&Path) -> bool) -> usize {
+    let restored: Vec<(PathBuf, String)> = {
+        let mut values = original_values();
+        let paths: Vec<PathBuf> = values.keys().filter(|p| filter(p)).cloned().collect();
+        let restored: Vec<_> = paths
+            .into_iter()
+            .filter_map(|path| values.remove_entry(&path))
+            .collect();
+        if !restored.is_empty() {
+            save_original_values(&values);
+        }
+        restored
+    };
+
+    let mut count = 0;
+    for (path, value) in restored {
+        let current = sysfs::read_sysfs_value(&path).ok();
+        if current.as_deref() == Some(value.as_str()) {
+            continue;
+        }
+
+        match sysfs::write_sysfs_value(&path, &value) {
+            Ok(()) => {
+                debug!("Restored {} to '{value}'", path.display());
+                count += 1;
+            }
+            Err(e) => warn!("Failed to restore {} to '{value}': {e}", path.display()),
+        }
+    }
+    count
+}
+
+/// Restore every recorded file, e.g. when the daemon exits
+pub fn restore_all() -> usize {
+    restore_where(|_| true)
+}
+
+/// A group of sysfs writes that is reverted as a whole if one of them fails.
+/// While a transaction is running, the value of every file is journaled
+/// before its first write. Dropping the transaction commits it.
+///
+/// The journal only sees the writes of this process. `watt profile set` hands
+/// the profile to a running daemon, but without one it applies the profile
+/// itself, and is not atomic against other processes writing the same files.
+pub struct Transaction {
+    /// Recorded original values when the transaction began
+    original_values: BTreeMap<PathBuf, String>,
+}
+
+impl Transaction {
+    pub fn begin() -> Self {
+        *journal() = Some(Vec::new());
+        Self {
+            original_values: original_values().clone(),
+        }
+    }
+
+    /// Keep every write of the transaction
+    pub fn commit(self) {}
+
+    /// Write the journaled values back in reverse order, returning the number
+    /// of files reverted
+    pub fn rollback(mut self) -> usize {
+        let entries = journal().take().unwrap_or_default();
+        // Files the transaction recorded are back at their original values,
+        // and files it restored are changed again, as before it began
+        {
+            let mut values = original_values();
+            *values = std::mem::take(&mut self.original_values);
+            save_original_values(&values);
+        }
+
+        let mut count = 0;
+        for (path, value) in entries.into_iter().rev() {
+            let current = sysfs::read_sysfs_value(&path).ok();
+            if current.as_deref() == Some(value.as_str()) {
+                continue;
+            }
+
+            match sysfs::write_sysfs_value(&path, &value) {
+                Ok(()) => {
+                    debug!("Rolled back {} to '{value}'", path.display());
+                    count += 1;
+                }
+                Err(e) => warn!("Failed to roll back {} to '{value}': {e}", path.display()),
+            }
+        }
+        count
+    }
+}
+
+impl Drop for Transaction {
+    fn drop(&mut self) {
+        *journal() = None;
+    }
+}
+
+/// Journal the current value of a file about to be written, if a transaction
+/// is running
+pub fn journal_write(path: &Path) {
+    if journal()
+        .as_ref()
+        .is_none_or(|entries| entries.iter().any(|(p, _)| p == path))
+    {
+        return;
+    }
+
+    match sysfs::read_sysfs_value(path) {
+        Ok(value) => journal_value(path, &value),
+        Err(e) => debug!("Not journaling prior value of {}: {e}", path.display()),
+    }
+}
+
+/// Journal the value to write back to a file on rollback, for files whose
+/// contents cannot be written back as read. Only the first value is kept.
+pub fn journal_value(path: &Path, value: &str) {
+    if let Some(entries) = journal().as_mut() {
+        if !entries.iter().any(|(p, _)| p == path) {
+            entries.push((path.to_path_buf(), value.to_string()));
+        }
+    }
+}
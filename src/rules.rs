@@ -0,0 +1,382 @@
+// Condition-based profile selection rules
+use crate::config::{RuleConditions, RuleConfig};
+use crate::core::SystemReport;
+use crate::profile;
+use log::info;
+use std::time::{Duration, Instant};
+
+/// System state that rule conditions are evaluated against
+#[derive(Debug, Clone)]
+pub struct RuleInputs {
+    pub on_ac: bool,
+    /// Average capacity across all batteries
+    pub battery_percent: Option<u8>,
+    /// Average CPU temperature in °C
+    pub temperature: Option<f32>,
+    /// Average CPU usage across all cores
+    pub cpu_usage: Option<f32>,
+    /// Highest busy percentage across all GPUs that report their load
+    pub gpu_busy: Option<f32>,
+    pub load_avg_1min: f32,
+    /// Time since the last detected user activity, only tracked by the daemon
+    pub idle_duration: Option<Duration>,
+    /// Battery discharge rate in %/hour, only tracked by the daemon
+    pub discharge_rate: Option<f32>,
+}
+
+impl RuleInputs {
+    /// Collect the inputs available from a single system report
+    pub fn from_report(report: &SystemReport) -> Self {
+        let usages: Vec<f32> = report
+            .cpu_cores
+            .iter()
+            .filter_map(|core| core.usage_percent)
+            .collect();
+
+        Self {
+            on_ac: profile::is_on_ac_power(report),
+            battery_percent: profile::battery_capacity(report),
+            temperature: report.cpu_global.average_temperature_celsius,
+            cpu_usage: (!usages.is_empty())
+                .then(|| usages.iter().sum::<f32>() / usages.len() as f32),
+            gpu_busy: report
+                .gpus
+                .iter()
+                .filter_map(|gpu| gpu.busy_percent)
+                .max()
+                .map(f32::from),
+            load_avg_1min: report.system_load.load_avg_1min,
+            idle_duration: None,
+            discharge_rate: None,
+        }
+    }
+}
+
+/// Outcome of comparing a single condition against the system state
+pub struct ConditionCheck {
+    /// The condition as written in the configuration, e.g. `battery_below = 20`
+    pub condition: String,
+    /// The observed value, if it is known
+    pub actual: Option<String>,
+    pub holds: bool,
+}
+
+impl RuleConditions {
+    /// Compare every condition that is set against the system state.
+    /// A condition whose value is unknown never holds.
+    pub fn check(&self, inputs: &RuleInputs) -> Vec<ConditionCheck> {
+        let mut checks = Vec::new();
+
+        if let Some(ac) = self.ac {
+            checks.push(ConditionCheck {
+                condition: format!("ac = {ac}"),
+                actual: Some(if inputs.on_ac { "on AC" } else { "on battery" }.to_string()),
+                holds: inputs.on_ac == ac,
+            });
+        }
+
+        let battery = inputs.battery_percent.map(f32::from);
+        let load = Some(inputs.load_avg_1min);
+        let idle = inputs.idle_duration.map(|idle| idle.as_secs_f32());
+
+        let thresholds = [
+            (
+                "battery_below",
+                self.battery_below.map(f32::from),
+                battery,
+                false,
+                "%",
+            ),
+            (
+                "battery_above",
+                self.battery_above.map(f32::from),
+                battery,
+                true,
+                "%",
+            ),
+            (
+                "temp_above",
+                self.temp_above,
+                inputs.temperature,
+                true,
+                "°C",
+            ),
+            (
+                "temp_below",
+                self.temp_below,
+                inputs.temperature,
+                false,
+                "°C",
+            ),
+            ("usage_above", self.usage_above, inputs.cpu_usage, true, "%"),
+            (
+                "usage_below",
+                self.usage_below,
+                inputs.cpu_usage,
+                false,
+                "%",
+            ),
+            (
+                "gpu_busy_above",
+                self.gpu_busy_above,
+                inputs.gpu_busy,
+                true,
+                "%",
+            ),
+            (
+                "gpu_busy_below",
+                self.gpu_busy_below,
+                inputs.gpu_busy,
+                false,
+                "%",
+            ),
+            ("load_above", self.load_above, load, true, ""),
+            ("load_below", self.load_below, load, false, ""),
+            (
+                "discharge_above",
+                self.discharge_above,
+                inputs.discharge_rate,
+                true,
+                "%/h",
+            ),
+        ];
+        for (key, threshold, actual, above, unit) in thresholds {
+            if let Some(threshold) = threshold {
+                checks.push(threshold_check(key, threshold, actual, above, unit));
+            }
+        }
+
+        if let Some(idle_for) = self.idle_for_sec {
+            checks.push(ConditionCheck {
+                condition: format!("idle_for_sec = {idle_for}"),
+                actual: idle.map(|idle| format!("{idle:.0}s")),
+                holds: inputs
+                    .idle_duration
+                    .is_some_and(|idle| idle >= Duration::from_secs(idle_for)),
+            });
+        }
+
+        checks
+    }
+
+    /// Whether every condition holds. A rule without conditions always matches.
+    pub fn holds(&self, inputs: &RuleInputs) -> bool {
+        self.check(inputs).iter().all(|check| check.holds)
+    }
+}
+
+fn threshold_check(
+    key: &str,
+    threshold: f32,
+    actual: Option<f32>,
+    above: bool,
+    unit: &str,
+) -> ConditionCheck {
+    ConditionCheck {
+        condition: format!("{key} = {}", trim_float(threshold)),
+        actual: actual.map(|value| format!("{}{unit}", trim_float(value))),
+        holds: actual.is_some_and(|value| {
+            if above {
+                value > threshold
+            } else {
+                value < threshold
+            }
+        }),
+    }
+}
+
+/// Format a value with at most two decimals and without trailing zeros
+fn trim_float(value: f32) -> String {
+    let formatted = format!("{value:.2}");
+    formatted
+        .trim_end_matches('0')
+        .trim_end_matches('.')
+        .to_string()
+}
+
+/// Debounce state of a single rule
+#[derive(Debug, Default, Clone)]
+struct RuleState {
+    /// When the conditions started holding
+    holding_since: Option<Instant>,
+    /// When the conditions of an active rule stopped holding
+    released_since: Option<Instant>,
+    active: bool,
+}
+
+/// Evaluates rules in priority order, keeping track of debounce durations
+/// across daemon cycles
+pub struct RuleEngine {
+    states: Vec<RuleState>,
+    debounce: bool,
+}
+
+impl RuleEngine {
+    /// A rule engine for the daemon, which honours debounce durations
+    pub const fn new() -> Self {
+        Self {
+            states: Vec::new(),
+            debounce: true,
+        }
+    }
+
+    /// A rule engine for a one-off evaluation, in which matching rules take
+    /// effect immediately
+    pub const fn immediate() -> Self {
+        Self {
+            states: Vec::new(),
+            debounce: false,
+        }
+    }
+
+    /// Evaluate the rules, which must be ordered by priority, and return the
+    /// first active one
+    pub fn evaluate<'a>(
+        &mut self,
+        rules: &'a [RuleConfig],
+        inputs: &RuleInputs,
+        now: Instant,
+    ) -> Option<&'a RuleConfig> {
+        self.states.resize_with(rules.len(), RuleState::default);
+
+        for (rule, state) in rules.iter().zip(&mut self.states) {
+            let debounce = if self.debounce {
+                Duration::from_secs(rule.debounce_sec)
+            } else {
+                Duration::ZERO
+            };
+            let name = rule.name.as_deref().unwrap_or_default();
+
+            if rule.when.holds(inputs) {
+                state.released_since = None;
+                let since = *state.holding_since.get_or_insert(now);
+                if !state.active && now.duration_since(since) >= debounce {
+                    if self.debounce {
+                        info!(
+                            "Rule '{name}' matched, selecting '{}' profile",
+                            rule.profile
+                        );
+                    }
+                    state.active = true;
+                }
+            } else {
+                state.holding_since = None;
+                if state.active {
+                    let since = *state.released_since.get_or_insert(now);
+                    if now.duration_since(since) >= debounce {
+                        if self.debounce {
+                            info!("Rule '{name}' no longer matches");
+                        }
+                        state.active = false;
+                    }
+                }
+            }
+        }
+
+        rules
+            .iter()
+            .zip(&self.states)
+            .find(|(_, state)| state.active)
+            .map(|(rule, _)| rule)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::RuleConditions;
+
+    fn inputs(temperature: f32) -> RuleInputs {
+        RuleInputs {
+            on_ac: true,
+            battery_percent: None,
+            temperature: Some(temperature),
+            cpu_usage: None,
+            gpu_busy: None,
+            load_avg_1min: 0.0,
+            idle_duration: None,
+            discharge_rate: None,
+        }
+    }
+
+    fn hot_rule(debounce_sec: u64) -> RuleConfig {
+        RuleConfig {
+            name: Some("hot".to_string()),
+            profile: "quiet".to_string(),
+            priority: 0,
+            debounce_sec,
+            when: RuleConditions {
+                temp_above: Some(80.0),
+                ..RuleConditions::default()
+            },
+        }
+    }
+
+    #[test]
+    fn rule_activates_only_after_holding_for_the_debounce() {
+        let rules = [hot_rule(10)];
+        let mut engine = RuleEngine::new();
+        let start = Instant::now();
+
+        assert!(engine.evaluate(&rules, &inputs(90.0), start).is_none());
+        let later = start + Duration::from_secs(9);
+        assert!(engine.evaluate(&rules, &inputs(90.0), later).is_none());
+        let later = start + Duration::from_secs(10);
+        assert!(engine.evaluate(&rules, &inputs(90.0), later).is_some());
+    }
+
+    #[test]
+    fn interrupted_conditions_restart_the_debounce() {
+        let rules = [hot_rule(10)];
+        let mut engine = RuleEngine::new();
+        let start = Instant::now();
+
+        engine.evaluate(&rules, &inputs(90.0), start);
+        engine.evaluate(&rules, &inputs(50.0), start + Duration::from_secs(5));
+        let later = start + Duration::from_secs(12);
+        assert!(engine.evaluate(&rules, &inputs(90.0), later).is_none());
+        let later = start + Duration::from_secs(22);
+        assert!(engine.evaluate(&rules, &inputs(90.0), later).is_some());
+    }
+
+    #[test]
+    fn active_rule_is_released_after_the_debounce() {
+        let rules = [hot_rule(10)];
+        let mut engine = RuleEngine::new();
+        let start = Instant::now();
+
+        engine.evaluate(&rules, &inputs(90.0), start);
+        assert!(
+            engine
+                .evaluate(&rules, &inputs(90.0), start + Duration::from_secs(10))
+                .is_some()
+        );
+        let released = start + Duration::from_secs(11);
+        assert!(engine.evaluate(&rules, &inputs(50.0), released).is_some());
+        let released = start + Duration::from_secs(21);
+        assert!(engine.evaluate(&rules, &inputs(50.0), released).is_none());
+    }
+
+    #[test]
+    fn immediate_engine_ignores_the_debounce() {
+        let rules = [hot_rule(60)];
+        let mut engine = RuleEngine::immediate();
+        assert!(
+            engine
+                .evaluate(&rules, &inputs(90.0), Instant::now())
+                .is_some()
+        );
+    }
+
+    #[test]
+    fn unknown_values_never_hold() {
+        let conditions = RuleConditions {
+            temp_above: Some(80.0),
+            ..RuleConditions::default()
+        };
+        let mut unknown = inputs(0.0);
+        unknown.temperature = None;
+        assert!(!conditions.holds(&unknown));
+        assert!(RuleConditions::default().holds(&unknown));
+    }
+}
@@ -0,0 +1,126 @@
+// Time-of-day and weekday schedules
+use crate::config::{AppConfig, ScheduleConfig};
+use jiff::civil::{DateTime, Weekday};
+use jiff::{ToSpan, Zoned};
+use std::time::Duration;
+
+impl ScheduleConfig {
+    /// Whether the schedule's window covers the given local time
+    pub fn is_active(&self, now: DateTime) -> bool {
+        let (from, to, time) = (self.from.0, self.to.0, now.time());
+        let day = now.weekday();
+
+        if from < to {
+            self.runs_on(day) && from <= time && time < to
+        } else {
+            // The window runs past midnight, or for a full day if `from` equals `to`
+            (self.runs_on(day) && time >= from) || (self.runs_on(day.previous()) && time < to)
+        }
+    }
+
+    /// Whether a window starts on the given day
+    fn runs_on(&self, day: Weekday) -> bool {
+        self.days.is_empty() || self.days.iter().any(|d| d.contains(day))
+    }
+}
+
+/// The first schedule that is active at `now`
+pub fn active_schedule<'a>(config: &'a AppConfig, now: &Zoned) -> Option<&'a ScheduleConfig> {
+    config
+        .schedules
+        .iter()
+        .find(|schedule| schedule.is_active(now.datetime()))
+}
+
+/// Time until the next schedule window starts or ends, so the daemon can
+/// apply the change right away rather than at its next poll
+pub fn until_next_boundary(config: &AppConfig, now: &Zoned) -> Option<Duration> {
+    let today = now.date();
+
+    config
+        .schedules
+        .iter()
+        .flat_map(|schedule| [schedule.from.0, schedule.to.0])
+        .flat_map(|time| {
+            // Every boundary occurs at least once within the next day
+            (0..=1).filter_map(move |days| {
+                let date = today.checked_add(days.days()).ok()?;
+                Some(date.to_datetime(time))
+            })
+        })
+        .filter_map(|boundary| boundary.to_zoned(now.time_zone().clone()).ok())
+        .filter_map(|boundary| Duration::try_from(now.duration_until(&boundary)).ok())
+        .filter(|until| !until.is_zero())
+        .min()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{ClockTime, ProfileOverrides, ScheduleDay};
+    use jiff::civil::{date, time};
+
+    fn schedule(days: Vec<ScheduleDay>, from: (i8, i8), to: (i8, i8)) -> ScheduleConfig {
+        ScheduleConfig {
+            name: None,
+            days,
+            from: ClockTime(time(from.0, from.1, 0, 0)),
+            to: ClockTime(time(to.0, to.1, 0, 0)),
+            profile: None,
+            settings: ProfileOverrides::default(),
+        }
+    }
+
+    /// A local time in the week of Monday, 2024-01-01
+    fn at(day: i8, hour: i8, minute: i8) -> DateTime {
+        date(2024, 1, day).at(hour, minute, 0, 0)
+    }
+
+    #[test]
+    fn daytime_window_covers_start_but_not_end() {
+        let work = schedule(vec![ScheduleDay::Weekdays], (9, 0), (17, 0));
+        assert_eq!(at(1, 9, 0).weekday(), Weekday::Monday);
+
+        assert!(!work.is_active(at(1, 8, 59)));
+        assert!(work.is_active(at(1, 9, 0)));
+        assert!(work.is_active(at(1, 16, 59)));
+        assert!(!work.is_active(at(1, 17, 0)));
+        // Saturday
+        assert!(!work.is_active(at(6, 10, 0)));
+    }
+
+    #[test]
+    fn window_past_midnight_belongs_to_the_day_it_starts() {
+        let night = schedule(vec![ScheduleDay::Fri], (22, 0), (6, 0));
+        assert_eq!(at(5, 0, 0).weekday(), Weekday::Friday);
+
+        assert!(night.is_active(at(5, 22, 0)));
+        assert!(night.is_active(at(5, 23, 30)));
+        // Early Saturday is still part of Friday's window
+        assert!(night.is_active(at(6, 3, 0)));
+        assert!(!night.is_active(at(6, 6, 0)));
+        // Early Friday belongs to Thursday's window, which does not run
+        assert!(!night.is_active(at(5, 3, 0)));
+        assert!(!night.is_active(at(6, 22, 0)));
+    }
+
+    #[test]
+    fn equal_start_and_end_cover_a_full_day() {
+        let day = schedule(vec![ScheduleDay::Mon], (8, 0), (8, 0));
+
+        assert!(day.is_active(at(1, 8, 0)));
+        assert!(day.is_active(at(2, 7, 59)));
+        assert!(!day.is_active(at(2, 8, 0)));
+        assert!(!day.is_active(at(1, 7, 59)));
+    }
+
+    #[test]
+    fn empty_days_run_every_day() {
+        let night = schedule(Vec::new(), (22, 0), (6, 0));
+        for day in 1..=7 {
+            assert!(night.is_active(at(day, 23, 0)));
+            assert!(night.is_active(at(day, 1, 0)));
+            assert!(!night.is_active(at(day, 12, 0)));
+        }
+    }
+}
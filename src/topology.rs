@@ -0,0 +1,300 @@
+// Hybrid CPU topology detection (P-cores and E-cores)
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+/// Capacities or maximum frequencies closer together than this fraction of
+/// the highest value belong to the same core type. This keeps favoured cores
+/// of non-hybrid CPUs, which boost slightly higher than the rest, from being
+/// mistaken for a separate core type.
+const CLUSTER_GAP: f32 = 0.1;
+
+/// Type of a core on a hybrid CPU
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CoreType {
+    /// Performance core (Intel P-core, ARM "big" core)
+    Performance,
+    /// Efficiency core (Intel E-core, ARM "LITTLE" core)
+    Efficiency,
+}
+
+impl fmt::Display for CoreType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Performance => write!(f, "P-core"),
+            Self::Efficiency => write!(f, "E-core"),
+        }
+    }
+}
+
+/// Where the core types were detected from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TopologySource {
+    /// `/sys/devices/cpu_core` and `/sys/devices/cpu_atom` on Intel hybrid CPUs
+    HybridPmu,
+    /// `cpu_capacity` reported by the scheduler
+    Capacity,
+    /// `cpuinfo_max_freq` of the cpufreq policies
+    MaxFrequency,
+}
+
+impl fmt::Display for TopologySource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::HybridPmu => write!(f, "cpu_core/cpu_atom"),
+            Self::Capacity => write!(f, "cpu_capacity"),
+            Self::MaxFrequency => write!(f, "cpuinfo_max_freq clusters"),
+        }
+    }
+}
+
+/// Core types of every CPU. On non-hybrid systems all cores are performance
+/// cores and `source` is `None`.
+#[derive(Debug, Clone, Default)]
+pub struct CpuTopology {
+    cores: BTreeMap<u32, CoreType>,
+    pub source: Option<TopologySource>,
+}
+
+impl CpuTopology {
+    /// Whether the CPU has both performance and efficiency cores
+    pub const fn is_hybrid(&self) -> bool {
+        self.source.is_some()
+    }
+
+    /// Type of a core, `None` for cores that were not found
+    pub fn core_type(&self, core_id: u32) -> Option<CoreType> {
+        self.cores.get(&core_id).copied()
+    }
+
+    /// IDs of the cores of a type, in ascending order
+    pub fn cores_of(&self, core_type: CoreType) -> impl Iterator<Item = u32> + '_ {
+        self.cores
+            .iter()
+            .filter(move |(_, t)| **t == core_type)
+            .map(|(id, _)| *id)
+    }
+}
+
+/// Detect the core types, preferring the most explicit source available:
+/// the Intel hybrid PMU devices, then scheduler capacities, and finally
+/// clusters of maximum frequencies across cpufreq policies.
+pub fn detect() -> CpuTopology {
+    let cpus = present_cpus();
+
+    let detected = hybrid_pmu_types()
+        .map(|types| (types, TopologySource::HybridPmu))
+        .or_else(|| {
+            classify(&per_cpu_values(&cpus, "cpu_capacity"))
+                .map(|types| (types, TopologySource::Capacity))
+        })
+        .or_else(|| {
+            classify(&policy_max_frequencies()).map(|types| (types, TopologySource::MaxFrequency))
+        });
+
+    let (types, source) = match detected {
+        Some((types, source)) => (types, Some(source)),
+        None => (BTreeMap::new(), None),
+    };
+
+    // Cores missing from the source, e.g. ones without cpufreq, count as
+    // performance cores so that settings still reach them
+    let cores = cpus
+        .iter()
+        .map(|&id| (id, types.get(&id).copied().unwrap_or(CoreType::Performance)))
+        .collect();
+
+    CpuTopology { cores, source }
+}
+
+/// IDs of every CPU directory under `/sys/devices/system/cpu`. IDs may have
+/// gaps, e.g. when CPUs were removed with hotplug.
+pub fn present_cpus() -> Vec<u32> {
+    let Ok(entries) = fs::read_dir("/sys/devices/system/cpu") else {
+        return Vec::new();
+    };
+
+    let mut cpus: Vec<u32> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name();
+            name.to_str()?.strip_prefix("cpu")?.parse().ok()
+        })
+        .collect();
+    cpus.sort_unstable();
+    cpus
+}
+
+/// Parse a CPU list such as `0-3,8,10-11`, returning `None` if it is malformed
+pub fn parse_cpu_list(list: &str) -> Option<Vec<u32>> {
+    let mut cpus = Vec::new();
+    for range in list.trim().split(',').filter(|range| !range.is_empty()) {
+        let (start, end) = range.split_once('-').unwrap_or((range, range));
+        let (start, end): (u32, u32) = (start.trim().parse().ok()?, end.trim().parse().ok()?);
+        if start > end {
+            return None;
+        }
+        cpus.extend(start..=end);
+    }
+    Some(cpus)
+}
+
+/// Format CPU IDs as a CPU list, e.g. `0-3,8`
+pub fn format_cpu_list(cpus: &[u32]) -> String {
+    let mut sorted = cpus.to_vec();
+    sorted.sort_unstable();
+    sorted.dedup();
+
+    let mut ranges: Vec<(u32, u32)> = Vec::new();
+    for cpu in sorted {
+        match ranges.last_mut() {
+            Some((_, end)) if *end + 1 == cpu => *end = cpu,
+            _ => ranges.push((cpu, cpu)),
+        }
+    }
+
+    ranges
+        .into_iter()
+        .map(|(start, end)| {
+            if start == end {
+                start.to_string()
+            } else {
+                format!("{start}-{end}")
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Core types from the PMU devices Intel hybrid CPUs register
+fn hybrid_pmu_types() -> Option<BTreeMap<u32, CoreType>> {
+    let p_cores = fs::read_to_string("/sys/devices/cpu_core/cpus").ok()?;
+    let e_cores = fs::read_to_string("/sys/devices/cpu_atom/cpus").ok()?;
+
+    let types: BTreeMap<u32, CoreType> = parse_cpu_list(&p_cores)?
+        .into_iter()
+        .map(|id| (id, CoreType::Performance))
+        .chain(
+            parse_cpu_list(&e_cores)?
+                .into_iter()
+                .map(|id| (id, CoreType::Efficiency)),
+        )
+        .collect();
+
+    types
+        .values()
+        .any(|t| *t == CoreType::Efficiency)
+        .then_some(types)
+}
+
+/// A per-CPU value, for the CPUs that expose it
+fn per_cpu_values(cpus: &[u32], file: &str) -> BTreeMap<u32, u32> {
+    cpus.iter()
+        .filter_map(|&id| {
+            let path = format!("/sys/devices/system/cpu/cpu{id}/{file}");
+            let value = fs::read_to_string(path).ok()?.trim().parse().ok()?;
+            Some((id, value))
+        })
+        .collect()
+}
+
+/// `cpuinfo_max_freq` of every cpufreq policy, assigned to its related CPUs
+fn policy_max_frequencies() -> BTreeMap<u32, u32> {
+    let Ok(entries) = fs::read_dir("/sys/devices/system/cpu/cpufreq") else {
+        return BTreeMap::new();
+    };
+
+    let mut frequencies = BTreeMap::new();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let is_policy = entry
+            .file_name()
+            .to_str()
+            .is_some_and(|name| name.starts_with("policy"));
+        if !is_policy {
+            continue;
+        }
+
+        let (Some(related), Some(max_freq)) = (
+            read_trimmed(&path.join("related_cpus")),
+            read_trimmed(&path.join("cpuinfo_max_freq")).and_then(|f| f.parse::<u32>().ok()),
+        ) else {
+            continue;
+        };
+        // `related_cpus` is a space separated list, unlike the PMU lists
+        for id in related
+            .split_whitespace()
+            .filter_map(parse_cpu_list)
+            .flatten()
+        {
+            frequencies.insert(id, max_freq);
+        }
+    }
+    frequencies
+}
+
+fn read_trimmed(path: &Path) -> Option<String> {
+    fs::read_to_string(path).ok().map(|s| s.trim().to_string())
+}
+
+/// Split CPUs into performance and efficiency cores at the largest gap
+/// between their values. Returns `None` if the values are too close together
+/// to form separate core types.
+fn classify(values: &BTreeMap<u32, u32>) -> Option<BTreeMap<u32, CoreType>> {
+    let mut distinct: Vec<u32> = values.values().copied().collect();
+    distinct.sort_unstable();
+    distinct.dedup();
+
+    let highest = *distinct.last()?;
+    let (gap, threshold) = distinct
+        .windows(2)
+        .map(|pair| (pair[1] - pair[0], pair[1]))
+        .max_by_key(|(gap, _)| *gap)?;
+
+    if (gap as f32) < highest as f32 * CLUSTER_GAP {
+        return None;
+    }
+
+    Some(
+        values
+            .iter()
+            .map(|(&id, &value)| {
+                let core_type = if value >= threshold {
+                    CoreType::Performance
+                } else {
+                    CoreType::Efficiency
+                };
+                (id, core_type)
+            })
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn single_cpus_and_ranges_are_expanded() {
+        assert_eq!(parse_cpu_list("0"), Some(vec![0]));
+        assert_eq!(parse_cpu_list("0-3"), Some(vec![0, 1, 2, 3]));
+        assert_eq!(parse_cpu_list("0,2,4-5\n"), Some(vec![0, 2, 4, 5]));
+        assert_eq!(parse_cpu_list("7-7"), Some(vec![7]));
+    }
+
+    #[test]
+    fn empty_lists_have_no_cpus() {
+        assert_eq!(parse_cpu_list(""), Some(vec![]));
+        assert_eq!(parse_cpu_list("\n"), Some(vec![]));
+    }
+
+    #[test]
+    fn malformed_lists_are_rejected() {
+        assert_eq!(parse_cpu_list("3-1"), None);
+        assert_eq!(parse_cpu_list("a"), None);
+        assert_eq!(parse_cpu_list("0-"), None);
+        assert_eq!(parse_cpu_list("-1"), None);
+        assert_eq!(parse_cpu_list("0-2-4"), None);
+    }
+}
@@ -55,6 +55,14 @@ pub enum EngineError {
 
     #[error("Configuration error: {0}")]
     ConfigurationError(String),
+
+    #[error("Failed to set {knob}, rolled back the profile: {source}")]
+    RolledBack {
+        knob: String,
+        source: Box<EngineError>,
+        /// Outcome of every setting of the profile
+        results: Vec<crate::engine::KnobResult>,
+    },
 }
 
 // A unified error type for the entire application
@@ -1,3 +1,4 @@
+use crate::restore;
 use crate::util::error::ControlError;
 use std::{fs, io, path::Path};
 
@@ -16,6 +17,7 @@ use std::{fs, io, path::Path};
 /// - `ControlError::WriteError` for other I/O errors
 pub fn write_sysfs_value(path: impl AsRef<Path>, value: &str) -> Result<(), ControlError> {
     let p = path.as_ref();
+    restore::journal_write(p);
 
     fs::write(p, value).map_err(|e| {
         let error_msg = format!("Path: {:?}, Value: '{}', Error: {}", p.display(), value, e);